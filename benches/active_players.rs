@@ -0,0 +1,163 @@
+//! Compares the sharded active player store with the single locked map it replaced, while event
+//! handlers upsert players on several threads and the population is counted at the same time.
+//!
+//! The binary has no library to link against, so the store and the types it uses are compiled
+//! into the benchmark from their sources.
+#![allow(dead_code)]
+
+#[path = "../src/census/constants.rs"]
+mod constants;
+#[path = "../src/active_players/player.rs"]
+mod player;
+#[path = "../src/active_players/store.rs"]
+mod store;
+
+/// The paths the sources use within the binary
+mod census {
+    pub(crate) use super::constants;
+}
+
+mod active_players {
+    pub use super::player::{ActivePlayer, ActivePlayerHashmap, Vehicle};
+    pub(crate) use super::store;
+}
+
+use active_players::store::{ActivePlayerStore, PopulationCounts, ShardedActivePlayers};
+use active_players::{ActivePlayer, ActivePlayerHashmap};
+use census::constants::{CharacterID, Loadout, WorldID, ZoneID};
+use chrono::{DateTime, Utc};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Roughly the players online on a busy world
+const PLAYERS: u64 = 10_000;
+/// The events each handler thread upserts during one iteration
+const EVENTS_PER_THREAD: u64 = 10_000;
+const LOADOUTS: [Loadout; 4] = [
+    Loadout::VSMAX,
+    Loadout::NCMedic,
+    Loadout::TRHeavyAssault,
+    Loadout::NSEngineer,
+];
+
+/// The store before it was sharded: one lock around every player, and a breakdown that clones
+/// the whole map before counting it
+#[derive(Default)]
+struct LockedActivePlayers {
+    players: Mutex<ActivePlayerHashmap>,
+}
+
+/// The two operations that contend in the process: upserts by the event handlers and counting
+/// the population for a snapshot
+trait Store: Sync {
+    fn upsert(&self, character_id: CharacterID, player: ActivePlayer);
+    fn population(&self) -> usize;
+}
+
+impl Store for LockedActivePlayers {
+    fn upsert(&self, character_id: CharacterID, player: ActivePlayer) {
+        if let Ok(mut players) = self.players.lock() {
+            players.insert(character_id, player);
+        }
+    }
+
+    fn population(&self) -> usize {
+        let Ok(players) = self.players.lock().map(|players| players.clone()) else {
+            return 0;
+        };
+
+        let mut counts = PopulationCounts::new();
+        for player in players.values() {
+            *counts
+                .entry((player.world, player.zone, player.team_id, player.loadout))
+                .or_insert(0) += 1;
+        }
+        counts.len()
+    }
+}
+
+impl Store for ShardedActivePlayers {
+    fn upsert(&self, character_id: CharacterID, player: ActivePlayer) {
+        ActivePlayerStore::upsert(self, character_id, player);
+    }
+
+    fn population(&self) -> usize {
+        self.counts().len()
+    }
+}
+
+fn player(event: u64, timestamp: DateTime<Utc>) -> ActivePlayer {
+    #[allow(clippy::cast_possible_truncation)]
+    let zone = ZoneID((event % 4 * 2 + 2) as u32);
+    #[allow(clippy::cast_possible_truncation)]
+    let loadout = LOADOUTS[(event % LOADOUTS.len() as u64) as usize];
+
+    ActivePlayer::from_loadout(WorldID::Miller, zone, loadout, timestamp)
+}
+
+fn populate(store: &impl Store, timestamp: DateTime<Utc>) {
+    for character_id in 0..PLAYERS {
+        store.upsert(character_id, player(character_id, timestamp));
+    }
+}
+
+/// Upsert `EVENTS_PER_THREAD` events on each of `threads` threads while the population is
+/// counted over and over on another thread
+fn simulate_events(store: &impl Store, threads: u64, timestamp: DateTime<Utc>) {
+    let handling = AtomicBool::new(true);
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            while handling.load(Ordering::Relaxed) {
+                std::hint::black_box(store.population());
+            }
+        });
+
+        let handlers: Vec<_> = (0..threads)
+            .map(|thread| {
+                scope.spawn(move || {
+                    for event in 0..EVENTS_PER_THREAD {
+                        let character_id = (thread * EVENTS_PER_THREAD + event) * 7919 % PLAYERS;
+                        store.upsert(character_id, player(event, timestamp));
+                    }
+                })
+            })
+            .collect();
+        for handler in handlers {
+            handler.join().ok();
+        }
+        handling.store(false, Ordering::Relaxed);
+    });
+}
+
+fn event_load(c: &mut Criterion) {
+    let timestamp = Utc::now();
+    let mut group = c.benchmark_group("event_load");
+    group.sample_size(20);
+
+    for threads in [1, 4, 8] {
+        let locked = LockedActivePlayers::default();
+        populate(&locked, timestamp);
+        group.bench_with_input(
+            BenchmarkId::new("mutex", threads),
+            &threads,
+            |b, threads| {
+                b.iter(|| simulate_events(&locked, *threads, timestamp));
+            },
+        );
+
+        let sharded = ShardedActivePlayers::default();
+        populate(&sharded, timestamp);
+        group.bench_with_input(
+            BenchmarkId::new("sharded", threads),
+            &threads,
+            |b, threads| b.iter(|| simulate_events(&sharded, *threads, timestamp)),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, event_load);
+criterion_main!(benches);
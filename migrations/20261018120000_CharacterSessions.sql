@@ -0,0 +1,40 @@
+-- Add migration script here
+BEGIN;
+
+ALTER TABLE public.character_session
+    DROP CONSTRAINT "FK_character_session_character",
+    DROP CONSTRAINT "FK_character_session_outfit";
+
+-- Census character and outfit IDs don't fit in an integer
+ALTER TABLE public."character"
+    ALTER COLUMN character_id TYPE BIGINT;
+
+ALTER TABLE public.outfit
+    ALTER COLUMN outfit_id TYPE BIGINT;
+
+ALTER TABLE public.character_session
+    ALTER COLUMN character_id TYPE BIGINT,
+    ALTER COLUMN outfit_id TYPE BIGINT,
+    ALTER COLUMN character_session_id ADD GENERATED BY DEFAULT AS IDENTITY,
+    ADD COLUMN world_id INTEGER,
+    ADD COLUMN session_length INTERVAL GENERATED ALWAYS AS (session_end - session_start) STORED;
+
+ALTER TABLE public.character_session
+    ADD CONSTRAINT "FK_character_session_character" FOREIGN KEY (character_id)
+        REFERENCES public."character" (character_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    ADD CONSTRAINT "FK_character_session_outfit" FOREIGN KEY (outfit_id)
+        REFERENCES public.outfit (outfit_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    ADD CONSTRAINT "FK_character_session_world" FOREIGN KEY (world_id)
+        REFERENCES public.world (world_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT;
+
+CREATE INDEX idx_character_session_open ON character_session (character_id) WHERE session_end IS NULL;
+
+CREATE INDEX idx_character_session_session_start ON character_session (session_start);
+
+COMMIT;
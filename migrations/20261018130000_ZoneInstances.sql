@@ -0,0 +1,24 @@
+-- Add migration script here
+ALTER TABLE public.zone_population
+    DROP CONSTRAINT "AK_UQ_population_zone",
+    ADD COLUMN instance_id integer NOT NULL DEFAULT 0;
+
+-- Zone IDs used to be stored with the instance packed in the upper 16 bits
+INSERT INTO public.zone (zone_id)
+SELECT DISTINCT zone_id & 65535
+FROM public.zone_population
+WHERE zone_id > 65535
+ON CONFLICT DO NOTHING;
+
+UPDATE public.zone_population
+SET instance_id = zone_id >> 16,
+    zone_id     = zone_id & 65535
+WHERE zone_id > 65535;
+
+DELETE
+FROM public.zone z
+WHERE z.zone_id > 65535
+  AND NOT EXISTS (SELECT 1 FROM public.zone_population zp WHERE zp.zone_id = z.zone_id);
+
+ALTER TABLE public.zone_population
+    ADD CONSTRAINT "AK_UQ_population_zone" UNIQUE (zone_id, instance_id, world_population_id);
@@ -0,0 +1,57 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS public.continent_lock
+(
+    continent_lock_id  integer GENERATED BY DEFAULT AS IDENTITY,
+    world_id           integer                     NOT NULL,
+    zone_id            integer                     NOT NULL,
+    locked             boolean                     NOT NULL,
+    triggering_faction smallint                    NOT NULL,
+    previous_faction   smallint                    NOT NULL,
+    vs_population      smallint                    NOT NULL,
+    nc_population      smallint                    NOT NULL,
+    tr_population      smallint                    NOT NULL,
+    metagame_event_id  smallint                    NOT NULL,
+    "timestamp"        timestamp without time zone NOT NULL,
+    CONSTRAINT "PK_continent_lock" PRIMARY KEY (continent_lock_id),
+    CONSTRAINT "FK_continent_lock_world" FOREIGN KEY (world_id)
+        REFERENCES public.world (world_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    CONSTRAINT "FK_continent_lock_zone" FOREIGN KEY (zone_id)
+        REFERENCES public.zone (zone_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+CREATE INDEX idx_continent_lock_world_zone_timestamp ON continent_lock (world_id, zone_id, "timestamp" DESC);
+
+-- A single metagame event (alert), identified by the instance ID Census gives it on each world
+CREATE TABLE IF NOT EXISTS public.alert
+(
+    alert_id          integer GENERATED BY DEFAULT AS IDENTITY,
+    world_id          integer                     NOT NULL,
+    instance_id       bigint                      NOT NULL,
+    zone_id           integer                     NOT NULL,
+    zone_instance_id  integer                     NOT NULL DEFAULT 0,
+    metagame_event_id smallint                    NOT NULL,
+    state             smallint                    NOT NULL,
+    experience_bonus  real                        NOT NULL,
+    faction_vs        real                        NOT NULL,
+    faction_nc        real                        NOT NULL,
+    faction_tr        real                        NOT NULL,
+    started_at        timestamp without time zone,
+    ended_at          timestamp without time zone,
+    last_update       timestamp without time zone NOT NULL,
+    CONSTRAINT "PK_alert" PRIMARY KEY (alert_id),
+    CONSTRAINT "AK_UQ_alert_instance" UNIQUE (world_id, instance_id),
+    CONSTRAINT "FK_alert_world" FOREIGN KEY (world_id)
+        REFERENCES public.world (world_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    CONSTRAINT "FK_alert_zone" FOREIGN KEY (zone_id)
+        REFERENCES public.zone (zone_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+CREATE INDEX idx_alert_running ON alert (world_id) WHERE ended_at IS NULL;
@@ -0,0 +1,45 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS public.facility
+(
+    facility_id      bigint                      NOT NULL,
+    zone_id          integer                     NOT NULL,
+    name             text                        NOT NULL,
+    facility_type_id smallint                    NOT NULL,
+    last_update      timestamp without time zone NOT NULL,
+    CONSTRAINT "PK_facility" PRIMARY KEY (facility_id),
+    CONSTRAINT "FK_facility_zone" FOREIGN KEY (zone_id)
+        REFERENCES public.zone (zone_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+-- Facilities changing owner or being defended, as reported by FacilityControl. Facilities aren't
+-- referenced, since a capture can be seen before the map is known.
+CREATE TABLE IF NOT EXISTS public.facility_capture
+(
+    facility_capture_id integer GENERATED BY DEFAULT AS IDENTITY,
+    world_id            integer                     NOT NULL,
+    zone_id             integer                     NOT NULL,
+    zone_instance_id    integer                     NOT NULL DEFAULT 0,
+    facility_id         bigint                      NOT NULL,
+    old_faction         smallint                    NOT NULL,
+    new_faction         smallint                    NOT NULL,
+    outfit_id           bigint,
+    duration_held       interval                    NOT NULL,
+    "timestamp"         timestamp without time zone NOT NULL,
+    CONSTRAINT "PK_facility_capture" PRIMARY KEY (facility_capture_id),
+    CONSTRAINT "FK_facility_capture_world" FOREIGN KEY (world_id)
+        REFERENCES public.world (world_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    CONSTRAINT "FK_facility_capture_zone" FOREIGN KEY (zone_id)
+        REFERENCES public.zone (zone_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    CONSTRAINT "FK_facility_capture_outfit" FOREIGN KEY (outfit_id)
+        REFERENCES public.outfit (outfit_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+CREATE INDEX idx_facility_capture_world_timestamp ON facility_capture (world_id, "timestamp" DESC);
@@ -0,0 +1,29 @@
+-- Add migration script here
+-- Kills and deaths per loadout since the previous population, so they can be compared with it
+CREATE TABLE IF NOT EXISTS public.kill_stats
+(
+    kill_stats_id  integer GENERATED BY DEFAULT AS IDENTITY,
+    population_id  integer  NOT NULL,
+    world_id       integer  NOT NULL,
+    zone_id        integer  NOT NULL,
+    faction_id     smallint NOT NULL,
+    loadout_id     smallint NOT NULL,
+    kills          integer  NOT NULL,
+    deaths         integer  NOT NULL,
+    headshots      integer  NOT NULL,
+    window_seconds integer  NOT NULL,
+    CONSTRAINT "PK_kill_stats" PRIMARY KEY (kill_stats_id),
+    CONSTRAINT "AK_UQ_kill_stats" UNIQUE (population_id, world_id, zone_id, faction_id, loadout_id),
+    CONSTRAINT "FK_kill_stats_population" FOREIGN KEY (population_id)
+        REFERENCES public.population (population_id)
+        ON UPDATE RESTRICT
+        ON DELETE CASCADE,
+    CONSTRAINT "FK_kill_stats_world" FOREIGN KEY (world_id)
+        REFERENCES public.world (world_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    CONSTRAINT "FK_kill_stats_zone" FOREIGN KEY (zone_id)
+        REFERENCES public.zone (zone_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
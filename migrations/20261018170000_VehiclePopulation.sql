@@ -0,0 +1,21 @@
+-- Add migration script here
+-- Players per vehicle, stored per zone definition next to the loadout population of a world
+CREATE TABLE IF NOT EXISTS public.vehicle_population
+(
+    vehicle_population_id integer GENERATED BY DEFAULT AS IDENTITY,
+    world_population_id   integer  NOT NULL,
+    zone_id               integer  NOT NULL,
+    team_id               smallint NOT NULL,
+    vehicle_id            smallint NOT NULL,
+    amount                smallint NOT NULL,
+    CONSTRAINT "PK_vehicle_population" PRIMARY KEY (vehicle_population_id),
+    CONSTRAINT "AK_UQ_vehicle_population" UNIQUE (world_population_id, zone_id, team_id, vehicle_id),
+    CONSTRAINT "FK_vehicle_population_world_population" FOREIGN KEY (world_population_id)
+        REFERENCES public.world_population (world_population_id)
+        ON UPDATE RESTRICT
+        ON DELETE CASCADE,
+    CONSTRAINT "FK_vehicle_population_zone" FOREIGN KEY (zone_id)
+        REFERENCES public.zone (zone_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
@@ -0,0 +1,42 @@
+-- Add migration script here
+BEGIN;
+
+-- The outfit of a character as last fetched from Census, `last_update` is when it was fetched.
+-- Characters that aren't in an outfit are stored without one so they aren't fetched again.
+ALTER TABLE public."character"
+    ADD COLUMN outfit_id BIGINT,
+    ADD CONSTRAINT "FK_character_outfit" FOREIGN KEY (outfit_id)
+        REFERENCES public.outfit (outfit_id)
+        ON UPDATE RESTRICT
+        ON DELETE SET NULL;
+
+ALTER TABLE public.outfit
+    ADD COLUMN alias CHARACTER VARYING;
+
+-- Online members per outfit, stored per zone definition next to the loadout population of a world
+CREATE TABLE IF NOT EXISTS public.outfit_population
+(
+    outfit_population_id integer GENERATED BY DEFAULT AS IDENTITY,
+    world_population_id  integer  NOT NULL,
+    zone_id              integer  NOT NULL,
+    outfit_id            bigint   NOT NULL,
+    amount               smallint NOT NULL,
+    CONSTRAINT "PK_outfit_population" PRIMARY KEY (outfit_population_id),
+    CONSTRAINT "AK_UQ_outfit_population" UNIQUE (world_population_id, zone_id, outfit_id),
+    CONSTRAINT "FK_outfit_population_world_population" FOREIGN KEY (world_population_id)
+        REFERENCES public.world_population (world_population_id)
+        ON UPDATE RESTRICT
+        ON DELETE CASCADE,
+    CONSTRAINT "FK_outfit_population_zone" FOREIGN KEY (zone_id)
+        REFERENCES public.zone (zone_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    CONSTRAINT "FK_outfit_population_outfit" FOREIGN KEY (outfit_id)
+        REFERENCES public.outfit (outfit_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+CREATE INDEX idx_outfit_population_outfit ON outfit_population (outfit_id);
+
+COMMIT;
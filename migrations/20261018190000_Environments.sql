@@ -0,0 +1,17 @@
+-- Add migration script here
+BEGIN;
+
+-- The Census environment of a world, PS4 worlds are numbered from 1000 (US) and 2000 (EU) like
+-- `WorldID::environment`
+ALTER TABLE public.world
+    ADD COLUMN environment CHARACTER VARYING GENERATED ALWAYS AS (
+        CASE
+            WHEN world_id BETWEEN 1000 AND 1999 THEN 'ps2ps4us'
+            WHEN world_id BETWEEN 2000 AND 2999 THEN 'ps2ps4eu'
+            ELSE 'ps2'
+        END
+    ) STORED;
+
+CREATE INDEX idx_world_environment ON world (environment);
+
+COMMIT;
@@ -0,0 +1,70 @@
+-- Add migration script here
+BEGIN;
+
+-- Raw snapshots are deleted by the retention job once they are rolled up, which removes the rows
+-- of every level below the snapshot
+ALTER TABLE public.world_population
+    DROP CONSTRAINT "FK_world_population_population",
+    ADD CONSTRAINT "FK_world_population_population" FOREIGN KEY (population_id)
+        REFERENCES public.population (population_id)
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE;
+
+ALTER TABLE public.zone_population
+    DROP CONSTRAINT "FK_zone_population_world_population",
+    ADD CONSTRAINT "FK_zone_population_world_population" FOREIGN KEY (world_population_id)
+        REFERENCES public.world_population (world_population_id)
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE;
+
+ALTER TABLE public.team_population
+    DROP CONSTRAINT "FK_team_zone_population",
+    ADD CONSTRAINT "FK_team_zone_population" FOREIGN KEY (zone_population_id)
+        REFERENCES public.zone_population (zone_population_id)
+        ON UPDATE RESTRICT
+        ON DELETE CASCADE;
+
+ALTER TABLE public.loadout_population
+    DROP CONSTRAINT "FK_loadout_team",
+    ADD CONSTRAINT "FK_loadout_team" FOREIGN KEY (team_population_id)
+        REFERENCES public.team_population (team_population_id)
+        ON UPDATE RESTRICT
+        ON DELETE CASCADE;
+
+-- The population per faction, zone or loadout of a world, rolled up from the snapshots of a
+-- bucket of `resolution` seconds. `zone_id` is NULL for the faction and loadout population of
+-- the whole world. `total` is the sum over every snapshot of the bucket, so the average is
+-- `total / snapshots` of `population_aggregate_world`.
+CREATE TABLE IF NOT EXISTS public.population_aggregate
+(
+    resolution integer           NOT NULL,
+    bucket     timestamp without time zone NOT NULL,
+    world_id   integer           NOT NULL,
+    zone_id    integer,
+    group_by   character varying NOT NULL,
+    key        integer           NOT NULL,
+    total      bigint            NOT NULL,
+    minimum    bigint            NOT NULL,
+    maximum    bigint            NOT NULL,
+    CONSTRAINT "AK_UQ_population_aggregate" UNIQUE NULLS NOT DISTINCT
+        (resolution, bucket, world_id, zone_id, group_by, key)
+) PARTITION BY LIST (resolution);
+
+CREATE TABLE IF NOT EXISTS public.population_aggregate_5m
+    PARTITION OF public.population_aggregate FOR VALUES IN (300);
+CREATE TABLE IF NOT EXISTS public.population_aggregate_hourly
+    PARTITION OF public.population_aggregate FOR VALUES IN (3600);
+CREATE TABLE IF NOT EXISTS public.population_aggregate_daily
+    PARTITION OF public.population_aggregate FOR VALUES IN (86400);
+
+-- The number of snapshots of a world in a bucket of `population_aggregate`
+CREATE TABLE IF NOT EXISTS public.population_aggregate_world
+(
+    resolution integer NOT NULL,
+    bucket     timestamp without time zone NOT NULL,
+    world_id   integer NOT NULL,
+    snapshots  integer NOT NULL,
+    CONSTRAINT "PK_population_aggregate_world" PRIMARY KEY (resolution, bucket, world_id)
+);
+
+COMMIT;
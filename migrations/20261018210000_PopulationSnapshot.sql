@@ -0,0 +1,61 @@
+-- Add migration script here
+BEGIN;
+
+-- Every loadout amount of a snapshot in one row, so reading the population does not join the
+-- zone, team and loadout levels. It is only written alongside the normalized levels when
+-- `database.population_layout` is `flat`, which copies the snapshots it is missing from them.
+CREATE TABLE IF NOT EXISTS public.population_snapshot
+(
+    population_id integer  NOT NULL,
+    "timestamp"   timestamp without time zone NOT NULL,
+    world_id      integer  NOT NULL,
+    zone_id       integer  NOT NULL,
+    instance_id   integer  NOT NULL,
+    team_id       smallint NOT NULL,
+    loadout_id    smallint NOT NULL,
+    amount        smallint NOT NULL,
+    CONSTRAINT "PK_population_snapshot"
+        PRIMARY KEY (world_id, population_id, zone_id, instance_id, team_id, loadout_id),
+    CONSTRAINT "FK_population_snapshot_population" FOREIGN KEY (population_id)
+        REFERENCES public.population (population_id)
+        ON UPDATE NO ACTION
+        ON DELETE CASCADE,
+    CONSTRAINT "FK_population_snapshot_world" FOREIGN KEY (world_id)
+        REFERENCES public.world (world_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    CONSTRAINT "FK_population_snapshot_zone" FOREIGN KEY (zone_id)
+        REFERENCES public.zone (zone_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    CONSTRAINT "FK_population_snapshot_faction" FOREIGN KEY (team_id)
+        REFERENCES public.faction (faction_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT,
+    CONSTRAINT "FK_population_snapshot_loadout" FOREIGN KEY (loadout_id)
+        REFERENCES public.loadout (loadout_id)
+        ON UPDATE RESTRICT
+        ON DELETE RESTRICT
+);
+
+CREATE INDEX IF NOT EXISTS idx_population_snapshot_timestamp
+    ON public.population_snapshot ("timestamp", world_id);
+
+CREATE INDEX IF NOT EXISTS idx_population_snapshot_population_id
+    ON public.population_snapshot (population_id);
+
+-- The latest snapshot of a world is read from `world_population` for both layouts
+CREATE INDEX IF NOT EXISTS idx_world_population_world_population_id
+    ON public.world_population (world_id, population_id);
+
+-- The normalized levels with the columns of `population_snapshot`, so a query can read either
+CREATE OR REPLACE VIEW public.population_loadout AS
+SELECT p.population_id, p."timestamp", wp.world_id, zp.zone_id, zp.instance_id, tp.team_id,
+    lp.loadout_id, lp.amount
+FROM public.population p
+JOIN public.world_population wp ON p.population_id = wp.population_id
+JOIN public.zone_population zp ON wp.world_population_id = zp.world_population_id
+JOIN public.team_population tp ON zp.zone_population_id = tp.zone_population_id
+JOIN public.loadout_population lp ON tp.team_population_id = lp.team_population_id;
+
+COMMIT;
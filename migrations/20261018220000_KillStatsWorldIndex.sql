@@ -0,0 +1,3 @@
+-- Add migration script here
+-- The latest window of each world is read by its population ID
+CREATE INDEX IF NOT EXISTS idx_kill_stats_world_population ON public.kill_stats (world_id, population_id);
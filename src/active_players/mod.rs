@@ -0,0 +1,285 @@
+#![allow(clippy::cast_lossless)]
+pub mod outfits;
+mod player;
+pub mod retention;
+pub mod snapshot;
+pub mod store;
+
+use crate::census::constants::{DefinitionID, ExperienceID, VehicleID, WorldID, ZoneID};
+use crate::census::event::GainExperience;
+use crate::controllers::population::{
+    PopulationAmount, WorldBreakdown, WorldOutfitBreakdown, WorldVehicleBreakdown,
+};
+use crate::kill_stats::{self, KillStatsDb};
+use crate::storage::configuration::{PopulationLayout, WorldConfig};
+use chrono::Utc;
+use metrics::{counter, gauge};
+pub use player::{ActivePlayer, ActivePlayerHashmap, Vehicle};
+use sqlx::{Pool, Postgres};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use store::ActivePlayerStore;
+use tracing::info;
+
+impl From<GainExperience> for ActivePlayer {
+    fn from(event: GainExperience) -> Self {
+        Self {
+            world: event.world_id,
+            zone: event.zone_id,
+            loadout: event.loadout_id,
+            team_id: event.team_id,
+            last_change: event.timestamp,
+            logged_in: false,
+            vehicle: None,
+            outfit: None,
+        }
+    }
+}
+
+pub type ActivePlayerDb = Arc<dyn ActivePlayerStore>;
+
+/// The vehicle a player must be in to gain each of the configured experience IDs
+pub type VehicleExperience = HashMap<ExperienceID, VehicleID>;
+
+/// The worlds and zones to include in the population, everything is included when no worlds are set
+#[derive(Debug, Clone, Default)]
+pub struct TrackedZones {
+    worlds: HashMap<WorldID, Option<HashSet<DefinitionID>>>,
+}
+
+impl From<&[WorldConfig]> for TrackedZones {
+    fn from(worlds: &[WorldConfig]) -> Self {
+        Self {
+            worlds: worlds
+                .iter()
+                .map(|world| {
+                    let zones = world
+                        .zones
+                        .as_ref()
+                        .map(|zones| zones.iter().copied().collect());
+                    (world.id, zones)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TrackedZones {
+    /// Players whose zone isn't known yet are included as long as their world is tracked
+    pub fn is_tracked(&self, world: WorldID, zone: ZoneID) -> bool {
+        if self.worlds.is_empty() {
+            return true;
+        }
+
+        match self.worlds.get(&world) {
+            None => false,
+            Some(None) => true,
+            Some(Some(zones)) => {
+                zone == ZoneID::UNKNOWN || zones.contains(&DefinitionID::from(zone))
+            }
+        }
+    }
+}
+
+pub async fn clean(active_players: ActivePlayerDb) -> Option<()> {
+    let active_players = active_players.clone();
+    loop {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+
+        let removed = active_players.retain_active(Utc::now());
+        info!("Cleaned {removed} active players");
+        counter!("niumside_active_players_cleanups").increment(1);
+    }
+}
+
+pub fn loadout_breakdown(
+    active_players: &ActivePlayerDb,
+    tracked_zones: &TrackedZones,
+) -> WorldBreakdown {
+    let mut loadout_breakdown: WorldBreakdown = HashMap::new();
+    let mut total_players: u32 = 0;
+
+    for ((world, zone, team_id, loadout), amount) in active_players.counts() {
+        if !tracked_zones.is_tracked(world, zone) {
+            continue;
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        let amount = amount.min(u32::from(PopulationAmount::MAX)) as PopulationAmount;
+
+        *loadout_breakdown
+            .entry(world)
+            .or_default()
+            .entry(zone.definition())
+            .or_default()
+            .entry(zone.instance())
+            .or_default()
+            .entry(team_id)
+            .or_default()
+            .entry(loadout)
+            .or_insert(0) += amount;
+
+        total_players += u32::from(amount);
+    }
+
+    gauge!("niumside_active_players").set(f64::from(total_players));
+
+    loadout_breakdown
+}
+
+pub fn vehicle_breakdown(
+    active_players: &ActivePlayerDb,
+    tracked_zones: &TrackedZones,
+) -> WorldVehicleBreakdown {
+    let mut vehicle_breakdown: WorldVehicleBreakdown = HashMap::new();
+
+    for ((world, zone, team_id, vehicle_id), amount) in active_players.vehicle_counts() {
+        if !tracked_zones.is_tracked(world, zone) {
+            continue;
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        let amount = amount.min(u32::from(PopulationAmount::MAX)) as PopulationAmount;
+
+        let vehicle = vehicle_breakdown
+            .entry(world)
+            .or_default()
+            .entry(zone.definition())
+            .or_default()
+            .entry(team_id)
+            .or_default()
+            .entry(vehicle_id)
+            .or_insert(0);
+        *vehicle = vehicle.saturating_add(amount);
+    }
+
+    vehicle_breakdown
+}
+
+pub fn outfit_breakdown(
+    active_players: &ActivePlayerDb,
+    tracked_zones: &TrackedZones,
+) -> WorldOutfitBreakdown {
+    let mut outfit_breakdown: WorldOutfitBreakdown = HashMap::new();
+
+    for ((world, zone, outfit_id), amount) in active_players.outfit_counts() {
+        if !tracked_zones.is_tracked(world, zone) {
+            continue;
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        let amount = amount.min(u32::from(PopulationAmount::MAX)) as PopulationAmount;
+
+        let outfit = outfit_breakdown
+            .entry(world)
+            .or_default()
+            .entry(zone.definition())
+            .or_default()
+            .entry(outfit_id)
+            .or_insert(0);
+        *outfit = outfit.saturating_add(amount);
+    }
+
+    outfit_breakdown
+}
+
+pub async fn process_loop(
+    active_players: ActivePlayerDb,
+    kill_stats: KillStatsDb,
+    db_pool: Pool<Postgres>,
+    tracked_zones: TrackedZones,
+    population_layout: PopulationLayout,
+) -> Option<()> {
+    let active_players = active_players.clone();
+    let db_pool = db_pool.clone();
+    loop {
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        let loadout_breakdown_numbers = loadout_breakdown(&active_players, &tracked_zones);
+        let vehicle_breakdown_numbers = vehicle_breakdown(&active_players, &tracked_zones);
+        let outfit_breakdown_numbers = outfit_breakdown(&active_players, &tracked_zones);
+        let kill_window = kill_stats::update(&kill_stats, |kill_stats| kill_stats.take(Utc::now()));
+        let population_id = snapshot::store(
+            &loadout_breakdown_numbers,
+            &vehicle_breakdown_numbers,
+            &outfit_breakdown_numbers,
+            population_layout,
+            &db_pool,
+        )
+        .await;
+        // The kill stats refer to the population, so they are lost with a skipped snapshot
+        if let (Some(kill_window), Some(population_id)) = (kill_window, population_id) {
+            kill_stats::store(&kill_window, population_id, &tracked_zones, &db_pool).await;
+        }
+        counter!("niumside_process_loop_iterations").increment(1);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::constants::{Faction, InstanceID, Loadout};
+    use chrono::DateTime;
+
+    fn timestamp(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn test_tracked_zones() {
+        let tracked_zones = TrackedZones::from(
+            [
+                WorldConfig {
+                    id: WorldID::Miller,
+                    zones: None,
+                },
+                WorldConfig {
+                    id: WorldID::Jaeger,
+                    zones: Some(vec![DefinitionID(2), DefinitionID(344)]),
+                },
+            ]
+            .as_slice(),
+        );
+
+        assert!(tracked_zones.is_tracked(WorldID::Miller, ZoneID(96)));
+        assert!(tracked_zones.is_tracked(WorldID::Jaeger, ZoneID(2)));
+        // Instance 5 of zone definition 344 is matched on its definition
+        assert!(tracked_zones.is_tracked(WorldID::Jaeger, ZoneID(0x0005_0158)));
+        assert!(tracked_zones.is_tracked(WorldID::Jaeger, ZoneID::UNKNOWN));
+        assert!(!tracked_zones.is_tracked(WorldID::Jaeger, ZoneID(4)));
+        assert!(!tracked_zones.is_tracked(WorldID::Cobalt, ZoneID(2)));
+        assert!(TrackedZones::default().is_tracked(WorldID::Cobalt, ZoneID(2)));
+    }
+
+    #[test]
+    fn test_loadout_breakdown_groups_instances_by_definition() {
+        let active_players: ActivePlayerDb = Arc::new(store::ShardedActivePlayers::default());
+        // Two instances of Koltyr and one player on Indar
+        for (character_id, zone) in [(1, 0x0001_000E), (2, 0x0002_000E), (3, 0x0002_000E), (4, 2)] {
+            active_players.upsert(
+                character_id,
+                ActivePlayer::from_loadout(
+                    WorldID::Miller,
+                    ZoneID(zone),
+                    Loadout::VSMAX,
+                    timestamp(0),
+                ),
+            );
+        }
+
+        let breakdown = loadout_breakdown(&active_players, &TrackedZones::default());
+        let miller = &breakdown[&WorldID::Miller];
+
+        assert_eq!(miller.len(), 2);
+        let koltyr = &miller[&DefinitionID(14)];
+        assert_eq!(koltyr[&InstanceID(1)][&Faction::VS][&Loadout::VSMAX], 1);
+        assert_eq!(koltyr[&InstanceID(2)][&Faction::VS][&Loadout::VSMAX], 2);
+        assert_eq!(
+            miller[&DefinitionID(2)][&InstanceID(0)][&Faction::VS][&Loadout::VSMAX],
+            1
+        );
+    }
+}
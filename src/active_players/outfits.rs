@@ -0,0 +1,364 @@
+use crate::active_players::ActivePlayerDb;
+use crate::census::constants::{CharacterID, Environment, OutfitID};
+use crate::census::rest::client::CensusRestClient;
+use crate::census::rest::outfit::{self, OutfitMember, MAX_IDS_PER_REQUEST};
+use crate::character_sessions::{self, SessionEvent, SessionSender};
+use crate::controllers::outfit as outfit_controller;
+use chrono::{DateTime, Utc};
+use metrics::{counter, gauge};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use strum::IntoEnumIterator;
+use tracing::{debug, error};
+
+/// Hours after which the outfit of a character is fetched again, in case they left or joined one
+const MEMBERSHIP_REFRESH_HOURS: i64 = 24;
+/// Hours after which the name and alias of an outfit are fetched again
+const OUTFIT_REFRESH_HOURS: i64 = 24 * 7;
+/// Seconds between looking up the outfits of players that weren't resolved yet
+const RESOLVE_INTERVAL_SECONDS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Membership {
+    outfit_id: Option<OutfitID>,
+    fetched: DateTime<Utc>,
+}
+
+/// The outfits of characters that were seen recently, so they don't need to be looked up in the
+/// database or Census on every pass
+#[derive(Debug, Default)]
+pub struct OutfitCache {
+    memberships: HashMap<CharacterID, Membership>,
+}
+
+impl OutfitCache {
+    fn is_current(membership: &Membership, now: DateTime<Utc>) -> bool {
+        membership.fetched + chrono::Duration::hours(MEMBERSHIP_REFRESH_HOURS) > now
+    }
+
+    /// The characters whose outfit isn't known or was fetched too long ago
+    pub fn unresolved(
+        &self,
+        character_ids: &[CharacterID],
+        now: DateTime<Utc>,
+    ) -> Vec<CharacterID> {
+        character_ids
+            .iter()
+            .filter(|character_id| {
+                self.memberships
+                    .get(character_id)
+                    .is_none_or(|membership| !Self::is_current(membership, now))
+            })
+            .copied()
+            .collect()
+    }
+
+    pub fn insert(
+        &mut self,
+        character_id: CharacterID,
+        outfit_id: Option<OutfitID>,
+        fetched: DateTime<Utc>,
+    ) {
+        self.memberships
+            .insert(character_id, Membership { outfit_id, fetched });
+    }
+
+    /// The outfit of a character, `None` when it isn't known
+    fn membership(&self, character_id: CharacterID) -> Option<Membership> {
+        self.memberships.get(&character_id).copied()
+    }
+
+    /// Forget the memberships that would be fetched again anyway
+    pub fn prune(&mut self, now: DateTime<Utc>) {
+        self.memberships
+            .retain(|_, membership| Self::is_current(membership, now));
+    }
+
+    pub fn len(&self) -> usize {
+        self.memberships.len()
+    }
+}
+
+/// Census only returns the characters that are in an outfit, the others are stored without one
+fn memberships(
+    character_ids: &[CharacterID],
+    members: &[OutfitMember],
+) -> Vec<(CharacterID, Option<OutfitID>)> {
+    let outfits: HashMap<CharacterID, OutfitID> = members
+        .iter()
+        .map(|member| (member.character_id, member.outfit_id))
+        .collect();
+
+    character_ids
+        .iter()
+        .map(|character_id| (*character_id, outfits.get(character_id).copied()))
+        .collect()
+}
+
+async fn fetch_outfits(
+    outfit_ids: &[OutfitID],
+    db_pool: &PgPool,
+    census_rest_client: &CensusRestClient,
+    now: DateTime<Utc>,
+) {
+    let fetched_after = (now - chrono::Duration::hours(OUTFIT_REFRESH_HOURS)).naive_utc();
+    let outdated =
+        match outfit_controller::get_outdated_outfits(db_pool, outfit_ids, fetched_after).await {
+            Ok(outdated) => outdated,
+            Err(e) => {
+                error!("Failed to check which outfits are outdated: {e}");
+                return;
+            }
+        };
+
+    for outfit_ids in outdated.chunks(MAX_IDS_PER_REQUEST) {
+        let outfits = match outfit::get_outfits(census_rest_client, outfit_ids).await {
+            Ok(outfits) => outfits,
+            Err(e) => {
+                counter!("niumside_outfit_requests_failed").increment(1);
+                error!("Error while requesting outfits: {e}");
+                continue;
+            }
+        };
+
+        if let Err(e) = outfit_controller::upsert_outfits(db_pool, &outfits, now.naive_utc()).await
+        {
+            error!("Failed to store outfits: {e}");
+        }
+    }
+}
+
+async fn fetch_memberships(
+    cache: &mut OutfitCache,
+    character_ids: &[CharacterID],
+    db_pool: &PgPool,
+    census_rest_client: &CensusRestClient,
+    now: DateTime<Utc>,
+) {
+    let members = match outfit::get_outfit_members(census_rest_client, character_ids).await {
+        Ok(members) => members,
+        Err(e) => {
+            counter!("niumside_outfit_requests_failed").increment(1);
+            error!("Error while requesting outfit members: {e}");
+            return;
+        }
+    };
+
+    let outfit_ids: Vec<OutfitID> = members
+        .iter()
+        .map(|member| member.outfit_id)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    fetch_outfits(&outfit_ids, db_pool, census_rest_client, now).await;
+
+    let memberships = memberships(character_ids, &members);
+    if let Err(e) =
+        outfit_controller::store_memberships(db_pool, &memberships, now.naive_utc()).await
+    {
+        error!("Failed to store outfit memberships: {e}");
+    }
+
+    for (character_id, outfit_id) in memberships {
+        cache.insert(character_id, outfit_id, now);
+    }
+}
+
+/// Put every active player whose outfit is known in their outfit, and record it on their session
+fn apply_memberships(
+    cache: &OutfitCache,
+    character_ids: &[CharacterID],
+    active_players: &ActivePlayerDb,
+    character_sessions: &SessionSender,
+) {
+    for character_id in character_ids {
+        let Some(membership) = cache.membership(*character_id) else {
+            continue;
+        };
+
+        active_players.set_outfit(*character_id, membership.outfit_id);
+        if let Some(outfit_id) = membership.outfit_id {
+            character_sessions::send(
+                character_sessions,
+                SessionEvent::Outfit {
+                    character_id: *character_id,
+                    outfit_id,
+                },
+            );
+        }
+    }
+}
+
+/// Look up the outfits of the active players that aren't known yet, first in the database and
+/// then in Census, and put every active player in their outfit
+async fn resolve(
+    cache: &mut OutfitCache,
+    active_players: &ActivePlayerDb,
+    character_sessions: &SessionSender,
+    db_pool: &PgPool,
+    census_rest_client: &CensusRestClient,
+) {
+    let now = Utc::now();
+    cache.prune(now);
+
+    let characters = active_players.character_ids();
+    let character_ids: Vec<CharacterID> = characters
+        .iter()
+        .map(|(character_id, _)| *character_id)
+        .collect();
+    let unresolved = cache.unresolved(&character_ids, now);
+
+    if !unresolved.is_empty() {
+        let fetched_after = (now - chrono::Duration::hours(MEMBERSHIP_REFRESH_HOURS)).naive_utc();
+        match outfit_controller::get_memberships(db_pool, &unresolved, fetched_after).await {
+            Ok(stored) => {
+                for membership in stored {
+                    cache.insert(
+                        membership.character_id,
+                        membership.outfit_id,
+                        membership.last_update.and_utc(),
+                    );
+                }
+            }
+            Err(e) => error!("Failed to get stored outfit memberships: {e}"),
+        }
+
+        let unresolved: HashSet<CharacterID> =
+            cache.unresolved(&unresolved, now).into_iter().collect();
+        debug!("Fetching the outfits of {} characters", unresolved.len());
+
+        // Characters and outfits of each environment are only known to its own namespace
+        for environment in Environment::iter() {
+            let environment_unresolved: Vec<CharacterID> = characters
+                .iter()
+                .filter(|(character_id, world_id)| {
+                    world_id.environment() == environment && unresolved.contains(character_id)
+                })
+                .map(|(character_id, _)| *character_id)
+                .collect();
+            let census_rest_client = census_rest_client.for_environment(environment);
+
+            for character_ids in environment_unresolved.chunks(MAX_IDS_PER_REQUEST) {
+                fetch_memberships(cache, character_ids, db_pool, &census_rest_client, now).await;
+            }
+        }
+    }
+
+    apply_memberships(cache, &character_ids, active_players, character_sessions);
+
+    #[allow(clippy::cast_precision_loss)]
+    gauge!("niumside_outfit_cache_size").set(cache.len() as f64);
+}
+
+/// Keep the outfits of the active players up to date for the outfit population and their sessions
+pub async fn run(
+    active_players: ActivePlayerDb,
+    character_sessions: SessionSender,
+    db_pool: PgPool,
+    census_rest_client: CensusRestClient,
+) {
+    let mut cache = OutfitCache::default();
+    let mut interval = tokio::time::interval(Duration::from_secs(RESOLVE_INTERVAL_SECONDS));
+
+    loop {
+        interval.tick().await;
+        resolve(
+            &mut cache,
+            &active_players,
+            &character_sessions,
+            &db_pool,
+            &census_rest_client,
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::store::ShardedActivePlayers;
+    use crate::census::constants::WorldID;
+    use crate::census::event::PlayerLogin;
+    use crate::event_handlers::player_login;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    fn timestamp(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn test_memberships_include_characters_without_outfit() {
+        let members = [OutfitMember {
+            character_id: 1,
+            outfit_id: 37_570_391_403_474_491,
+        }];
+
+        assert_eq!(
+            memberships(&[1, 2], &members),
+            vec![(1, Some(37_570_391_403_474_491)), (2, None)]
+        );
+    }
+
+    #[test]
+    fn test_cache_refreshes_old_memberships() {
+        let mut cache = OutfitCache::default();
+        cache.insert(1, Some(37_570_391_403_474_491), timestamp(0));
+        cache.insert(2, None, timestamp(0));
+
+        assert_eq!(cache.unresolved(&[1, 2, 3], timestamp(60)), vec![3]);
+        assert_eq!(
+            cache.membership(1).unwrap().outfit_id,
+            Some(37_570_391_403_474_491)
+        );
+        assert_eq!(cache.membership(2).unwrap().outfit_id, None);
+        assert_eq!(cache.membership(3), None);
+
+        let refresh = timestamp(MEMBERSHIP_REFRESH_HOURS * 60 * 60);
+        assert_eq!(cache.unresolved(&[1, 2], refresh), vec![1, 2]);
+        cache.prune(refresh);
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[sqlx::test]
+    #[allow(clippy::cast_possible_wrap)]
+    async fn test_resolved_outfit_is_stored_on_session(db_pool: PgPool) {
+        let character_id = 5_429_573_939_285_739_921;
+        let outfit_id = 37_570_391_403_474_491;
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let now = Utc::now();
+
+        player_login::handle(
+            &PlayerLogin {
+                character_id,
+                timestamp: now,
+                world_id: WorldID::Miller,
+            },
+            &active_players,
+            &sender,
+        );
+        let mut cache = OutfitCache::default();
+        cache.insert(character_id, Some(outfit_id), now);
+        apply_memberships(&cache, &[character_id], &active_players, &sender);
+
+        // The tracker stops once every event was stored and the sender is gone
+        drop(sender);
+        character_sessions::run(receiver, &db_pool).await;
+
+        let stored: Option<i64> = sqlx::query_scalar(
+            "SELECT outfit_id FROM character_session WHERE character_id = $1 AND session_end IS NULL",
+        )
+        .bind(character_id as i64)
+        .fetch_one(&db_pool)
+        .await
+        .unwrap();
+        assert_eq!(stored, Some(outfit_id as i64));
+        assert_eq!(
+            active_players.get(character_id).unwrap().outfit,
+            Some(outfit_id)
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use crate::census::constants::{
+    CharacterID, Faction, Loadout, OutfitID, VehicleID, WorldID, ZoneID,
+};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// Minutes without activity after which a player that wasn't seen logging in is removed
+const ACTIVITY_TIMEOUT_MINUTES: i64 = 3;
+/// Minutes without activity after which a logged in player is removed, in case their logout was missed
+const SESSION_TIMEOUT_MINUTES: i64 = 60;
+/// Minutes without seeing a vehicle after which the player is assumed to have left it
+const VEHICLE_TIMEOUT_MINUTES: i64 = 3;
+
+#[derive(Debug, Clone)]
+pub struct ActivePlayer {
+    pub world: WorldID,
+    /// The zone as sent by Census, use `ZoneID::definition` and `ZoneID::instance` to split it
+    pub zone: ZoneID,
+    pub loadout: Loadout,
+    pub team_id: Faction,
+    pub last_change: DateTime<Utc>,
+    /// Whether a `PlayerLogin` was seen for this player, in which case they are only removed on
+    /// `PlayerLogout` or after the much longer `SESSION_TIMEOUT_MINUTES`
+    pub logged_in: bool,
+    /// The vehicle the player was last seen in, kept until an event shows them on foot or
+    /// `VEHICLE_TIMEOUT_MINUTES` passed without seeing the vehicle again
+    pub vehicle: Option<Vehicle>,
+    /// The outfit the player is a member of, `None` until it was resolved through Census or when
+    /// they aren't in an outfit
+    pub outfit: Option<OutfitID>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vehicle {
+    pub vehicle_id: VehicleID,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl Vehicle {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.last_seen + chrono::Duration::minutes(VEHICLE_TIMEOUT_MINUTES) > now
+    }
+}
+
+impl ActivePlayer {
+    /// Create an active player from events that only carry a loadout, such as `Death`.
+    /// The team is derived from the loadout, so NSO players are tracked as `Faction::NS`.
+    pub const fn from_loadout(
+        world: WorldID,
+        zone: ZoneID,
+        loadout: Loadout,
+        last_change: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            world,
+            zone,
+            loadout,
+            team_id: loadout.get_faction(),
+            last_change,
+            logged_in: false,
+            vehicle: None,
+            outfit: None,
+        }
+    }
+
+    /// Create an active player from a `PlayerLogin`, before their zone and loadout are known
+    pub const fn from_login(world: WorldID, last_change: DateTime<Utc>) -> Self {
+        Self {
+            world,
+            zone: ZoneID::UNKNOWN,
+            loadout: Loadout::Unknown,
+            team_id: Faction::Unknown,
+            last_change,
+            logged_in: true,
+            vehicle: None,
+            outfit: None,
+        }
+    }
+
+    /// Update the location of a player that is already being tracked without changing their loadout
+    pub const fn refresh(&mut self, world: WorldID, zone: ZoneID, last_change: DateTime<Utc>) {
+        self.world = world;
+        self.zone = zone;
+        self.last_change = last_change;
+    }
+
+    /// Whether the player is part of the population. Players only seen logging in have no zone,
+    /// loadout or faction yet and are left out until an event with their loadout arrives.
+    pub fn is_placed(&self) -> bool {
+        self.loadout != Loadout::Unknown
+    }
+
+    /// Whether the player should still be counted at `now`. Players without a known session
+    /// fall back to the experience based timeout.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        let timeout = if self.logged_in {
+            chrono::Duration::minutes(SESSION_TIMEOUT_MINUTES)
+        } else {
+            chrono::Duration::minutes(ACTIVITY_TIMEOUT_MINUTES)
+        };
+
+        self.last_change + timeout > now
+    }
+}
+
+pub type ActivePlayerHashmap = HashMap<CharacterID, ActivePlayer>;
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn timestamp(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn test_is_active_falls_back_to_activity_timeout() {
+        let player =
+            ActivePlayer::from_loadout(WorldID::Miller, ZoneID(2), Loadout::VSMAX, timestamp(0));
+
+        assert!(player.is_active(timestamp(2 * 60)));
+        assert!(!player.is_active(timestamp(ACTIVITY_TIMEOUT_MINUTES * 60)));
+    }
+
+    #[test]
+    fn test_is_active_keeps_logged_in_players() {
+        let player = ActivePlayer::from_login(WorldID::Miller, timestamp(0));
+
+        assert!(player.is_active(timestamp(ACTIVITY_TIMEOUT_MINUTES * 60)));
+        assert!(!player.is_active(timestamp(SESSION_TIMEOUT_MINUTES * 60)));
+    }
+}
@@ -0,0 +1,764 @@
+//! Rolls the population snapshots up into five minute, hourly and daily aggregates and deletes
+//! the snapshots and aggregates that are older than configured
+
+use crate::controllers::population_history::{levels, Level, Resolution};
+use crate::storage::configuration::{PopulationLayout, RetentionConfig};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use metrics::counter;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// How many buckets are rolled up per transaction, so the first run over a large database
+/// doesn't hold a single transaction for hours
+const BUCKETS_PER_CHUNK: i64 = 12;
+/// How many seconds of raw snapshots are deleted per statement, about 100 snapshots
+const DELETE_BATCH_SECONDS: i64 = 3000;
+
+/// The start of the bucket of `resolution` a moment falls in, buckets are aligned to the epoch
+fn bucket_start(moment: NaiveDateTime, resolution: Resolution) -> NaiveDateTime {
+    let timestamp = moment.and_utc().timestamp();
+    DateTime::from_timestamp(timestamp - timestamp.rem_euclid(resolution.seconds()), 0)
+        .map_or(moment, |start| start.naive_utc())
+}
+
+/// The range of buckets of `resolution` that can be rolled up from the finer resolution, which
+/// continues after the last bucket rolled up and ends at the last complete bucket
+fn rollup_range(
+    resolution: Resolution,
+    levels: &BTreeMap<Resolution, Level>,
+    now: NaiveDateTime,
+) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    let source = levels.get(&resolution.finer()?)?;
+    let start = levels.get(&resolution).map_or_else(
+        || bucket_start(source.oldest, resolution),
+        |rolled_up| rolled_up.until,
+    );
+    let end = if resolution.finer() == Some(Resolution::Raw) {
+        bucket_start(now, resolution)
+    } else {
+        bucket_start(source.until, resolution)
+    };
+
+    (start < end).then_some((start, end))
+}
+
+/// Before when a resolution can be deleted. Buckets that weren't rolled up into the coarser
+/// resolution, or that the last coarser bucket was rolled up from, are always kept.
+fn delete_before(
+    resolution: Resolution,
+    config: &RetentionConfig,
+    levels: &BTreeMap<Resolution, Level>,
+    now: NaiveDateTime,
+) -> Option<NaiveDateTime> {
+    let retention = match resolution {
+        Resolution::Raw => chrono::Duration::hours(config.raw_hours),
+        Resolution::FiveMinutes => chrono::Duration::days(config.five_minute_days),
+        Resolution::Hourly => chrono::Duration::days(config.hourly_days),
+        Resolution::Daily => chrono::Duration::days(config.daily_days?),
+    };
+    let cutoff = now - retention;
+
+    let coarser = match resolution {
+        Resolution::Raw => Resolution::FiveMinutes,
+        Resolution::FiveMinutes => Resolution::Hourly,
+        Resolution::Hourly => Resolution::Daily,
+        Resolution::Daily => return Some(cutoff),
+    };
+    let rolled_up = levels.get(&coarser)?;
+    let last_bucket = rolled_up.until - chrono::Duration::seconds(coarser.seconds());
+
+    Some(cutoff.min(last_bucket))
+}
+
+#[allow(clippy::cast_possible_truncation)]
+const fn resolution_column(resolution: Resolution) -> i32 {
+    resolution.seconds() as i32
+}
+
+/// Roll the raw snapshots of a range up into five minute buckets
+async fn rollup_raw(
+    db_pool: &PgPool,
+    population_layout: PopulationLayout,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    let resolution = resolution_column(Resolution::FiveMinutes);
+    let mut transaction = db_pool.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM population_aggregate WHERE resolution = $1 AND bucket >= $2 AND bucket < $3",
+        resolution,
+        start,
+        end
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM population_aggregate_world
+        WHERE resolution = $1 AND bucket >= $2 AND bucket < $3",
+        resolution,
+        start,
+        end
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO population_aggregate_world (resolution, bucket, world_id, snapshots)
+        SELECT
+            $1,
+            date_bin($1::INTEGER * INTERVAL '1 second', p.timestamp, TIMESTAMP 'epoch'),
+            wp.world_id,
+            COUNT(*)
+        FROM population p
+        JOIN world_population wp ON p.population_id = wp.population_id
+        WHERE p.timestamp >= $2 AND p.timestamp < $3
+        GROUP BY 2, wp.world_id",
+        resolution,
+        start,
+        end
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    // Every snapshot is summed per faction and loadout of each zone and the whole world, and per
+    // zone, which the zone ID of the grouping set tells apart
+    sqlx::query!(
+        "WITH points AS (
+            SELECT
+                date_bin($1::INTEGER * INTERVAL '1 second', p.timestamp, TIMESTAMP 'epoch')
+                    AS bucket,
+                p.world_id,
+                p.zone_id,
+                CASE
+                    WHEN GROUPING(p.loadout_id) = 0 THEN 'loadout'
+                    WHEN GROUPING(p.team_id) = 0 THEN 'faction'
+                    ELSE 'zone'
+                END AS group_by,
+                COALESCE(p.loadout_id, p.team_id, p.zone_id) AS key,
+                SUM(p.amount) AS amount
+            FROM (
+                SELECT * FROM population_snapshot WHERE $4
+                UNION ALL
+                SELECT * FROM population_loadout WHERE NOT $4
+            ) p
+            WHERE p.timestamp >= $2 AND p.timestamp < $3
+            GROUP BY p.population_id, p.timestamp, p.world_id, GROUPING SETS (
+                (p.zone_id, p.team_id),
+                (p.team_id),
+                (p.zone_id, p.loadout_id),
+                (p.loadout_id),
+                (p.zone_id)
+            )
+        )
+        INSERT INTO population_aggregate
+            (resolution, bucket, world_id, zone_id, group_by, key, total, minimum, maximum)
+        SELECT
+            $1,
+            points.bucket,
+            points.world_id,
+            points.zone_id,
+            points.group_by,
+            points.key,
+            SUM(points.amount),
+            CASE WHEN COUNT(*) < aw.snapshots THEN 0 ELSE MIN(points.amount) END,
+            MAX(points.amount)
+        FROM points
+        JOIN population_aggregate_world aw ON aw.resolution = $1
+            AND aw.bucket = points.bucket
+            AND aw.world_id = points.world_id
+        GROUP BY
+            points.bucket,
+            points.world_id,
+            points.zone_id,
+            points.group_by,
+            points.key,
+            aw.snapshots",
+        resolution,
+        start,
+        end,
+        population_layout.is_flat()
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await
+}
+
+/// Roll the buckets of a range up into the buckets of the next coarser resolution
+async fn rollup_aggregates(
+    db_pool: &PgPool,
+    resolution: Resolution,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    let Some(source) = resolution.finer() else {
+        return Ok(());
+    };
+    let source = resolution_column(source);
+    let resolution = resolution_column(resolution);
+    let mut transaction = db_pool.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM population_aggregate WHERE resolution = $1 AND bucket >= $2 AND bucket < $3",
+        resolution,
+        start,
+        end
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM population_aggregate_world
+        WHERE resolution = $1 AND bucket >= $2 AND bucket < $3",
+        resolution,
+        start,
+        end
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO population_aggregate_world (resolution, bucket, world_id, snapshots)
+        SELECT
+            $2,
+            date_bin($2::INTEGER * INTERVAL '1 second', bucket, TIMESTAMP 'epoch'),
+            world_id,
+            SUM(snapshots)
+        FROM population_aggregate_world
+        WHERE resolution = $1 AND bucket >= $3 AND bucket < $4
+        GROUP BY 2, world_id",
+        source,
+        resolution,
+        start,
+        end
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "WITH sources AS (
+            SELECT
+                date_bin($2::INTEGER * INTERVAL '1 second', bucket, TIMESTAMP 'epoch') AS bucket,
+                world_id,
+                COUNT(*) AS sub_buckets
+            FROM population_aggregate_world
+            WHERE resolution = $1 AND bucket >= $3 AND bucket < $4
+            GROUP BY 1, world_id
+        )
+        INSERT INTO population_aggregate
+            (resolution, bucket, world_id, zone_id, group_by, key, total, minimum, maximum)
+        SELECT
+            $2,
+            s.bucket,
+            a.world_id,
+            a.zone_id,
+            a.group_by,
+            a.key,
+            SUM(a.total),
+            CASE WHEN COUNT(*) < s.sub_buckets THEN 0 ELSE MIN(a.minimum) END,
+            MAX(a.maximum)
+        FROM population_aggregate a
+        JOIN sources s ON s.world_id = a.world_id
+            AND s.bucket = date_bin($2::INTEGER * INTERVAL '1 second', a.bucket, TIMESTAMP 'epoch')
+        WHERE a.resolution = $1 AND a.bucket >= $3 AND a.bucket < $4
+        GROUP BY s.bucket, a.world_id, a.zone_id, a.group_by, a.key, s.sub_buckets",
+        source,
+        resolution,
+        start,
+        end
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await
+}
+
+/// Roll up every complete bucket of a resolution that wasn't rolled up yet
+async fn rollup(
+    db_pool: &PgPool,
+    resolution: Resolution,
+    population_layout: PopulationLayout,
+    now: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    let levels = levels(db_pool, population_layout).await?;
+    let Some((mut start, end)) = rollup_range(resolution, &levels, now) else {
+        return Ok(());
+    };
+
+    let chunk = chrono::Duration::seconds(resolution.seconds() * BUCKETS_PER_CHUNK);
+    while start < end {
+        let chunk_end = (start + chunk).min(end);
+        if resolution.finer() == Some(Resolution::Raw) {
+            rollup_raw(db_pool, population_layout, start, chunk_end).await?;
+        } else {
+            rollup_aggregates(db_pool, resolution, start, chunk_end).await?;
+        }
+        start = chunk_end;
+    }
+
+    Ok(())
+}
+
+/// Delete the snapshots or aggregates of a resolution from before a moment
+async fn delete(
+    db_pool: &PgPool,
+    resolution: Resolution,
+    before: NaiveDateTime,
+) -> Result<u64, sqlx::Error> {
+    if resolution == Resolution::Raw {
+        // Only the loadouts are deleted, the vehicles, outfits and kill statistics that refer to
+        // the snapshot aren't rolled up. They are deleted from both layouts, so neither keeps
+        // snapshots that the other doesn't have.
+        let mut deleted = 0;
+        loop {
+            let flat = sqlx::query!(
+                "DELETE FROM population_snapshot
+                WHERE timestamp < LEAST(
+                    $1,
+                    (SELECT MIN(timestamp) FROM population_snapshot)
+                        + $2::BIGINT * INTERVAL '1 second'
+                )",
+                before,
+                DELETE_BATCH_SECONDS
+            )
+            .execute(db_pool)
+            .await?;
+            // The teams and loadouts of a zone are deleted with it
+            let normalized = sqlx::query!(
+                "DELETE FROM zone_population zp
+                USING world_population wp, population p
+                WHERE zp.world_population_id = wp.world_population_id
+                    AND wp.population_id = p.population_id
+                    AND p.timestamp < LEAST(
+                        $1,
+                        (SELECT MIN(timestamp) FROM population_loadout)
+                            + $2::BIGINT * INTERVAL '1 second'
+                    )",
+                before,
+                DELETE_BATCH_SECONDS
+            )
+            .execute(db_pool)
+            .await?;
+            deleted += flat.rows_affected() + normalized.rows_affected();
+            if flat.rows_affected() == 0 && normalized.rows_affected() == 0 {
+                return Ok(deleted);
+            }
+        }
+    }
+
+    let resolution = resolution_column(resolution);
+    let mut transaction = db_pool.begin().await?;
+    let deleted = sqlx::query!(
+        "DELETE FROM population_aggregate WHERE resolution = $1 AND bucket < $2",
+        resolution,
+        before
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM population_aggregate_world WHERE resolution = $1 AND bucket < $2",
+        resolution,
+        before
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(deleted.rows_affected())
+}
+
+/// Roll up the new snapshots and delete everything past its retention
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `config` - How long each resolution is kept
+/// * `population_layout` - Whether the snapshots are rolled up from the normalized levels or from
+///   `population_snapshot`
+/// * `now` - The current time, only complete buckets before it are rolled up
+///
+/// # Returns
+///
+/// * `Ok(())` - Everything was rolled up and deleted
+/// * `Err(sqlx::Error)` - The error returned by sqlx, the next run continues where it failed
+pub async fn apply(
+    db_pool: &PgPool,
+    config: &RetentionConfig,
+    population_layout: PopulationLayout,
+    now: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    for resolution in [
+        Resolution::FiveMinutes,
+        Resolution::Hourly,
+        Resolution::Daily,
+    ] {
+        rollup(db_pool, resolution, population_layout, now).await?;
+    }
+
+    let levels = levels(db_pool, population_layout).await?;
+    for resolution in [
+        Resolution::Raw,
+        Resolution::FiveMinutes,
+        Resolution::Hourly,
+        Resolution::Daily,
+    ] {
+        let Some(before) = delete_before(resolution, config, &levels, now) else {
+            continue;
+        };
+        let deleted = delete(db_pool, resolution, before).await?;
+        if deleted > 0 {
+            let label: &'static str = resolution.into();
+            counter!("niumside_population_retention_deleted", "resolution" => label)
+                .increment(deleted);
+            info!("Deleted {deleted} {label} population rows from before {before}");
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn run(db_pool: PgPool, config: RetentionConfig, population_layout: PopulationLayout) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_minutes * 60));
+    loop {
+        interval.tick().await;
+        if let Err(e) = apply(&db_pool, &config, population_layout, Utc::now().naive_utc()).await {
+            counter!("niumside_population_retention_failures").increment(1);
+            error!("Failed to apply the population retention: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn at(minutes: i64) -> NaiveDateTime {
+        DateTime::from_timestamp(1_760_000_000 + minutes * 60, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    /// The start of a day, which every resolution is aligned to
+    fn day() -> NaiveDateTime {
+        bucket_start(at(0), Resolution::Daily)
+    }
+
+    async fn insert_lookups(db_pool: &PgPool) {
+        sqlx::raw_sql(
+            "INSERT INTO world (world_id) VALUES (10);
+            INSERT INTO zone (zone_id) VALUES (2);
+            INSERT INTO faction (faction_id) VALUES (1);
+            INSERT INTO loadout (loadout_id) VALUES (1);",
+        )
+        .execute(db_pool)
+        .await
+        .unwrap();
+    }
+
+    /// Store a snapshot of Miller with VS players on Indar in both layouts, with the kills and
+    /// vehicles counted with it
+    async fn store_snapshot(db_pool: &PgPool, timestamp: NaiveDateTime, amount: i16) {
+        sqlx::query(
+            "WITH p AS (
+                INSERT INTO population (timestamp) VALUES ($1) RETURNING population_id, timestamp
+            ),
+            wp AS (
+                INSERT INTO world_population (world_id, population_id)
+                SELECT 10, population_id FROM p
+                RETURNING world_population_id
+            ),
+            zp AS (
+                INSERT INTO zone_population (zone_id, instance_id, world_population_id)
+                SELECT 2, 0, world_population_id FROM wp
+                RETURNING zone_population_id
+            ),
+            tp AS (
+                INSERT INTO team_population (team_id, zone_population_id)
+                SELECT 1, zone_population_id FROM zp
+                RETURNING team_population_id
+            ),
+            lp AS (
+                INSERT INTO loadout_population (loadout_id, team_population_id, amount)
+                SELECT 1, team_population_id, $2 FROM tp
+            ),
+            ps AS (
+                INSERT INTO population_snapshot
+                    (population_id, timestamp, world_id, zone_id, instance_id, team_id, loadout_id, amount)
+                SELECT population_id, timestamp, 10, 2, 0, 1, 1, $2 FROM p
+            ),
+            vp AS (
+                INSERT INTO vehicle_population (world_population_id, zone_id, team_id, vehicle_id, amount)
+                SELECT world_population_id, 2, 1, 1, $2 FROM wp
+            )
+            INSERT INTO kill_stats
+                (population_id, world_id, zone_id, faction_id, loadout_id, kills, deaths, headshots, window_seconds)
+            SELECT population_id, 10, 2, 1, 1, $2, 0, 0, 30 FROM p",
+        )
+        .bind(timestamp)
+        .bind(amount)
+        .execute(db_pool)
+        .await
+        .unwrap();
+    }
+
+    /// Store the aggregates of `buckets` consecutive buckets of 10 snapshots each. Loadout 1 has
+    /// `i` players in bucket `i`, loadout 3 is only in the first bucket.
+    async fn store_aggregates(db_pool: &PgPool, resolution: Resolution, buckets: i64) {
+        let seconds = resolution_column(resolution);
+        for i in 0..buckets {
+            let bucket = day() + chrono::Duration::seconds(resolution.seconds() * i);
+            sqlx::query(
+                "INSERT INTO population_aggregate_world (resolution, bucket, world_id, snapshots)
+                VALUES ($1, $2, 10, 10)",
+            )
+            .bind(seconds)
+            .bind(bucket)
+            .execute(db_pool)
+            .await
+            .unwrap();
+            sqlx::query(
+                "INSERT INTO population_aggregate
+                    (resolution, bucket, world_id, zone_id, group_by, key, total, minimum, maximum)
+                VALUES ($1, $2, 10, NULL, 'loadout', 1, $3 * 10, $3, $3 + 1)",
+            )
+            .bind(seconds)
+            .bind(bucket)
+            .bind(i)
+            .execute(db_pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO population_aggregate
+                (resolution, bucket, world_id, zone_id, group_by, key, total, minimum, maximum)
+            VALUES ($1, $2, 10, NULL, 'loadout', 3, 20, 2, 2)",
+        )
+        .bind(seconds)
+        .bind(day())
+        .execute(db_pool)
+        .await
+        .unwrap();
+    }
+
+    /// The snapshots of each bucket and the loadout's total, minimum and maximum
+    async fn aggregates(
+        db_pool: &PgPool,
+        resolution: Resolution,
+        group_by: &str,
+        key: i32,
+    ) -> Vec<(i32, i64, i64, i64)> {
+        sqlx::query_as(
+            "SELECT aw.snapshots, a.total, a.minimum, a.maximum
+            FROM population_aggregate a
+            JOIN population_aggregate_world aw ON aw.resolution = a.resolution
+                AND aw.bucket = a.bucket
+                AND aw.world_id = a.world_id
+            WHERE a.resolution = $1 AND a.zone_id IS NULL AND a.group_by = $2 AND a.key = $3
+            ORDER BY a.bucket",
+        )
+        .bind(resolution_column(resolution))
+        .bind(group_by)
+        .bind(key)
+        .fetch_all(db_pool)
+        .await
+        .unwrap()
+    }
+
+    async fn count(db_pool: &PgPool, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table}"))
+            .fetch_one(db_pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn test_rollup_raw_into_five_minutes(db_pool: PgPool) {
+        insert_lookups(&db_pool).await;
+        for i in 0..20_i16 {
+            let timestamp = day() + chrono::Duration::seconds(30 * i64::from(i));
+            store_snapshot(&db_pool, timestamp, i + 1).await;
+        }
+
+        rollup(
+            &db_pool,
+            Resolution::FiveMinutes,
+            PopulationLayout::Normalized,
+            day() + chrono::Duration::minutes(15),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            aggregates(&db_pool, Resolution::FiveMinutes, "faction", 1).await,
+            vec![(10, 55, 1, 10), (10, 155, 11, 20)]
+        );
+        assert_eq!(
+            aggregates(&db_pool, Resolution::FiveMinutes, "loadout", 1).await,
+            vec![(10, 55, 1, 10), (10, 155, 11, 20)]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_rollup_raw_from_flat_layout(db_pool: PgPool) {
+        insert_lookups(&db_pool).await;
+        for i in 0..20_i16 {
+            let timestamp = day() + chrono::Duration::seconds(30 * i64::from(i));
+            store_snapshot(&db_pool, timestamp, i + 1).await;
+        }
+        // Only the flat layout has the loadouts
+        sqlx::query("DELETE FROM zone_population")
+            .execute(&db_pool)
+            .await
+            .unwrap();
+
+        rollup(
+            &db_pool,
+            Resolution::FiveMinutes,
+            PopulationLayout::Flat,
+            day() + chrono::Duration::minutes(15),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            aggregates(&db_pool, Resolution::FiveMinutes, "faction", 1).await,
+            vec![(10, 55, 1, 10), (10, 155, 11, 20)]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_rollup_five_minutes_into_hourly(db_pool: PgPool) {
+        insert_lookups(&db_pool).await;
+        store_aggregates(&db_pool, Resolution::FiveMinutes, 24).await;
+
+        rollup(
+            &db_pool,
+            Resolution::Hourly,
+            PopulationLayout::Normalized,
+            day() + chrono::Duration::hours(3),
+        )
+        .await
+        .unwrap();
+
+        // Buckets 0 to 11 and 12 to 23
+        assert_eq!(
+            aggregates(&db_pool, Resolution::Hourly, "loadout", 1).await,
+            vec![(120, 660, 0, 12), (120, 2100, 12, 24)]
+        );
+        // Loadout 3 was missing from all but one bucket of the hour
+        assert_eq!(
+            aggregates(&db_pool, Resolution::Hourly, "loadout", 3).await,
+            vec![(120, 20, 0, 2)]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_rollup_hourly_into_daily(db_pool: PgPool) {
+        insert_lookups(&db_pool).await;
+        store_aggregates(&db_pool, Resolution::Hourly, 24).await;
+
+        rollup(
+            &db_pool,
+            Resolution::Daily,
+            PopulationLayout::Normalized,
+            day() + chrono::Duration::days(2),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            aggregates(&db_pool, Resolution::Daily, "loadout", 1).await,
+            vec![(240, 2760, 0, 24)]
+        );
+        assert_eq!(
+            aggregates(&db_pool, Resolution::Daily, "loadout", 3).await,
+            vec![(240, 20, 0, 2)]
+        );
+    }
+
+    #[sqlx::test]
+    async fn test_delete_raw_in_batches(db_pool: PgPool) {
+        insert_lookups(&db_pool).await;
+        // More snapshots than are deleted per statement, after a gap longer than a statement
+        store_snapshot(&db_pool, day() - chrono::Duration::hours(2), 1).await;
+        for i in 0..250 {
+            store_snapshot(&db_pool, day() + chrono::Duration::seconds(30 * i), 1).await;
+        }
+        let before = day() + chrono::Duration::hours(1);
+
+        let deleted = delete(&db_pool, Resolution::Raw, before).await.unwrap();
+
+        // A row per snapshot in each layout
+        assert_eq!(deleted, 2 * 121);
+        assert_eq!(count(&db_pool, "population_snapshot").await, 130);
+        assert_eq!(count(&db_pool, "loadout_population").await, 130);
+        for population_layout in [PopulationLayout::Normalized, PopulationLayout::Flat] {
+            assert_eq!(
+                levels(&db_pool, population_layout).await.unwrap()[&Resolution::Raw].oldest,
+                before
+            );
+        }
+        // The snapshots are kept for the statistics that aren't rolled up
+        assert_eq!(count(&db_pool, "population").await, 251);
+        assert_eq!(count(&db_pool, "kill_stats").await, 251);
+        assert_eq!(count(&db_pool, "vehicle_population").await, 251);
+    }
+
+    #[test]
+    fn test_rollup_range_and_retention() {
+        let now = at(24 * 60);
+        let mut levels = BTreeMap::from([(
+            Resolution::Raw,
+            Level {
+                oldest: at(0),
+                until: now,
+            },
+        )]);
+        let config = RetentionConfig {
+            raw_hours: 2,
+            ..RetentionConfig::default()
+        };
+
+        assert_eq!(
+            rollup_range(Resolution::FiveMinutes, &levels, now),
+            Some((
+                bucket_start(at(0), Resolution::FiveMinutes),
+                bucket_start(now, Resolution::FiveMinutes)
+            ))
+        );
+        assert_eq!(rollup_range(Resolution::Hourly, &levels, now), None);
+        // Nothing was rolled up yet, so no snapshot can be deleted
+        assert_eq!(delete_before(Resolution::Raw, &config, &levels, now), None);
+
+        levels.insert(
+            Resolution::FiveMinutes,
+            Level {
+                oldest: bucket_start(at(0), Resolution::FiveMinutes),
+                until: bucket_start(now, Resolution::FiveMinutes),
+            },
+        );
+        assert_eq!(
+            rollup_range(Resolution::Hourly, &levels, now),
+            Some((
+                bucket_start(at(0), Resolution::Hourly),
+                bucket_start(now, Resolution::Hourly)
+            ))
+        );
+        assert_eq!(
+            delete_before(Resolution::Raw, &config, &levels, now),
+            Some(at(22 * 60))
+        );
+        // Snapshots that weren't rolled up are kept past their retention
+        assert_eq!(
+            delete_before(Resolution::Raw, &config, &levels, at(48 * 60)),
+            Some(bucket_start(now, Resolution::FiveMinutes) - chrono::Duration::minutes(5))
+        );
+        assert_eq!(
+            delete_before(Resolution::Daily, &config, &levels, now),
+            None
+        );
+    }
+}
@@ -0,0 +1,604 @@
+//! Writes a population snapshot in a single transaction, so a failed write never leaves a
+//! partial snapshot for the API to serve
+
+use crate::controllers::population::{WorldBreakdown, WorldOutfitBreakdown, WorldVehicleBreakdown};
+use crate::storage::configuration::PopulationLayout;
+use metrics::counter;
+use sqlx::{PgConnection, PgPool};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// How often writing a snapshot is attempted before it is skipped
+const SNAPSHOT_ATTEMPTS: u32 = 3;
+const SNAPSHOT_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Default, PartialEq, Eq)]
+#[allow(clippy::struct_field_names)]
+struct ZoneRows {
+    world_ids: Vec<i32>,
+    zone_ids: Vec<i32>,
+    instance_ids: Vec<i32>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+#[allow(clippy::struct_field_names)]
+struct TeamRows {
+    world_ids: Vec<i32>,
+    zone_ids: Vec<i32>,
+    instance_ids: Vec<i32>,
+    team_ids: Vec<i16>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct LoadoutRows {
+    world_ids: Vec<i32>,
+    zone_ids: Vec<i32>,
+    instance_ids: Vec<i32>,
+    team_ids: Vec<i16>,
+    loadout_ids: Vec<i16>,
+    amounts: Vec<i16>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct VehicleRows {
+    world_ids: Vec<i32>,
+    zone_ids: Vec<i32>,
+    team_ids: Vec<i16>,
+    vehicle_ids: Vec<i16>,
+    amounts: Vec<i16>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct OutfitRows {
+    world_ids: Vec<i32>,
+    zone_ids: Vec<i32>,
+    outfit_ids: Vec<i64>,
+    amounts: Vec<i16>,
+}
+
+/// A snapshot as one array per column, so every table is written with a single `UNNEST` insert.
+/// Rows refer to their parents by world, zone, instance and team instead of generated IDs.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SnapshotRows {
+    world_ids: Vec<i32>,
+    zones: ZoneRows,
+    teams: TeamRows,
+    loadouts: LoadoutRows,
+    vehicles: VehicleRows,
+    outfits: OutfitRows,
+}
+
+impl SnapshotRows {
+    #[allow(clippy::cast_possible_wrap)]
+    fn new(
+        loadout_breakdown: &WorldBreakdown,
+        vehicle_breakdown: &WorldVehicleBreakdown,
+        outfit_breakdown: &WorldOutfitBreakdown,
+    ) -> Self {
+        let mut rows = Self::default();
+
+        for (world_id, zone_map) in loadout_breakdown {
+            let world_id = *world_id as i32;
+            rows.world_ids.push(world_id);
+
+            for (zone_id, instance_map) in zone_map {
+                let zone_id = i32::from(zone_id.0);
+                for (instance_id, team_map) in instance_map {
+                    let instance_id = i32::from(instance_id.0);
+                    rows.zones.world_ids.push(world_id);
+                    rows.zones.zone_ids.push(zone_id);
+                    rows.zones.instance_ids.push(instance_id);
+
+                    for (team_id, loadout_map) in team_map {
+                        let team_id = *team_id as i16;
+                        rows.teams.world_ids.push(world_id);
+                        rows.teams.zone_ids.push(zone_id);
+                        rows.teams.instance_ids.push(instance_id);
+                        rows.teams.team_ids.push(team_id);
+
+                        for (loadout_id, amount) in loadout_map {
+                            rows.loadouts.world_ids.push(world_id);
+                            rows.loadouts.zone_ids.push(zone_id);
+                            rows.loadouts.instance_ids.push(instance_id);
+                            rows.loadouts.team_ids.push(team_id);
+                            rows.loadouts.loadout_ids.push(*loadout_id as i16);
+                            rows.loadouts.amounts.push(*amount as i16);
+                        }
+                    }
+                }
+            }
+        }
+
+        for (world_id, zone_map) in vehicle_breakdown {
+            for (zone_id, team_map) in zone_map {
+                for (team_id, vehicle_map) in team_map {
+                    for (vehicle_id, amount) in vehicle_map {
+                        rows.vehicles.world_ids.push(*world_id as i32);
+                        rows.vehicles.zone_ids.push(i32::from(zone_id.0));
+                        rows.vehicles.team_ids.push(*team_id as i16);
+                        rows.vehicles.vehicle_ids.push(*vehicle_id as i16);
+                        rows.vehicles.amounts.push(*amount as i16);
+                    }
+                }
+            }
+        }
+
+        for (world_id, zone_map) in outfit_breakdown {
+            for (zone_id, outfit_map) in zone_map {
+                for (outfit_id, amount) in outfit_map {
+                    rows.outfits.world_ids.push(*world_id as i32);
+                    rows.outfits.zone_ids.push(i32::from(zone_id.0));
+                    rows.outfits.outfit_ids.push(*outfit_id as i64);
+                    rows.outfits.amounts.push(*amount as i16);
+                }
+            }
+        }
+
+        rows
+    }
+}
+
+/// Add the worlds, zones, factions, loadouts and outfits the snapshot refers to
+async fn insert_lookups(rows: &SnapshotRows, connection: &mut PgConnection) -> sqlx::Result<()> {
+    sqlx::query!(
+        "INSERT INTO world (world_id) SELECT DISTINCT UNNEST($1::INTEGER[]) ON CONFLICT DO NOTHING",
+        &rows.world_ids
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO zone (zone_id)
+        SELECT DISTINCT UNNEST($1::INTEGER[] || $2::INTEGER[] || $3::INTEGER[])
+        ON CONFLICT DO NOTHING",
+        &rows.zones.zone_ids,
+        &rows.vehicles.zone_ids,
+        &rows.outfits.zone_ids
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO faction (faction_id) SELECT DISTINCT UNNEST($1::SMALLINT[]) ON CONFLICT DO NOTHING",
+        &rows.teams.team_ids
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO loadout (loadout_id) SELECT DISTINCT UNNEST($1::SMALLINT[]) ON CONFLICT DO NOTHING",
+        &rows.loadouts.loadout_ids
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO outfit (outfit_id) SELECT DISTINCT UNNEST($1::BIGINT[]) ON CONFLICT DO NOTHING",
+        &rows.outfits.outfit_ids
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    Ok(())
+}
+
+/// The loadouts are always written to the normalized levels, so the layout can be switched back,
+/// and with the flat layout also as one `population_snapshot` row each
+async fn insert_loadout_population(
+    rows: &SnapshotRows,
+    population_id: i32,
+    population_layout: PopulationLayout,
+    connection: &mut PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "INSERT INTO world_population (world_id, population_id)
+        SELECT UNNEST($1::INTEGER[]), $2",
+        &rows.world_ids,
+        population_id
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO zone_population (zone_id, instance_id, world_population_id)
+        SELECT z.zone_id, z.instance_id, wp.world_population_id
+        FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::INTEGER[]) AS z(world_id, zone_id, instance_id)
+        JOIN world_population wp ON wp.world_id = z.world_id AND wp.population_id = $4",
+        &rows.zones.world_ids,
+        &rows.zones.zone_ids,
+        &rows.zones.instance_ids,
+        population_id
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO team_population (team_id, zone_population_id)
+        SELECT t.team_id, zp.zone_population_id
+        FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::INTEGER[], $4::SMALLINT[])
+            AS t(world_id, zone_id, instance_id, team_id)
+        JOIN world_population wp ON wp.world_id = t.world_id AND wp.population_id = $5
+        JOIN zone_population zp ON zp.world_population_id = wp.world_population_id
+            AND zp.zone_id = t.zone_id
+            AND zp.instance_id = t.instance_id",
+        &rows.teams.world_ids,
+        &rows.teams.zone_ids,
+        &rows.teams.instance_ids,
+        &rows.teams.team_ids,
+        population_id
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO loadout_population (loadout_id, team_population_id, amount)
+        SELECT l.loadout_id, tp.team_population_id, l.amount
+        FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::INTEGER[], $4::SMALLINT[], $5::SMALLINT[], $6::SMALLINT[])
+            AS l(world_id, zone_id, instance_id, team_id, loadout_id, amount)
+        JOIN world_population wp ON wp.world_id = l.world_id AND wp.population_id = $7
+        JOIN zone_population zp ON zp.world_population_id = wp.world_population_id
+            AND zp.zone_id = l.zone_id
+            AND zp.instance_id = l.instance_id
+        JOIN team_population tp ON tp.zone_population_id = zp.zone_population_id
+            AND tp.team_id = l.team_id",
+        &rows.loadouts.world_ids,
+        &rows.loadouts.zone_ids,
+        &rows.loadouts.instance_ids,
+        &rows.loadouts.team_ids,
+        &rows.loadouts.loadout_ids,
+        &rows.loadouts.amounts,
+        population_id
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    if !population_layout.is_flat() {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "INSERT INTO population_snapshot
+            (population_id, timestamp, world_id, zone_id, instance_id, team_id, loadout_id, amount)
+        SELECT p.population_id, p.timestamp, l.*
+        FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::INTEGER[], $4::SMALLINT[], $5::SMALLINT[], $6::SMALLINT[])
+            AS l(world_id, zone_id, instance_id, team_id, loadout_id, amount)
+        CROSS JOIN population p
+        WHERE p.population_id = $7",
+        &rows.loadouts.world_ids,
+        &rows.loadouts.zone_ids,
+        &rows.loadouts.instance_ids,
+        &rows.loadouts.team_ids,
+        &rows.loadouts.loadout_ids,
+        &rows.loadouts.amounts,
+        population_id
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    Ok(())
+}
+
+/// Vehicles and outfits are only stored for worlds that have a loadout population
+async fn insert_vehicle_and_outfit_population(
+    rows: &SnapshotRows,
+    population_id: i32,
+    connection: &mut PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "INSERT INTO vehicle_population (world_population_id, zone_id, team_id, vehicle_id, amount)
+        SELECT wp.world_population_id, v.zone_id, v.team_id, v.vehicle_id, v.amount
+        FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::SMALLINT[], $4::SMALLINT[], $5::SMALLINT[])
+            AS v(world_id, zone_id, team_id, vehicle_id, amount)
+        JOIN world_population wp ON wp.world_id = v.world_id AND wp.population_id = $6",
+        &rows.vehicles.world_ids,
+        &rows.vehicles.zone_ids,
+        &rows.vehicles.team_ids,
+        &rows.vehicles.vehicle_ids,
+        &rows.vehicles.amounts,
+        population_id
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO outfit_population (world_population_id, zone_id, outfit_id, amount)
+        SELECT wp.world_population_id, o.zone_id, o.outfit_id, o.amount
+        FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::BIGINT[], $4::SMALLINT[])
+            AS o(world_id, zone_id, outfit_id, amount)
+        JOIN world_population wp ON wp.world_id = o.world_id AND wp.population_id = $5",
+        &rows.outfits.world_ids,
+        &rows.outfits.zone_ids,
+        &rows.outfits.outfit_ids,
+        &rows.outfits.amounts,
+        population_id
+    )
+    .execute(&mut *connection)
+    .await?;
+
+    Ok(())
+}
+
+/// Store the population in a single transaction and return its ID, which the kill statistics of
+/// the same window refer to
+pub async fn store_pop(
+    loadout_breakdown: &WorldBreakdown,
+    vehicle_breakdown: &WorldVehicleBreakdown,
+    outfit_breakdown: &WorldOutfitBreakdown,
+    population_layout: PopulationLayout,
+    db_pool: &PgPool,
+) -> sqlx::Result<i32> {
+    let rows = SnapshotRows::new(loadout_breakdown, vehicle_breakdown, outfit_breakdown);
+    let mut transaction = db_pool.begin().await?;
+
+    let population_id =
+        sqlx::query!("INSERT INTO population (timestamp) VALUES (default) RETURNING population_id")
+            .fetch_one(&mut *transaction)
+            .await?
+            .population_id;
+
+    insert_lookups(&rows, &mut transaction).await?;
+    insert_loadout_population(&rows, population_id, population_layout, &mut transaction).await?;
+    insert_vehicle_and_outfit_population(&rows, population_id, &mut transaction).await?;
+
+    transaction.commit().await?;
+    Ok(population_id)
+}
+
+/// Store the population, trying again a few times before the snapshot is skipped. Returns the
+/// ID of the population when it was stored.
+pub async fn store(
+    loadout_breakdown: &WorldBreakdown,
+    vehicle_breakdown: &WorldVehicleBreakdown,
+    outfit_breakdown: &WorldOutfitBreakdown,
+    population_layout: PopulationLayout,
+    db_pool: &PgPool,
+) -> Option<i32> {
+    for attempt in 1..=SNAPSHOT_ATTEMPTS {
+        match store_pop(
+            loadout_breakdown,
+            vehicle_breakdown,
+            outfit_breakdown,
+            population_layout,
+            db_pool,
+        )
+        .await
+        {
+            Ok(population_id) => {
+                info!("Stored pop");
+                return Some(population_id);
+            }
+            Err(e) => {
+                counter!("niumside_population_failed_writes").increment(1);
+                warn!("Failed to store the population (attempt {attempt} of {SNAPSHOT_ATTEMPTS}): {e}");
+            }
+        }
+
+        if attempt < SNAPSHOT_ATTEMPTS {
+            tokio::time::sleep(SNAPSHOT_RETRY_DELAY).await;
+        }
+    }
+
+    counter!("niumside_population_snapshots_skipped").increment(1);
+    error!("Skipped the population snapshot after {SNAPSHOT_ATTEMPTS} failed attempts");
+    None
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::constants::{DefinitionID, Faction, InstanceID, Loadout, WorldID};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use std::collections::HashMap;
+
+    /// Two loadouts of VS on Koltyr, a TR vehicle and an outfit on Indar, all on Miller
+    fn breakdowns() -> (WorldBreakdown, WorldVehicleBreakdown, WorldOutfitBreakdown) {
+        let mut loadout_breakdown: WorldBreakdown = HashMap::new();
+        let team = loadout_breakdown
+            .entry(WorldID::Miller)
+            .or_default()
+            .entry(DefinitionID(14))
+            .or_default()
+            .entry(InstanceID(3))
+            .or_default()
+            .entry(Faction::VS)
+            .or_default();
+        team.insert(Loadout::VSMAX, 2);
+        team.insert(Loadout::VSHeavyAssault, 1);
+
+        let mut vehicle_breakdown: WorldVehicleBreakdown = HashMap::new();
+        vehicle_breakdown
+            .entry(WorldID::Miller)
+            .or_default()
+            .entry(DefinitionID(2))
+            .or_default()
+            .entry(Faction::TR)
+            .or_default()
+            .insert(1, 4);
+
+        let mut outfit_breakdown: WorldOutfitBreakdown = HashMap::new();
+        outfit_breakdown
+            .entry(WorldID::Miller)
+            .or_default()
+            .entry(DefinitionID(2))
+            .or_default()
+            .insert(37_509_488_620_604_883, 5);
+
+        (loadout_breakdown, vehicle_breakdown, outfit_breakdown)
+    }
+
+    #[test]
+    fn test_snapshot_rows() {
+        let (loadout_breakdown, vehicle_breakdown, outfit_breakdown) = breakdowns();
+        let rows = SnapshotRows::new(&loadout_breakdown, &vehicle_breakdown, &outfit_breakdown);
+
+        assert_eq!(rows.world_ids, vec![10]);
+        assert_eq!(
+            rows.zones,
+            ZoneRows {
+                world_ids: vec![10],
+                zone_ids: vec![14],
+                instance_ids: vec![3],
+            }
+        );
+        assert_eq!(rows.teams.team_ids, vec![Faction::VS as i16]);
+        assert_eq!(rows.loadouts.instance_ids, vec![3, 3]);
+        let mut amounts = rows.loadouts.amounts.clone();
+        amounts.sort_unstable();
+        assert_eq!(amounts, vec![1, 2]);
+        assert_eq!(
+            rows.vehicles,
+            VehicleRows {
+                world_ids: vec![10],
+                zone_ids: vec![2],
+                team_ids: vec![Faction::TR as i16],
+                vehicle_ids: vec![1],
+                amounts: vec![4],
+            }
+        );
+        assert_eq!(rows.outfits.outfit_ids, vec![37_509_488_620_604_883]);
+    }
+
+    async fn count(db_pool: &PgPool, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {table}"))
+            .fetch_one(db_pool)
+            .await
+            .unwrap()
+    }
+
+    /// Make every write of the loadouts fail, after the snapshot itself was inserted
+    async fn fail_loadout_writes(db_pool: &PgPool) {
+        sqlx::raw_sql(
+            "CREATE FUNCTION fail_write() RETURNS trigger AS $$
+            BEGIN
+                RAISE EXCEPTION 'write failed';
+            END
+            $$ LANGUAGE plpgsql;
+            CREATE TRIGGER fail_write BEFORE INSERT ON loadout_population
+                FOR EACH STATEMENT EXECUTE FUNCTION fail_write();",
+        )
+        .execute(db_pool)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_store_pop_commits_snapshot(db_pool: PgPool) {
+        let (loadout_breakdown, vehicle_breakdown, outfit_breakdown) = breakdowns();
+
+        let population_id = store_pop(
+            &loadout_breakdown,
+            &vehicle_breakdown,
+            &outfit_breakdown,
+            PopulationLayout::Flat,
+            &db_pool,
+        )
+        .await
+        .unwrap();
+
+        let amounts: Vec<(i32, i32, i16, i16)> = sqlx::query_as(
+            "SELECT world_id, zone_id, loadout_id, amount
+            FROM population_snapshot
+            WHERE population_id = $1
+            ORDER BY loadout_id",
+        )
+        .bind(population_id)
+        .fetch_all(&db_pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            amounts,
+            vec![
+                (10, 14, Loadout::VSHeavyAssault as i16, 1),
+                (10, 14, Loadout::VSMAX as i16, 2)
+            ]
+        );
+        assert_eq!(count(&db_pool, "world_population").await, 1);
+        assert_eq!(count(&db_pool, "loadout_population").await, 2);
+        assert_eq!(count(&db_pool, "vehicle_population").await, 1);
+        assert_eq!(count(&db_pool, "outfit_population").await, 1);
+    }
+
+    #[sqlx::test]
+    async fn test_store_pop_normalized_skips_population_snapshot(db_pool: PgPool) {
+        let (loadout_breakdown, vehicle_breakdown, outfit_breakdown) = breakdowns();
+
+        store_pop(
+            &loadout_breakdown,
+            &vehicle_breakdown,
+            &outfit_breakdown,
+            PopulationLayout::Normalized,
+            &db_pool,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(count(&db_pool, "zone_population").await, 1);
+        assert_eq!(count(&db_pool, "team_population").await, 1);
+        assert_eq!(count(&db_pool, "loadout_population").await, 2);
+        assert_eq!(count(&db_pool, "population_snapshot").await, 0);
+    }
+
+    #[sqlx::test]
+    async fn test_store_pop_rolls_back_failed_snapshot(db_pool: PgPool) {
+        let (loadout_breakdown, vehicle_breakdown, outfit_breakdown) = breakdowns();
+        fail_loadout_writes(&db_pool).await;
+
+        assert!(store_pop(
+            &loadout_breakdown,
+            &vehicle_breakdown,
+            &outfit_breakdown,
+            PopulationLayout::Flat,
+            &db_pool,
+        )
+        .await
+        .is_err());
+
+        for table in [
+            "population",
+            "world_population",
+            "zone_population",
+            "population_snapshot",
+            "world",
+        ] {
+            assert_eq!(count(&db_pool, table).await, 0, "{table} has rows");
+        }
+    }
+
+    // The local recorder is only set on the thread of the test runtime
+    #[sqlx::test]
+    #[allow(clippy::future_not_send)]
+    async fn test_store_skips_snapshot_after_failed_attempts(db_pool: PgPool) {
+        let (loadout_breakdown, vehicle_breakdown, outfit_breakdown) = breakdowns();
+        fail_loadout_writes(&db_pool).await;
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let _guard = metrics::set_default_local_recorder(&recorder);
+
+        let stored = store(
+            &loadout_breakdown,
+            &vehicle_breakdown,
+            &outfit_breakdown,
+            PopulationLayout::Normalized,
+            &db_pool,
+        )
+        .await;
+
+        assert_eq!(stored, None);
+        assert_eq!(count(&db_pool, "population").await, 0);
+        let counters: HashMap<String, DebugValue> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| (key.key().name().to_owned(), value))
+            .collect();
+        assert_eq!(
+            counters["niumside_population_failed_writes"],
+            DebugValue::Counter(u64::from(SNAPSHOT_ATTEMPTS))
+        );
+        assert_eq!(
+            counters["niumside_population_snapshots_skipped"],
+            DebugValue::Counter(1)
+        );
+    }
+}
@@ -0,0 +1,588 @@
+use crate::active_players::{ActivePlayer, ActivePlayerHashmap, Vehicle};
+use crate::census::constants::{
+    CharacterID, Faction, Loadout, OutfitID, VehicleID, WorldID, ZoneID,
+};
+use chrono::{DateTime, Utc};
+use metrics::counter;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// The amount of shards used by `ShardedActivePlayers::default`
+const DEFAULT_SHARDS: usize = 16;
+
+/// The group a player is counted in for the population breakdown
+pub type PopulationKey = (WorldID, ZoneID, Faction, Loadout);
+
+pub type PopulationCounts = HashMap<PopulationKey, u32>;
+
+/// The group a player in a vehicle is counted in for the vehicle breakdown
+pub type VehicleKey = (WorldID, ZoneID, Faction, VehicleID);
+
+pub type VehicleCounts = HashMap<VehicleKey, u32>;
+
+/// The group a player in an outfit is counted in for the outfit breakdown
+pub type OutfitKey = (WorldID, ZoneID, OutfitID);
+
+pub type OutfitCounts = HashMap<OutfitKey, u32>;
+
+/// Storage for the players that are currently online, shared by the event handlers and the
+/// loops that clean and store the population
+pub trait ActivePlayerStore: Send + Sync {
+    /// Insert or update a player while keeping track of whether they were seen logging in
+    fn upsert(&self, character_id: CharacterID, player: ActivePlayer);
+
+    /// Mark a player as logged in, keeping their zone and loadout if they were already tracked
+    fn login(&self, character_id: CharacterID, world: WorldID, timestamp: DateTime<Utc>);
+
+    /// Update the location of a player that is already tracked without changing their loadout.
+    /// Returns whether the player was tracked.
+    fn refresh(
+        &self,
+        character_id: CharacterID,
+        world: WorldID,
+        zone: ZoneID,
+        timestamp: DateTime<Utc>,
+    ) -> bool;
+
+    /// Set the vehicle of a tracked player, `None` when they were seen on foot.
+    /// Returns whether the player was tracked.
+    fn set_vehicle(
+        &self,
+        character_id: CharacterID,
+        vehicle_id: Option<VehicleID>,
+        timestamp: DateTime<Utc>,
+    ) -> bool;
+
+    /// Set the outfit of a tracked player, returns whether the player was tracked
+    fn set_outfit(&self, character_id: CharacterID, outfit_id: Option<OutfitID>) -> bool;
+
+    fn remove(&self, character_id: CharacterID) -> Option<ActivePlayer>;
+
+    fn get(&self, character_id: CharacterID) -> Option<ActivePlayer>;
+
+    /// Remove the players that are no longer active at `now` and the vehicles they were no
+    /// longer seen in, returns the amount of players removed
+    fn retain_active(&self, now: DateTime<Utc>) -> usize;
+
+    fn len(&self) -> usize;
+
+    /// The characters of every tracked player with the world they play on
+    fn character_ids(&self) -> Vec<(CharacterID, WorldID)>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The amount of players in each group, kept up to date on every change so the breakdown
+    /// doesn't need to go through every player
+    fn counts(&self) -> PopulationCounts;
+
+    /// The amount of players in each vehicle, kept up to date like `counts`
+    fn vehicle_counts(&self) -> VehicleCounts;
+
+    /// The amount of players in each outfit, kept up to date like `counts`
+    fn outfit_counts(&self) -> OutfitCounts;
+}
+
+#[derive(Debug, Default)]
+struct Shard {
+    players: ActivePlayerHashmap,
+    counts: PopulationCounts,
+    vehicle_counts: VehicleCounts,
+    outfit_counts: OutfitCounts,
+}
+
+fn population_key(player: &ActivePlayer) -> Option<PopulationKey> {
+    player
+        .is_placed()
+        .then_some((player.world, player.zone, player.team_id, player.loadout))
+}
+
+fn vehicle_key(player: &ActivePlayer) -> Option<VehicleKey> {
+    if !player.is_placed() {
+        return None;
+    }
+    player.vehicle.map(|vehicle| {
+        (
+            player.world,
+            player.zone,
+            player.team_id,
+            vehicle.vehicle_id,
+        )
+    })
+}
+
+fn outfit_key(player: &ActivePlayer) -> Option<OutfitKey> {
+    if !player.is_placed() {
+        return None;
+    }
+    player
+        .outfit
+        .map(|outfit_id| (player.world, player.zone, outfit_id))
+}
+
+fn count<K: std::hash::Hash + Eq>(counts: &mut HashMap<K, u32>, key: K) {
+    *counts.entry(key).or_insert(0) += 1;
+}
+
+fn uncount<K: std::hash::Hash + Eq>(counts: &mut HashMap<K, u32>, key: &K) {
+    if let Some(count) = counts.get_mut(key) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(key);
+        }
+    }
+}
+
+/// Move a player from the group they were counted in to their new group, either can be `None`
+/// when the player isn't counted at all
+fn recount<K: std::hash::Hash + Eq>(
+    counts: &mut HashMap<K, u32>,
+    previous: Option<K>,
+    current: Option<K>,
+) {
+    if previous == current {
+        return;
+    }
+
+    if let Some(key) = previous {
+        uncount(counts, &key);
+    }
+    if let Some(key) = current {
+        count(counts, key);
+    }
+}
+
+impl Shard {
+    fn count(&mut self, player: &ActivePlayer) {
+        if let Some(key) = population_key(player) {
+            count(&mut self.counts, key);
+        }
+        if let Some(key) = vehicle_key(player) {
+            count(&mut self.vehicle_counts, key);
+        }
+        if let Some(key) = outfit_key(player) {
+            count(&mut self.outfit_counts, key);
+        }
+    }
+
+    fn uncount(&mut self, player: &ActivePlayer) {
+        if let Some(key) = population_key(player) {
+            uncount(&mut self.counts, &key);
+        }
+        if let Some(key) = vehicle_key(player) {
+            uncount(&mut self.vehicle_counts, &key);
+        }
+        if let Some(key) = outfit_key(player) {
+            uncount(&mut self.outfit_counts, &key);
+        }
+    }
+
+    fn insert(&mut self, character_id: CharacterID, player: ActivePlayer) {
+        self.count(&player);
+        if let Some(previous) = self.players.insert(character_id, player) {
+            self.uncount(&previous);
+        }
+    }
+
+    fn remove(&mut self, character_id: CharacterID) -> Option<ActivePlayer> {
+        let player = self.players.remove(&character_id)?;
+        self.uncount(&player);
+        Some(player)
+    }
+
+    /// Change a tracked player, returns whether the player was tracked
+    fn update(
+        &mut self,
+        character_id: CharacterID,
+        change: impl FnOnce(&mut ActivePlayer),
+    ) -> bool {
+        let Some(player) = self.players.get_mut(&character_id) else {
+            return false;
+        };
+
+        let previous = (
+            population_key(player),
+            vehicle_key(player),
+            outfit_key(player),
+        );
+        change(player);
+
+        recount(&mut self.counts, previous.0, population_key(player));
+        recount(&mut self.vehicle_counts, previous.1, vehicle_key(player));
+        recount(&mut self.outfit_counts, previous.2, outfit_key(player));
+
+        true
+    }
+
+    fn retain_active(&mut self, now: DateTime<Utc>) -> usize {
+        let inactive: Vec<CharacterID> = self
+            .players
+            .iter()
+            .filter(|(_, player)| !player.is_active(now))
+            .map(|(character_id, _)| *character_id)
+            .collect();
+
+        for character_id in &inactive {
+            self.remove(*character_id);
+        }
+
+        let left_vehicle: Vec<CharacterID> = self
+            .players
+            .iter()
+            .filter(|(_, player)| {
+                player
+                    .vehicle
+                    .is_some_and(|vehicle| !vehicle.is_active(now))
+            })
+            .map(|(character_id, _)| *character_id)
+            .collect();
+
+        for character_id in left_vehicle {
+            self.update(character_id, |player| player.vehicle = None);
+        }
+
+        inactive.len()
+    }
+}
+
+/// Active players split over several independently locked maps, so handlers working on different
+/// characters rarely wait for each other
+#[derive(Debug)]
+pub struct ShardedActivePlayers {
+    shards: Vec<Mutex<Shard>>,
+}
+
+impl Default for ShardedActivePlayers {
+    fn default() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+}
+
+impl ShardedActivePlayers {
+    pub fn with_shards(shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1)).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    fn shard(&self, character_id: CharacterID) -> MutexGuard<'_, Shard> {
+        // Fibonacci hashing spreads the sequential character IDs evenly over the shards
+        #[allow(clippy::cast_possible_truncation)]
+        let index = (character_id.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32) as usize;
+        lock(&self.shards[index % self.shards.len()])
+    }
+}
+
+/// A panic while holding a shard can't leave it half updated, so a poisoned shard is still used
+fn lock(shard: &Mutex<Shard>) -> MutexGuard<'_, Shard> {
+    shard.lock().unwrap_or_else(|poisoned: PoisonError<_>| {
+        counter!("niumside_active_players_lock_failed").increment(1);
+        poisoned.into_inner()
+    })
+}
+
+impl ActivePlayerStore for ShardedActivePlayers {
+    fn upsert(&self, character_id: CharacterID, mut player: ActivePlayer) {
+        let mut shard = self.shard(character_id);
+
+        if let Some(existing) = shard.players.get(&character_id) {
+            player.logged_in |= existing.logged_in;
+            // Changing loadout means the player redeployed, which leaves their vehicle
+            if player.vehicle.is_none() && player.loadout == existing.loadout {
+                player.vehicle = existing.vehicle;
+            }
+            player.outfit = player.outfit.or(existing.outfit);
+        }
+
+        shard.insert(character_id, player);
+    }
+
+    fn login(&self, character_id: CharacterID, world: WorldID, timestamp: DateTime<Utc>) {
+        let mut shard = self.shard(character_id);
+
+        let tracked = shard.update(character_id, |player| {
+            player.world = world;
+            player.last_change = timestamp;
+            player.logged_in = true;
+        });
+
+        if !tracked {
+            shard.insert(character_id, ActivePlayer::from_login(world, timestamp));
+        }
+    }
+
+    fn refresh(
+        &self,
+        character_id: CharacterID,
+        world: WorldID,
+        zone: ZoneID,
+        timestamp: DateTime<Utc>,
+    ) -> bool {
+        self.shard(character_id).update(character_id, |player| {
+            player.refresh(world, zone, timestamp);
+        })
+    }
+
+    fn set_vehicle(
+        &self,
+        character_id: CharacterID,
+        vehicle_id: Option<VehicleID>,
+        timestamp: DateTime<Utc>,
+    ) -> bool {
+        self.shard(character_id).update(character_id, |player| {
+            player.vehicle = vehicle_id.map(|vehicle_id| Vehicle {
+                vehicle_id,
+                last_seen: timestamp,
+            });
+        })
+    }
+
+    fn set_outfit(&self, character_id: CharacterID, outfit_id: Option<OutfitID>) -> bool {
+        self.shard(character_id).update(character_id, |player| {
+            player.outfit = outfit_id;
+        })
+    }
+
+    fn remove(&self, character_id: CharacterID) -> Option<ActivePlayer> {
+        self.shard(character_id).remove(character_id)
+    }
+
+    fn get(&self, character_id: CharacterID) -> Option<ActivePlayer> {
+        self.shard(character_id).players.get(&character_id).cloned()
+    }
+
+    fn retain_active(&self, now: DateTime<Utc>) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| lock(shard).retain_active(now))
+            .sum()
+    }
+
+    fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| lock(shard).players.len())
+            .sum()
+    }
+
+    fn character_ids(&self) -> Vec<(CharacterID, WorldID)> {
+        self.shards
+            .iter()
+            .flat_map(|shard| {
+                lock(shard)
+                    .players
+                    .iter()
+                    .map(|(character_id, player)| (*character_id, player.world))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn counts(&self) -> PopulationCounts {
+        let mut counts = PopulationCounts::new();
+
+        for shard in &self.shards {
+            for (key, count) in &lock(shard).counts {
+                *counts.entry(*key).or_insert(0) += count;
+            }
+        }
+
+        counts
+    }
+
+    fn vehicle_counts(&self) -> VehicleCounts {
+        let mut vehicle_counts = VehicleCounts::new();
+
+        for shard in &self.shards {
+            for (key, count) in &lock(shard).vehicle_counts {
+                *vehicle_counts.entry(*key).or_insert(0) += count;
+            }
+        }
+
+        vehicle_counts
+    }
+
+    fn outfit_counts(&self) -> OutfitCounts {
+        let mut outfit_counts = OutfitCounts::new();
+
+        for shard in &self.shards {
+            for (key, count) in &lock(shard).outfit_counts {
+                *outfit_counts.entry(*key).or_insert(0) += count;
+            }
+        }
+
+        outfit_counts
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn timestamp(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    fn player(zone: u32, loadout: Loadout) -> ActivePlayer {
+        ActivePlayer::from_loadout(WorldID::Miller, ZoneID(zone), loadout, timestamp(0))
+    }
+
+    #[test]
+    fn test_upsert_keeps_login_state() {
+        let active_players = ShardedActivePlayers::default();
+        active_players.login(1, WorldID::Miller, timestamp(0));
+
+        active_players.upsert(1, player(2, Loadout::VSMAX));
+
+        let player = active_players.get(1).unwrap();
+        assert!(player.logged_in);
+        assert_eq!(player.zone, ZoneID(2));
+        assert_eq!(player.loadout, Loadout::VSMAX);
+    }
+
+    #[test]
+    fn test_counts_follow_changes() {
+        let active_players = ShardedActivePlayers::default();
+        active_players.upsert(1, player(2, Loadout::VSMAX));
+        active_players.upsert(2, player(2, Loadout::VSMAX));
+        active_players.upsert(3, player(4, Loadout::VSMedic));
+
+        let max_on_indar = (WorldID::Miller, ZoneID(2), Faction::VS, Loadout::VSMAX);
+        assert_eq!(active_players.counts().get(&max_on_indar), Some(&2));
+
+        // Moving to another zone moves the player to another group
+        assert!(active_players.refresh(1, WorldID::Miller, ZoneID(4), timestamp(10)));
+        assert!(!active_players.refresh(4, WorldID::Miller, ZoneID(4), timestamp(10)));
+        active_players.remove(2);
+
+        let counts = active_players.counts();
+        assert_eq!(counts.get(&max_on_indar), None);
+        assert_eq!(
+            counts.get(&(WorldID::Miller, ZoneID(4), Faction::VS, Loadout::VSMAX)),
+            Some(&1)
+        );
+        assert_eq!(counts.values().sum::<u32>(), 2);
+        assert_eq!(active_players.len(), 2);
+    }
+
+    #[test]
+    fn test_retain_active_removes_counts() {
+        let active_players = ShardedActivePlayers::default();
+        active_players.upsert(1, player(2, Loadout::VSMAX));
+        active_players.login(2, WorldID::Miller, timestamp(0));
+
+        assert_eq!(active_players.retain_active(timestamp(10 * 60)), 1);
+        assert_eq!(active_players.len(), 1);
+        assert!(active_players.counts().is_empty());
+    }
+
+    #[test]
+    fn test_logged_in_players_are_counted_once_placed() {
+        let active_players = ShardedActivePlayers::default();
+        active_players.login(1, WorldID::Miller, timestamp(0));
+        assert!(active_players.refresh(1, WorldID::Miller, ZoneID(2), timestamp(10)));
+
+        // Neither the login nor a facility capture tells the loadout of the player
+        assert_eq!(active_players.len(), 1);
+        assert!(active_players.counts().is_empty());
+
+        active_players.upsert(1, player(2, Loadout::VSMAX));
+
+        assert_eq!(
+            active_players.counts(),
+            PopulationCounts::from([(
+                (WorldID::Miller, ZoneID(2), Faction::VS, Loadout::VSMAX),
+                1
+            )])
+        );
+    }
+
+    #[test]
+    fn test_vehicle_counts_follow_changes() {
+        let active_players = ShardedActivePlayers::default();
+        active_players.upsert(1, player(2, Loadout::VSHeavyAssault));
+        active_players.upsert(2, player(2, Loadout::VSHeavyAssault));
+
+        assert!(active_players.set_vehicle(1, Some(4), timestamp(0)));
+        assert!(active_players.set_vehicle(2, Some(4), timestamp(0)));
+        assert!(!active_players.set_vehicle(3, Some(4), timestamp(0)));
+
+        let magrider_on_indar = (WorldID::Miller, ZoneID(2), Faction::VS, 4);
+        assert_eq!(
+            active_players.vehicle_counts().get(&magrider_on_indar),
+            Some(&2)
+        );
+
+        // Other experience keeps the vehicle, redeploying as another class leaves it
+        active_players.upsert(1, player(2, Loadout::VSHeavyAssault));
+        active_players.upsert(2, player(2, Loadout::VSEngineer));
+        assert_eq!(
+            active_players.get(1).unwrap().vehicle.unwrap().vehicle_id,
+            4
+        );
+        assert_eq!(active_players.get(2).unwrap().vehicle, None);
+        assert_eq!(
+            active_players.vehicle_counts().get(&magrider_on_indar),
+            Some(&1)
+        );
+
+        // Driving to another zone moves the vehicle with the player
+        assert!(active_players.refresh(1, WorldID::Miller, ZoneID(4), timestamp(10)));
+        let vehicle_counts = active_players.vehicle_counts();
+        assert_eq!(vehicle_counts.get(&magrider_on_indar), None);
+        assert_eq!(
+            vehicle_counts.get(&(WorldID::Miller, ZoneID(4), Faction::VS, 4)),
+            Some(&1)
+        );
+    }
+
+    #[test]
+    fn test_retain_active_expires_vehicles() {
+        let active_players = ShardedActivePlayers::default();
+        active_players.login(1, WorldID::Miller, timestamp(0));
+        active_players.upsert(1, player(2, Loadout::VSHeavyAssault));
+        active_players.set_vehicle(1, Some(4), timestamp(0));
+
+        assert_eq!(active_players.retain_active(timestamp(5 * 60)), 0);
+        assert_eq!(active_players.get(1).unwrap().vehicle, None);
+        assert!(active_players.vehicle_counts().is_empty());
+        assert_eq!(active_players.counts().values().sum::<u32>(), 1);
+    }
+
+    #[test]
+    fn test_outfit_counts_follow_changes() {
+        let active_players = ShardedActivePlayers::default();
+        active_players.upsert(1, player(2, Loadout::VSMAX));
+        active_players.upsert(2, player(2, Loadout::VSMedic));
+
+        assert!(active_players.set_outfit(1, Some(10)));
+        assert!(active_players.set_outfit(2, Some(10)));
+        assert!(!active_players.set_outfit(3, Some(10)));
+
+        let outfit_on_indar = (WorldID::Miller, ZoneID(2), 10);
+        assert_eq!(
+            active_players.outfit_counts().get(&outfit_on_indar),
+            Some(&2)
+        );
+
+        // Events don't carry the outfit, so it is kept when the player is seen again
+        active_players.upsert(1, player(4, Loadout::VSMAX));
+        assert!(active_players.set_outfit(2, None));
+
+        let outfit_counts = active_players.outfit_counts();
+        assert_eq!(outfit_counts.get(&outfit_on_indar), None);
+        assert_eq!(
+            outfit_counts.get(&(WorldID::Miller, ZoneID(4), 10)),
+            Some(&1)
+        );
+
+        active_players.remove(1);
+        assert!(active_players.outfit_counts().is_empty());
+
+        let mut character_ids = active_players.character_ids();
+        character_ids.sort_unstable();
+        assert_eq!(character_ids, vec![(2, WorldID::Miller)]);
+    }
+}
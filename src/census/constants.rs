@@ -24,9 +24,12 @@ use strum::{EnumIter, FromRepr, VariantNames};
     FromRepr,
     PartialOrd,
     Ord,
+    Default,
 )]
 #[allow(clippy::upper_case_acronyms)]
 pub enum Loadout {
+    /// Also used for loadouts Census added after this list was written
+    #[default]
     Unknown = 0,
     NCInfiltrator = 1,
     NCLightAssault = 3,
@@ -105,8 +108,11 @@ impl Loadout {
     FromRepr,
     PartialOrd,
     Ord,
+    Default,
 )]
 pub enum Faction {
+    /// Also used for factions Census added after this list was written
+    #[default]
     Unknown = 0,
     VS = 1,
     NC = 2,
@@ -116,6 +122,29 @@ pub enum Faction {
 
 pub type TeamID = Faction;
 
+/// The state a metagame event (alert) changed to, as sent in `metagame_event_state`
+#[repr(u8)]
+#[derive(
+    Serialize,
+    Deserialize,
+    Copy,
+    Clone,
+    Eq,
+    Debug,
+    PartialEq,
+    Hash,
+    TryFromPrimitive,
+    IntoPrimitive,
+    strum::Display,
+)]
+pub enum MetagameEventState {
+    Started = 135,
+    Restarted = 136,
+    Canceled = 137,
+    Ended = 138,
+    ExperienceBonusChanged = 139,
+}
+
 #[repr(u16)]
 #[derive(
     FromStr,
@@ -135,9 +164,13 @@ pub type TeamID = Faction;
     FromRepr,
     PartialOrd,
     Ord,
+    Default,
 )]
 #[strum(ascii_case_insensitive)]
 pub enum WorldID {
+    /// Also used for worlds Census added after this list was written
+    #[default]
+    Unknown = 0,
     Jaeger = 19,
     Briggs = 25,
     Miller = 10,
@@ -145,6 +178,93 @@ pub enum WorldID {
     Connery = 1,
     Emerald = 17,
     Soltech = 40,
+    Genudine = 1000,
+    Ceres = 2000,
+}
+
+impl WorldID {
+    /// The environment the world is part of, PS4 worlds are numbered from 1000 (US) and 2000 (EU).
+    /// Unknown worlds are assumed to be PC worlds, like the environments that are connected to
+    /// when nothing is configured.
+    pub const fn environment(self) -> Environment {
+        match self {
+            Self::Unknown
+            | Self::Jaeger
+            | Self::Briggs
+            | Self::Miller
+            | Self::Cobalt
+            | Self::Connery
+            | Self::Emerald
+            | Self::Soltech => Environment::Ps2,
+            Self::Genudine => Environment::Ps2Ps4Us,
+            Self::Ceres => Environment::Ps2Ps4Eu,
+        }
+    }
+}
+
+/// A Census environment, each with its own worlds, realtime stream and REST namespace
+#[derive(
+    strum::EnumString,
+    Serialize,
+    Deserialize,
+    Copy,
+    Clone,
+    Eq,
+    Debug,
+    PartialEq,
+    Hash,
+    EnumIter,
+    strum::Display,
+    strum::IntoStaticStr,
+    PartialOrd,
+    Ord,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum Environment {
+    Ps2,
+    Ps2Ps4Us,
+    Ps2Ps4Eu,
+}
+
+impl Environment {
+    /// The namespace of the environment in the Census REST API
+    pub const fn namespace(self) -> &'static str {
+        match self {
+            Self::Ps2 => "ps2:v2",
+            Self::Ps2Ps4Us => "ps2ps4us:v2",
+            Self::Ps2Ps4Eu => "ps2ps4eu:v2",
+        }
+    }
+}
+
+/// The Census event servers reported in heartbeats, named after the world they serve
+#[derive(
+    strum::EnumString,
+    Serialize,
+    Deserialize,
+    Copy,
+    Clone,
+    Eq,
+    Debug,
+    PartialEq,
+    Hash,
+    EnumIter,
+    VariantNames,
+    strum::Display,
+    PartialOrd,
+    Ord,
+)]
+pub enum EventServerEndpoint {
+    Jaeger,
+    Briggs,
+    Miller,
+    Cobalt,
+    Connery,
+    Emerald,
+    Soltech,
+    Genudine,
+    Ceres,
 }
 
 pub type CharacterID = u64;
@@ -155,6 +275,11 @@ pub type OutfitID = u64;
 )]
 pub struct ZoneID(pub u32);
 
+impl ZoneID {
+    /// Used for players whose zone isn't known yet, for example right after logging in
+    pub const UNKNOWN: Self = Self(0);
+}
+
 #[derive(
     FromStr, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
 )]
@@ -163,6 +288,23 @@ pub struct DefinitionID(pub u16);
     FromStr, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
 )]
 pub struct InstanceID(pub u16);
+
+impl DefinitionID {
+    /// The definition of `ZoneID::UNKNOWN`
+    pub const UNKNOWN: Self = Self(0);
+}
+
+impl ZoneID {
+    /// The zone a zone ID is an instance of, shared by every instance of Koltyr, Sanctuary, etc.
+    pub fn definition(self) -> DefinitionID {
+        DefinitionID::from(self)
+    }
+
+    /// The instance of the zone, 0 for continents that aren't instanced
+    pub fn instance(self) -> InstanceID {
+        InstanceID::from(self)
+    }
+}
 pub type FacilityID = u32;
 pub type ExperienceID = u16;
 pub type VehicleID = u16;
@@ -0,0 +1,276 @@
+use crate::census::event::{
+    ContinentLock, ContinentUnlock, Death, FacilityControl, GainExperience, MetagameEvent,
+    PlayerFacilityCapture, PlayerFacilityDefend, PlayerLogin, PlayerLogout, VehicleDestroy,
+};
+use chrono::{DateTime, Utc};
+use metrics::counter;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{error, warn};
+
+/// How many messages are saved for every event type and field, enough to investigate a change
+/// by Census without filling the disk when every event of a type fails to parse
+const MAX_SAMPLES: u32 = 10;
+
+/// Why a message from Census couldn't be handled
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ParseFailure {
+    /// The event name of service messages, the message type of other messages
+    pub event: String,
+    /// The field that couldn't be parsed, empty when it couldn't be determined
+    pub field: String,
+    pub error: String,
+}
+
+impl ParseFailure {
+    /// Find the event and field a message failed to parse on. Only called for messages that
+    /// failed to parse, since it parses the message again.
+    pub fn new(text: &str, error: &serde_json::Error) -> Self {
+        let Ok(message) = serde_json::from_str::<Value>(text) else {
+            return Self {
+                event: "invalid_json".to_owned(),
+                field: String::new(),
+                error: error.to_string(),
+            };
+        };
+
+        let payload = &message["payload"];
+        let (event, field) = payload["event_name"].as_str().map_or_else(
+            || {
+                let message_type = message["type"].as_str().unwrap_or("unknown");
+                (message_type.to_owned(), String::new())
+            },
+            |event_name| {
+                let field = failing_field(event_name, payload).unwrap_or_default();
+                (event_name.to_owned(), field)
+            },
+        );
+
+        Self {
+            event,
+            field,
+            error: error.to_string(),
+        }
+    }
+
+    /// An event name that isn't known, which parses as `Event::Unknown`
+    pub fn unknown_event(text: &str) -> Self {
+        let event = serde_json::from_str::<Value>(text)
+            .ok()
+            .and_then(|message| message["payload"]["event_name"].as_str().map(str::to_owned))
+            .unwrap_or_default();
+
+        Self {
+            event,
+            field: "event_name".to_owned(),
+            error: "unknown event".to_owned(),
+        }
+    }
+
+    /// Count the failure and save the message when it is sampled
+    pub fn report(&self, text: &str, dead_letters: Option<&DeadLetters>) {
+        counter!(
+            "niumside_parse_failures",
+            "event" => self.event.clone(),
+            "field" => self.field.clone()
+        )
+        .increment(1);
+        warn!(
+            "Failed to parse {} message on field {:?}: {}",
+            self.event, self.field, self.error
+        );
+
+        if let Some(dead_letters) = dead_letters {
+            dead_letters.save(self, text);
+        }
+    }
+}
+
+/// Deserialize the payload as the event it claims to be, to find the field that fails
+fn failing_field(event_name: &str, payload: &Value) -> Option<String> {
+    fn path<T: DeserializeOwned>(payload: &Value) -> Option<String> {
+        let error = serde_path_to_error::deserialize::<_, T>(payload).err()?;
+        let path = error.path().to_string();
+
+        if path == "." {
+            // Missing fields are reported on the event itself
+            let message = error.inner().to_string();
+            let field = message.strip_prefix("missing field `")?.split('`').next()?;
+            return Some(field.to_owned());
+        }
+
+        Some(path)
+    }
+
+    match event_name {
+        "PlayerLogin" => path::<PlayerLogin>(payload),
+        "PlayerLogout" => path::<PlayerLogout>(payload),
+        "Death" => path::<Death>(payload),
+        "VehicleDestroy" => path::<VehicleDestroy>(payload),
+        "GainExperience" => path::<GainExperience>(payload),
+        "PlayerFacilityCapture" => path::<PlayerFacilityCapture>(payload),
+        "PlayerFacilityDefend" => path::<PlayerFacilityDefend>(payload),
+        "ContinentLock" => path::<ContinentLock>(payload),
+        "ContinentUnlock" => path::<ContinentUnlock>(payload),
+        "FacilityControl" => path::<FacilityControl>(payload),
+        "MetagameEvent" => path::<MetagameEvent>(payload),
+        _ => None,
+    }
+}
+
+#[derive(Serialize)]
+struct DeadLetter<'a> {
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    failure: &'a ParseFailure,
+    text: &'a str,
+}
+
+/// Saves a sample of the messages that failed to parse as NDJSON
+#[derive(Debug)]
+pub struct DeadLetters {
+    path: PathBuf,
+    samples: Mutex<HashMap<(String, String), u32>>,
+}
+
+impl DeadLetters {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            samples: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether another message of the event type and field should be saved
+    fn sample(&self, failure: &ParseFailure) -> bool {
+        self.samples.lock().map_or_else(
+            |_| {
+                error!("Failed to lock the dead letter samples");
+                false
+            },
+            |mut samples| {
+                let count = samples
+                    .entry((failure.event.clone(), failure.field.clone()))
+                    .or_default();
+                *count += 1;
+                *count <= MAX_SAMPLES
+            },
+        )
+    }
+
+    fn append(&self, dead_letter: &DeadLetter) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(dead_letter)?;
+        line.push(b'\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(&line)
+    }
+
+    /// Save a message that failed to parse, returns whether it was saved
+    pub fn save(&self, failure: &ParseFailure, text: &str) -> bool {
+        if !self.sample(failure) {
+            return false;
+        }
+
+        let dead_letter = DeadLetter {
+            timestamp: Utc::now(),
+            failure,
+            text,
+        };
+
+        if let Err(e) = self.append(&dead_letter) {
+            counter!("niumside_dead_letter_failed_writes").increment(1);
+            error!("Failed to save the message to {}: {e}", self.path.display());
+            return false;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::constants::WorldID;
+    use crate::census::event::Event;
+    use crate::census::CensusMessage;
+
+    const DEATH: &str = r#"{"payload":{"attacker_character_id":"5428010618015189713","attacker_fire_mode_id":"7401","attacker_loadout_id":"6","attacker_team_id":"2","attacker_vehicle_id":"0","attacker_weapon_id":"7169","character_id":"5429573939285739921","character_loadout_id":"20","event_name":"Death","is_critical":"0","is_headshot":"1","team_id":"1","timestamp":"1728117291","vehicle_id":"0","world_id":"10","zone_id":"65538"},"service":"event","type":"serviceMessage"}"#;
+
+    fn parse_failure(text: &str) -> ParseFailure {
+        let error = serde_json::from_str::<CensusMessage>(text).unwrap_err();
+        ParseFailure::new(text, &error)
+    }
+
+    #[test]
+    fn test_unknown_world_is_parsed() {
+        let text = DEATH.replace(r#""world_id":"10""#, r#""world_id":"99""#);
+        let message = serde_json::from_str::<CensusMessage>(&text).unwrap();
+
+        let CensusMessage::ServiceMessage {
+            payload: Event::Death(death),
+        } = message
+        else {
+            panic!("Expected a Death event");
+        };
+        assert_eq!(death.world_id, WorldID::Unknown);
+    }
+
+    #[test]
+    fn test_parse_failure_finds_field() {
+        let failure = parse_failure(&DEATH.replace(
+            r#""character_id":"5429573939285739921""#,
+            r#""character_id":"x""#,
+        ));
+        assert_eq!(failure.event, "Death");
+        assert_eq!(failure.field, "character_id");
+
+        let failure = parse_failure(&DEATH.replace(r#""zone_id":"65538""#, r#""zone":"2""#));
+        assert_eq!(failure.field, "zone_id");
+
+        let failure = parse_failure(r#"{"type":"somethingNew"}"#);
+        assert_eq!(failure.event, "somethingNew");
+        assert_eq!(failure.field, "");
+    }
+
+    #[test]
+    fn test_dead_letters_are_sampled() {
+        let path = std::env::temp_dir().join(format!(
+            "niumside-dead-letters-{}.ndjson",
+            std::process::id()
+        ));
+        let dead_letters = DeadLetters::new(&path);
+        let text = DEATH.replace(
+            r#""character_id":"5429573939285739921""#,
+            r#""character_id":"x""#,
+        );
+        let failure = parse_failure(&text);
+
+        for _ in 0..MAX_SAMPLES {
+            assert!(dead_letters.save(&failure, &text));
+        }
+        assert!(!dead_letters.save(&failure, &text));
+        assert!(dead_letters.save(&ParseFailure::unknown_event(&text), &text));
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<Value> = saved
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0]["field"], "character_id");
+        assert_eq!(lines[0]["text"], text);
+        assert_eq!(lines[10]["field"], "event_name");
+    }
+}
@@ -0,0 +1,177 @@
+//! Drops events that were already received from another realtime upstream
+
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::error;
+
+/// How long an event is remembered. Upstreams relay the same Census stream, so they deliver an
+/// event within seconds of each other.
+const DEDUP_WINDOW: Duration = Duration::from_secs(30);
+
+/// Whether an event was seen before
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Not seen before, or repeated by the upstream that delivered it first. Census can send
+    /// the same payload twice, an upstream only duplicates what another one relayed.
+    First,
+    /// Already delivered by another upstream, this long ago
+    Duplicate(Duration),
+}
+
+#[derive(Debug, Default)]
+struct Seen {
+    /// When and from which upstream each hash was first seen
+    first_seen: HashMap<u64, (Instant, String)>,
+    /// The hashes in the order they were first seen, to forget them after `DEDUP_WINDOW`
+    order: VecDeque<(Instant, u64)>,
+}
+
+impl Seen {
+    fn prune(&mut self, now: Instant) {
+        while let Some((seen, hash)) = self.order.front().copied() {
+            if now.saturating_duration_since(seen) <= DEDUP_WINDOW {
+                break;
+            }
+            self.first_seen.remove(&hash);
+            self.order.pop_front();
+        }
+    }
+}
+
+/// Remembers the events of the last `DEDUP_WINDOW`, shared by the clients of every upstream
+#[derive(Debug, Default)]
+pub struct Deduplicator {
+    seen: Mutex<Seen>,
+}
+
+impl Deduplicator {
+    pub fn check(&self, hash: u64, upstream: &str, now: Instant) -> Delivery {
+        let Ok(mut seen) = self.seen.lock() else {
+            error!("Failed to lock the realtime deduplicator");
+            return Delivery::First;
+        };
+        seen.prune(now);
+
+        if let Some((first_seen, first_upstream)) = seen.first_seen.get(&hash) {
+            if first_upstream == upstream {
+                return Delivery::First;
+            }
+            return Delivery::Duplicate(now.saturating_duration_since(*first_seen));
+        }
+
+        seen.first_seen.insert(hash, (now, upstream.to_owned()));
+        seen.order.push_back((now, hash));
+        Delivery::First
+    }
+}
+
+/// Hash a value with the keys of objects in sorted order, so upstreams that order the fields of
+/// a payload differently still produce the same hash
+fn hash_value(value: &Value, hasher: &mut impl Hasher) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_unstable_by_key(|(key, _)| *key);
+            for (key, value) in entries {
+                key.hash(hasher);
+                hash_value(value, hasher);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                hash_value(value, hasher);
+            }
+        }
+        value => value.to_string().hash(hasher),
+    }
+}
+
+/// The hash of the payload of a service message, `None` for other messages such as heartbeats
+pub fn payload_hash(message: &Value) -> Option<u64> {
+    if message["type"] != "serviceMessage" {
+        return None;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    hash_value(&message["payload"], &mut hasher);
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn message_hash(text: &str) -> Option<u64> {
+        payload_hash(&serde_json::from_str(text).unwrap())
+    }
+
+    #[test]
+    fn test_payload_hash_ignores_field_order() {
+        let hash = message_hash(
+            r#"{"payload":{"character_id":"1","event_name":"PlayerLogin","world_id":"10"},"service":"event","type":"serviceMessage"}"#,
+        )
+        .unwrap();
+        let reordered = message_hash(
+            r#"{"type":"serviceMessage","payload":{"world_id":"10","event_name":"PlayerLogin","character_id":"1"},"service":"event"}"#,
+        )
+        .unwrap();
+        let other = message_hash(
+            r#"{"payload":{"character_id":"2","event_name":"PlayerLogin","world_id":"10"},"service":"event","type":"serviceMessage"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(hash, reordered);
+        assert_ne!(hash, other);
+        assert_eq!(
+            message_hash(r#"{"online":{},"service":"event","type":"heartbeat"}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_duplicates_are_forgotten_after_window() {
+        let deduplicator = Deduplicator::default();
+        let now = Instant::now();
+
+        assert_eq!(deduplicator.check(1, "first", now), Delivery::First);
+        assert_eq!(
+            deduplicator.check(1, "second", now + Duration::from_secs(2)),
+            Delivery::Duplicate(Duration::from_secs(2))
+        );
+        assert_eq!(
+            deduplicator.check(2, "second", now + Duration::from_secs(2)),
+            Delivery::First
+        );
+
+        let later = now + DEDUP_WINDOW + Duration::from_secs(1);
+        assert_eq!(deduplicator.check(1, "second", later), Delivery::First);
+        assert_eq!(
+            deduplicator.check(2, "first", later),
+            Delivery::Duplicate(Duration::from_secs(29))
+        );
+    }
+
+    #[test]
+    fn test_repeats_of_the_same_upstream_are_kept() {
+        let deduplicator = Deduplicator::default();
+        let now = Instant::now();
+
+        assert_eq!(deduplicator.check(1, "first", now), Delivery::First);
+        assert_eq!(
+            deduplicator.check(1, "first", now + Duration::from_secs(1)),
+            Delivery::First
+        );
+        // Both copies of the other upstream repeat what the first one delivered
+        for _ in 0..2 {
+            assert_eq!(
+                deduplicator.check(1, "second", now + Duration::from_secs(2)),
+                Delivery::Duplicate(Duration::from_secs(2))
+            );
+        }
+    }
+}
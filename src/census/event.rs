@@ -2,9 +2,9 @@ use crate::census::constants::{
     CharacterID, ExperienceID, FacilityID, Faction, FiremodeID, Loadout, OutfitID, VehicleID,
     WeaponID, WorldID, ZoneID,
 };
-use crate::census::utils::deserialize_from_str_custom_impl;
 use crate::census::utils::{
-    de_bool_from_str_int, deserialize_duration_from_str, deserialize_from_str, serialize_duration,
+    de_bool_from_str_int, deserialize_duration_from_str, deserialize_from_str,
+    deserialize_or_unknown, serialize_duration,
 };
 use std::fmt::{Display, Formatter};
 
@@ -80,6 +80,44 @@ impl serde::Serialize for EventNames {
     }
 }
 
+impl std::str::FromStr for EventNames {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(experience_id) = s.strip_prefix("GainExperience_experience_id_") {
+            return Ok(Self::GainExperienceId(experience_id.parse()?));
+        }
+
+        match s {
+            "AchievementEarned" => Ok(Self::AchievementEarned),
+            "BattleRankUp" => Ok(Self::BattleRankUp),
+            "Death" => Ok(Self::Death),
+            "ItemAdded" => Ok(Self::ItemAdded),
+            "SkillAdded" => Ok(Self::SkillAdded),
+            "VehicleDestroy" => Ok(Self::VehicleDestroy),
+            "GainExperience" => Ok(Self::GainExperience),
+            "PlayerFacilityCapture" => Ok(Self::PlayerFacilityCapture),
+            "PlayerFacilityDefend" => Ok(Self::PlayerFacilityDefend),
+            "ContinentLock" => Ok(Self::ContinentLock),
+            "ContinentUnlock" => Ok(Self::ContinentUnlock),
+            "FacilityControl" => Ok(Self::FacilityControl),
+            "MetagameEvent" => Ok(Self::MetagameEvent),
+            "PlayerLogin" => Ok(Self::PlayerLogin),
+            "PlayerLogout" => Ok(Self::PlayerLogout),
+            _ => Err(anyhow::anyhow!("Invalid event name: {s}")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for EventNames {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserialize_from_str(deserializer)
+    }
+}
+
 #[derive(Deserialize, PartialEq, Debug, Clone)]
 #[serde(tag = "event_name")]
 #[allow(clippy::enum_variant_names)]
@@ -99,6 +137,33 @@ pub enum Event {
     AchievementEarned,
     SkillAdded,
     BattleRankUp,
+    /// An event Census added after this list was written
+    #[serde(other)]
+    Unknown,
+}
+
+impl Event {
+    /// The world the event happened on, `None` for events that don't include one
+    pub const fn world_id(&self) -> Option<WorldID> {
+        match self {
+            Self::PlayerLogin(PlayerLogin { world_id, .. })
+            | Self::PlayerLogout(PlayerLogout { world_id, .. })
+            | Self::Death(Death { world_id, .. })
+            | Self::VehicleDestroy(VehicleDestroy { world_id, .. })
+            | Self::GainExperience(GainExperience { world_id, .. })
+            | Self::PlayerFacilityCapture(PlayerFacilityCapture { world_id, .. })
+            | Self::PlayerFacilityDefend(PlayerFacilityDefend { world_id, .. })
+            | Self::ContinentLock(ContinentLock { world_id, .. })
+            | Self::ContinentUnlock(ContinentUnlock { world_id, .. })
+            | Self::FacilityControl(FacilityControl { world_id, .. })
+            | Self::MetagameEvent(MetagameEvent { world_id, .. }) => Some(*world_id),
+            Self::ItemAdded
+            | Self::AchievementEarned
+            | Self::SkillAdded
+            | Self::BattleRankUp
+            | Self::Unknown => None,
+        }
+    }
 }
 
 impl Display for Event {
@@ -149,6 +214,9 @@ impl Display for Event {
             Self::BattleRankUp => {
                 write!(f, "BattleRankUp")
             }
+            Self::Unknown => {
+                write!(f, "Unknown")
+            }
         }
     }
 }
@@ -162,7 +230,7 @@ pub struct PlayerLogin {
         serialize_with = "TimestampMilliSeconds::<i64>::serialize_as"
     )]
     pub timestamp: DateTime<Utc>,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub world_id: WorldID,
 }
 
@@ -175,7 +243,7 @@ pub struct PlayerLogout {
         serialize_with = "TimestampMilliSeconds::<i64>::serialize_as"
     )]
     pub timestamp: DateTime<Utc>,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub world_id: WorldID,
 }
 
@@ -185,7 +253,7 @@ pub struct Death {
     pub attacker_character_id: CharacterID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub attacker_fire_mode_id: FiremodeID,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub attacker_loadout_id: Loadout,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub attacker_vehicle_id: VehicleID,
@@ -193,7 +261,7 @@ pub struct Death {
     pub attacker_weapon_id: WeaponID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub character_id: CharacterID,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub character_loadout_id: Loadout,
     #[serde(deserialize_with = "de_bool_from_str_int")]
     pub is_headshot: bool,
@@ -204,7 +272,7 @@ pub struct Death {
     pub timestamp: DateTime<Utc>,
     #[serde(default, deserialize_with = "deserialize_from_str")]
     pub vehicle_id: VehicleID,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub world_id: WorldID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub zone_id: ZoneID,
@@ -214,7 +282,7 @@ pub struct Death {
 pub struct VehicleDestroy {
     #[serde(deserialize_with = "deserialize_from_str")]
     pub attacker_character_id: CharacterID,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub attacker_loadout_id: Loadout,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub attacker_vehicle_id: VehicleID,
@@ -224,7 +292,7 @@ pub struct VehicleDestroy {
     pub character_id: CharacterID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub facility_id: FacilityID,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub faction_id: Faction,
     #[serde(
         deserialize_with = "TimestampSeconds::<String>::deserialize_as",
@@ -233,7 +301,7 @@ pub struct VehicleDestroy {
     pub timestamp: DateTime<Utc>,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub vehicle_id: VehicleID,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub world_id: WorldID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub zone_id: ZoneID,
@@ -290,6 +358,51 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_event_names_round_trip() {
+        use crate::census::event::EventNames;
+
+        for event_name in [EventNames::PlayerLogin, EventNames::GainExperienceId(140)] {
+            let serialized = serde_json::to_string(&event_name).unwrap();
+            let deserialized: EventNames = serde_json::from_str(&serialized).unwrap();
+            assert_eq!(deserialized, event_name);
+        }
+
+        assert!(serde_json::from_str::<EventNames>(r#""NotAnEvent""#).is_err());
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_unknown_event_and_loadout() {
+        use crate::census::event::Death;
+
+        let event: Event =
+            serde_json::from_str(r#"{"event_name":"NotAnEvent","world_id":"10"}"#).unwrap();
+        assert_eq!(event, Event::Unknown);
+
+        let event: Event = serde_json::from_str(r#"{"attacker_character_id":"5428010618015189713","attacker_fire_mode_id":"7401","attacker_loadout_id":"99","attacker_team_id":"2","attacker_vehicle_id":"0","attacker_weapon_id":"7169","character_id":"5429573939285739921","character_loadout_id":"20","event_name":"Death","is_critical":"0","is_headshot":"1","team_id":"1","timestamp":"1728117291","vehicle_id":"0","world_id":"10","zone_id":"2"}"#).unwrap();
+        let Event::Death(Death {
+            attacker_loadout_id,
+            character_loadout_id,
+            ..
+        }) = event
+        else {
+            panic!("Unexpected event type");
+        };
+        assert_eq!(attacker_loadout_id, Loadout::Unknown);
+        assert_eq!(character_loadout_id, Loadout::VSHeavyAssault);
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_unknown_world() {
+        let event: Event = serde_json::from_str(r#"{"character_id":"5429573939285739921","event_name":"PlayerLogin","timestamp":"1728117291","world_id":"99"}"#).unwrap();
+
+        assert_eq!(event.world_id(), Some(WorldID::Unknown));
+        assert_eq!(Event::SkillAdded.world_id(), None);
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
@@ -298,7 +411,7 @@ pub struct GainExperience {
     pub character_id: CharacterID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub experience_id: ExperienceID,
-    #[serde(deserialize_with = "deserialize_from_str_custom_impl")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub loadout_id: Loadout,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub other_id: CharacterID,
@@ -307,13 +420,13 @@ pub struct GainExperience {
         serialize_with = "TimestampMilliSeconds::<i64>::serialize_as"
     )]
     pub timestamp: DateTime<Utc>,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub world_id: WorldID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub zone_id: ZoneID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub amount: u16,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub team_id: Faction,
 }
 
@@ -323,13 +436,14 @@ pub struct PlayerFacilityCapture {
     pub character_id: CharacterID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub facility_id: FacilityID,
+    #[serde(deserialize_with = "deserialize_from_str")]
     pub outfit_id: OutfitID,
     #[serde(
         deserialize_with = "TimestampSeconds::<String>::deserialize_as",
         serialize_with = "TimestampMilliSeconds::<i64>::serialize_as"
     )]
     pub timestamp: DateTime<Utc>,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub world_id: WorldID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub zone_id: ZoneID,
@@ -341,13 +455,14 @@ pub struct PlayerFacilityDefend {
     pub character_id: CharacterID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub facility_id: FacilityID,
+    #[serde(deserialize_with = "deserialize_from_str")]
     pub outfit_id: OutfitID,
     #[serde(
         deserialize_with = "TimestampSeconds::<String>::deserialize_as",
         serialize_with = "TimestampMilliSeconds::<i64>::serialize_as"
     )]
     pub timestamp: DateTime<Utc>,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub world_id: WorldID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub zone_id: ZoneID,
@@ -362,17 +477,18 @@ pub struct FacilityControl {
     pub duration_held: Duration,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub facility_id: FacilityID,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub new_faction_id: Faction,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub old_faction_id: Faction,
+    #[serde(deserialize_with = "deserialize_from_str")]
     pub outfit_id: OutfitID,
     #[serde(
         deserialize_with = "TimestampSeconds::<String>::deserialize_as",
         serialize_with = "TimestampMilliSeconds::<i64>::serialize_as"
     )]
     pub timestamp: DateTime<Utc>,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub world_id: WorldID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub zone_id: ZoneID,
@@ -385,13 +501,13 @@ pub struct ContinentLock {
         serialize_with = "TimestampMilliSeconds::<i64>::serialize_as"
     )]
     pub timestamp: DateTime<Utc>,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub world_id: WorldID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub zone_id: ZoneID,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub triggering_faction: Faction,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub previous_faction: Faction,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub vs_population: u16,
@@ -410,13 +526,13 @@ pub struct ContinentUnlock {
         serialize_with = "TimestampMilliSeconds::<i64>::serialize_as"
     )]
     pub timestamp: DateTime<Utc>,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub world_id: WorldID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub zone_id: ZoneID,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub triggering_faction: Faction,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub previous_faction: Faction,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub vs_population: u16,
@@ -436,7 +552,7 @@ pub struct MetagameEvent {
         serialize_with = "TimestampMilliSeconds::<i64>::serialize_as"
     )]
     pub timestamp: DateTime<Utc>,
-    #[serde(deserialize_with = "deserialize_from_str")]
+    #[serde(deserialize_with = "deserialize_or_unknown")]
     pub world_id: WorldID,
     #[serde(deserialize_with = "deserialize_from_str")]
     pub instance_id: u32,
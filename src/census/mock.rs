@@ -0,0 +1,290 @@
+//! A local stand-in for Census, so the realtime and REST clients can be tested offline
+
+use crate::census::constants::Environment;
+use crate::census::realtime::RealtimeClientConfig;
+use crate::census::rest::client::{CensusCollections, CensusRequestType, CensusRestClient};
+use futures::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc};
+use tokio_tungstenite::tungstenite::Message;
+use url::Url;
+
+pub const SERVICE_ID: &str = "example";
+/// How long to wait for the client under test before failing the test
+const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+const CONNECTED: &str = r#"{"connected":"true","service":"push","type":"connectionStateChanged"}"#;
+
+/// The JSON bodies to respond with, by request path with or without its query
+type Routes = Arc<Mutex<HashMap<String, String>>>;
+
+pub struct MockCensus {
+    pub realtime_url: Url,
+    pub census_url: Url,
+    routes: Routes,
+    push: broadcast::Sender<String>,
+    received: mpsc::UnboundedReceiver<String>,
+}
+
+impl MockCensus {
+    /// Start the push server and the REST stub on random local ports
+    pub async fn start() -> Self {
+        let realtime = bind().await;
+        let rest = bind().await;
+        let realtime_url = local_url("ws", &realtime, "/streaming");
+        let census_url = local_url("http", &rest, "/");
+
+        let routes = Routes::default();
+        let (push, _) = broadcast::channel(100);
+        let (received_sender, received) = mpsc::unbounded_channel();
+
+        tokio::spawn(serve_realtime(realtime, push.clone(), received_sender));
+        tokio::spawn(serve_rest(rest, routes.clone()));
+
+        Self {
+            realtime_url,
+            census_url,
+            routes,
+            push,
+            received,
+        }
+    }
+
+    pub fn rest_client(&self) -> CensusRestClient {
+        CensusRestClient {
+            census_url: self.census_url.clone(),
+            service_id: SERVICE_ID.to_owned(),
+            environment: Environment::Ps2,
+        }
+    }
+
+    pub fn realtime_config(&self) -> RealtimeClientConfig {
+        RealtimeClientConfig {
+            environment: Environment::Ps2,
+            service_id: SERVICE_ID.to_owned(),
+            realtime_url: Some(self.realtime_url.clone()),
+            worlds: Vec::new(),
+            event_names: None,
+            characters: None,
+        }
+    }
+
+    /// Respond to requests for `path`. A path with a query only matches requests with exactly
+    /// that query, which take precedence over the path without a query.
+    pub fn respond(&self, path: &str, body: &serde_json::Value) {
+        if let Ok(mut routes) = self.routes.lock() {
+            routes.insert(path.to_owned(), body.to_string());
+        }
+    }
+
+    /// Respond to `get` requests for a collection, such as `census/get/ps2:v2/character`
+    pub fn respond_collection(
+        &self,
+        collection: CensusCollections,
+        query: Option<&str>,
+        body: &serde_json::Value,
+    ) {
+        let Ok(url) = self
+            .rest_client()
+            .get_request_url(CensusRequestType::Get, collection)
+        else {
+            return;
+        };
+
+        match query {
+            Some(query) => self.respond(&format!("{}?{query}", url.path()), body),
+            None => self.respond(url.path(), body),
+        }
+    }
+
+    /// Send a text frame to every connected client
+    pub fn push(&self, text: &str) {
+        // Frames pushed while no client is connected are dropped, like Census does
+        let _ = self.push.send(text.to_owned());
+    }
+
+    pub fn push_heartbeat(&self, online: &[(&str, bool)]) {
+        let online: serde_json::Map<String, serde_json::Value> = online
+            .iter()
+            .map(|(endpoint, online)| ((*endpoint).to_owned(), online.to_string().into()))
+            .collect();
+
+        self.push(
+            &serde_json::json!({"online": online, "service": "event", "type": "heartbeat"})
+                .to_string(),
+        );
+    }
+
+    /// Send an event, `payload` being the event as found in the `payload` of a service message
+    pub fn push_event(&self, payload: &str) {
+        self.push(&format!(
+            r#"{{"payload":{payload},"service":"event","type":"serviceMessage"}}"#
+        ));
+    }
+
+    /// Wait for the next text frame a client sent
+    pub async fn next_received(&mut self) -> Option<String> {
+        tokio::time::timeout(RECEIVE_TIMEOUT, self.received.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Wait for a client to subscribe, after which pushed frames reach it
+    pub async fn next_subscription(&mut self) -> Option<serde_json::Value> {
+        while let Some(text) = self.next_received().await {
+            let Ok(action) = serde_json::from_str::<serde_json::Value>(&text) else {
+                continue;
+            };
+            if action["action"] == "subscribe" {
+                return Some(action);
+            }
+        }
+
+        None
+    }
+}
+
+async fn bind() -> TcpListener {
+    match TcpListener::bind("127.0.0.1:0").await {
+        Ok(listener) => listener,
+        Err(e) => panic!("Unable to bind the mock Census server: {e}"),
+    }
+}
+
+fn local_url(scheme: &str, listener: &TcpListener, path: &str) -> Url {
+    let address = match listener.local_addr() {
+        Ok(address) => address,
+        Err(e) => panic!("Unable to get the address of the mock Census server: {e}"),
+    };
+
+    match Url::parse(&format!("{scheme}://{address}{path}")) {
+        Ok(url) => url,
+        Err(e) => panic!("Invalid mock Census URL: {e}"),
+    }
+}
+
+/// What Census echoes after a subscribe action
+fn subscription_echo(action: &serde_json::Value) -> Option<String> {
+    if action["action"] != "subscribe" {
+        return None;
+    }
+
+    Some(
+        serde_json::json!({
+            "subscription": {
+                "characterCount": 0,
+                "eventNames": action["eventNames"],
+                "logicalAndCharactersWithWorlds": action["logicalAndCharactersWithWorlds"],
+                "worlds": action["worlds"],
+            }
+        })
+        .to_string(),
+    )
+}
+
+async fn serve_realtime(
+    listener: TcpListener,
+    push: broadcast::Sender<String>,
+    received: mpsc::UnboundedSender<String>,
+) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(realtime_connection(
+            stream,
+            push.subscribe(),
+            received.clone(),
+        ));
+    }
+}
+
+async fn realtime_connection(
+    stream: TcpStream,
+    mut push: broadcast::Receiver<String>,
+    received: mpsc::UnboundedSender<String>,
+) {
+    let Ok(websocket) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let (mut sink, mut stream) = websocket.split();
+
+    if sink
+        .send(Message::Text(CONNECTED.to_owned()))
+        .await
+        .is_err()
+    {
+        return;
+    }
+
+    loop {
+        let frame = tokio::select! {
+            pushed = push.recv() => match pushed {
+                Ok(text) => Some(text),
+                Err(broadcast::error::RecvError::Lagged(_)) => None,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let echo = serde_json::from_str(&text)
+                        .ok()
+                        .and_then(|action| subscription_echo(&action));
+                    let _ = received.send(text);
+                    echo
+                }
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => None,
+            },
+        };
+
+        if let Some(frame) = frame {
+            if sink.send(Message::Text(frame)).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+async fn serve_rest(listener: TcpListener, routes: Routes) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(rest_request(stream, routes.clone()));
+    }
+}
+
+/// Answer a single HTTP/1.1 request and close the connection
+async fn rest_request(mut stream: TcpStream, routes: Routes) {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(read) => request.extend_from_slice(&buffer[..read]),
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let target = request.split_whitespace().nth(1).unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default();
+
+    let body = routes
+        .lock()
+        .ok()
+        .and_then(|routes| routes.get(target).or_else(|| routes.get(path)).cloned());
+    let (status, body) = body.map_or_else(
+        || {
+            (
+                "404 Not Found",
+                serde_json::json!({"error": format!("No mock response for {target}")}).to_string(),
+            )
+        },
+        |body| ("200 OK", body),
+    );
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
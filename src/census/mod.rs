@@ -1,20 +1,27 @@
 pub mod constants;
+pub mod dead_letter;
+pub mod dedup;
 pub mod event;
+#[cfg(test)]
+pub mod mock;
 pub mod realtime;
+pub mod recording;
 
 pub mod rest;
+pub mod server_health;
 pub mod structs;
 mod subscription;
+pub mod territory;
 mod utils;
 
 use event::Event;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use server_health::EventServerStatus;
 use subscription::SubscriptionSettings;
 use subscription::{CharacterSubscription, EventSubscription, WorldSubscription};
 use url::Url;
-use utils::{deserialize_from_str, serialize_optional_bool};
+use utils::{deserialize_event_server_status, deserialize_from_str, serialize_optional_bool};
 
 lazy_static! {
     pub static ref REALTIME_URL: Url = match Url::parse("wss://push.planetside2.com/streaming") {
@@ -66,7 +73,7 @@ pub enum Action {
     },
 }
 
-#[derive(Deserialize, PartialEq, Eq, Debug)]
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Subscription {
     pub character_count: u64,
@@ -75,6 +82,12 @@ pub struct Subscription {
     pub worlds: Vec<String>,
 }
 
+/// What Census sends after a subscribe action, which unlike other messages has no `type`
+#[derive(Deserialize, PartialEq, Eq, Debug)]
+pub struct SubscriptionAcknowledgement {
+    pub subscription: Subscription,
+}
+
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum CensusMessage {
@@ -83,8 +96,8 @@ pub enum CensusMessage {
         connected: bool,
     },
     Heartbeat {
-        // TODO: EventServerEndpoint / WorldId / request::WorldIds -> bool
-        online: HashMap<String, String>,
+        #[serde(deserialize_with = "deserialize_event_server_status")]
+        online: EventServerStatus,
     },
     ServiceMessage {
         payload: Event,
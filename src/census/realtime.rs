@@ -1,34 +1,88 @@
-use crate::active_players;
-use crate::census::event::EventNames;
+use crate::census::constants::{CharacterID, Environment, WorldID};
+use crate::census::dead_letter::{DeadLetters, ParseFailure};
+use crate::census::dedup::{self, Deduplicator, Delivery};
+use crate::census::event::{Event, EventNames};
+use crate::census::recording::{self, RecordingSender};
+use crate::census::server_health::{self, ActiveSubscription};
 use crate::census::subscription::{
     CharacterSubscription, EventSubscription, SubscriptionSettings, WorldSubscription,
 };
 use crate::census::Action;
-use crate::census::{CensusMessage, REALTIME_URL};
-use crate::event_handlers::receive_events;
+use crate::census::{CensusMessage, Subscription, SubscriptionAcknowledgement, REALTIME_URL};
+use crate::event_handlers::pipeline::EventPipeline;
+use crate::event_handlers::{MetagameTracker, PopulationTracker};
 use async_trait::async_trait;
-use ezsockets::{ClientConfig, CloseCode, CloseFrame};
-use metrics::counter;
-use std::thread;
-use tracing::{debug, error, info};
+use chrono::Utc;
+use ezsockets::client::ClientCloseMode;
+use ezsockets::{ClientConfig, CloseCode, CloseFrame, WSError};
+use metrics::{counter, histogram};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 use url::Url;
 
+/// Delay before the first reconnect attempt, doubled after every connection that didn't receive events
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_mins(5);
+/// Census sends a heartbeat every 30 seconds, so missing a few means the connection is gone
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(90);
+/// Census is known to keep sending heartbeats while events stopped arriving. Only checked for
+/// subscriptions that are never quiet for this long, see `event_timeout`.
+const EVENT_TIMEOUT: Duration = Duration::from_mins(5);
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(10);
+/// How often a subscription is sent again when Census acknowledges less than was requested
+const MAX_SUBSCRIPTION_RETRIES: u32 = 3;
+
 struct CensusRealtimeClient {
     client: ezsockets::Client<Self>,
-    subscription: SubscriptionSettings,
-    state: State,
+    handler: MessageHandler,
+    /// Whether a subscription was already sent on an earlier connection
+    resubscribe: bool,
 }
 
 #[derive(Clone)]
 pub struct State {
-    pub active_players: active_players::ActivePlayerDb,
+    pub events: EventPipeline,
+    pub server_health: server_health::ServerHealthDb,
+    /// Receives every text frame from Census when the stream is being recorded
+    pub recording: Option<RecordingSender>,
+    /// Saves a sample of the messages that failed to parse
+    pub dead_letters: Option<Arc<DeadLetters>>,
+    /// Drops the events already received from another upstream, only set when connecting to
+    /// several upstreams
+    pub deduplicator: Option<Arc<Deduplicator>>,
+}
+
+/// Handles the messages of a single connection, whether they come from Census or a recording
+pub struct MessageHandler {
+    state: State,
+    health: Arc<Mutex<ConnectionHealth>>,
+    /// The host the messages come from, used to label the upstream metrics
+    upstream: String,
+    /// Not set when replaying a recording, which has no subscription to check
+    connection: Option<Connection>,
+}
+
+/// What was requested on a realtime connection, to check the acknowledged subscription against
+#[derive(Debug, Clone)]
+struct Connection {
+    environment: Environment,
+    subscription: SubscriptionSettings,
 }
 
 #[derive(Debug, Clone)]
 pub struct RealtimeClientConfig {
-    pub environment: String,
+    pub environment: Environment,
     pub service_id: String,
     pub realtime_url: Option<Url>,
+    /// The worlds to subscribe to, all worlds when empty
+    pub worlds: Vec<WorldID>,
+    /// The events to subscribe to, `default_event_names` when not set
+    pub event_names: Option<Vec<EventNames>>,
+    /// The characters to subscribe to, all characters when not set
+    pub characters: Option<Vec<CharacterID>>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -39,21 +93,113 @@ pub enum RealtimeError {
     SerdeError(#[from] serde_json::Error),
 }
 
+/// Why a connection to Census was given up on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectionLoss {
+    Closed,
+    Disconnected,
+    MissingHeartbeat,
+    NoEvents,
+}
+
+impl ConnectionLoss {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::Disconnected => "disconnected",
+            Self::MissingHeartbeat => "missing_heartbeat",
+            Self::NoEvents => "no_events",
+        }
+    }
+}
+
+/// What was received on a single connection, used to detect a stale stream
+#[derive(Debug, Clone, Copy)]
+struct ConnectionHealth {
+    started: Instant,
+    last_heartbeat: Option<Instant>,
+    last_event: Option<Instant>,
+    /// How long the connection may go without events, not checked when `None`
+    event_timeout: Option<Duration>,
+    subscribed: bool,
+    /// How often the subscription was sent again because Census acknowledged less
+    subscription_retries: u32,
+    disconnected: bool,
+}
+
+impl ConnectionHealth {
+    const fn new(started: Instant, event_timeout: Option<Duration>) -> Self {
+        Self {
+            started,
+            last_heartbeat: None,
+            last_event: None,
+            event_timeout,
+            subscribed: false,
+            subscription_retries: 0,
+            disconnected: false,
+        }
+    }
+
+    /// Check whether the connection should be replaced. Timeouts count from the start of the
+    /// connection until the first heartbeat or event arrives.
+    fn check(&self, now: Instant) -> Option<ConnectionLoss> {
+        let since =
+            |last: Option<Instant>| now.saturating_duration_since(last.unwrap_or(self.started));
+
+        if self.disconnected {
+            Some(ConnectionLoss::Disconnected)
+        } else if since(self.last_heartbeat) > HEARTBEAT_TIMEOUT {
+            Some(ConnectionLoss::MissingHeartbeat)
+        } else if self
+            .event_timeout
+            .is_some_and(|timeout| since(self.last_event) > timeout)
+        {
+            Some(ConnectionLoss::NoEvents)
+        } else {
+            None
+        }
+    }
+}
+
+/// Exponential backoff between reconnect attempts
+#[derive(Debug, Default)]
+struct Backoff {
+    attempts: u32,
+}
+
+impl Backoff {
+    fn next_delay(&mut self) -> Duration {
+        let delay = RECONNECT_INITIAL_DELAY
+            .saturating_mul(2_u32.saturating_pow(self.attempts))
+            .min(RECONNECT_MAX_DELAY);
+        self.attempts = self.attempts.saturating_add(1);
+        delay
+    }
+
+    const fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+fn update_health(health: &Mutex<ConnectionHealth>, update: impl FnOnce(&mut ConnectionHealth)) {
+    health.lock().map_or_else(
+        |_| error!("Failed to lock the realtime connection health"),
+        |mut guard| update(&mut guard),
+    );
+}
+
 #[async_trait]
 impl ezsockets::ClientExt for CensusRealtimeClient {
     type Call = ();
 
     async fn on_text(&mut self, text: String) -> Result<(), ezsockets::Error> {
         // info!("received message: {text}");
-        let parsed_message: Result<CensusMessage, serde_json::Error> = serde_json::from_str(&text);
-        match parsed_message {
-            Ok(message) => handle_census_msg(
-                &self.client,
-                &self.subscription,
-                self.state.clone(),
-                message,
-            )?,
-            Err(error) => error!("Failed to parse message: {text} - {error}"),
+        if let Some(recording) = &self.handler.state.recording {
+            recording::record(recording, &text);
+        }
+
+        if self.handler.handle_text(&text) {
+            self.subscribe()?;
         }
 
         Ok(())
@@ -73,31 +219,225 @@ impl ezsockets::ClientExt for CensusRealtimeClient {
         info!("connected");
         Ok(())
     }
+
+    // Reconnecting is left to `client`, so it can back off and start with a fresh subscription
+    async fn on_connect_fail(
+        &mut self,
+        error: WSError,
+    ) -> Result<ClientCloseMode, ezsockets::Error> {
+        warn!("Failed to connect to Census: {error}");
+        Ok(ClientCloseMode::Close)
+    }
+
+    async fn on_close(
+        &mut self,
+        frame: Option<CloseFrame>,
+    ) -> Result<ClientCloseMode, ezsockets::Error> {
+        warn!("Census closed the connection: {frame:?}");
+        Ok(ClientCloseMode::Close)
+    }
+
+    async fn on_disconnect(&mut self) -> Result<ClientCloseMode, ezsockets::Error> {
+        warn!("Lost the connection to Census");
+        Ok(ClientCloseMode::Close)
+    }
 }
 
-fn handle_census_msg(
-    client: &ezsockets::Client<CensusRealtimeClient>,
-    subscription: &SubscriptionSettings,
-    state: State,
-    message: CensusMessage,
-) -> Result<(), RealtimeError> {
-    match message {
-        CensusMessage::ConnectionStateChanged { connected } => {
-            handle_connection_state(connected, subscription, client)?;
+impl MessageHandler {
+    /// Handle the messages of a recording, which has no connection to keep healthy
+    pub fn new(state: State) -> Self {
+        Self {
+            state,
+            health: Arc::new(Mutex::new(ConnectionHealth::new(Instant::now(), None))),
+            upstream: "replay".to_owned(),
+            connection: None,
         }
-        CensusMessage::Heartbeat { .. } => {
-            counter!("realtime_messages_received_heartbeat").increment(1);
+    }
+
+    /// Whether another upstream already delivered this event. Duplicates still count as events
+    /// for the health of the connection, a slower upstream isn't a silent one.
+    fn is_duplicate(&self, message: &Value) -> bool {
+        let Some(deduplicator) = &self.state.deduplicator else {
+            return false;
+        };
+        let Some(hash) = dedup::payload_hash(message) else {
+            return false;
+        };
+
+        let now = Instant::now();
+        match deduplicator.check(hash, &self.upstream, now) {
+            Delivery::First => {
+                histogram!("niumside_realtime_upstream_lag_seconds", "upstream" => self.upstream.clone())
+                    .record(0.0);
+                false
+            }
+            Delivery::Duplicate(lag) => {
+                update_health(&self.health, |health| health.last_event = Some(now));
+                histogram!("niumside_realtime_upstream_lag_seconds", "upstream" => self.upstream.clone())
+                    .record(lag.as_secs_f64());
+                counter!("niumside_realtime_upstream_duplicates", "upstream" => self.upstream.clone())
+                    .increment(1);
+                true
+            }
         }
-        CensusMessage::ServiceStateChanged { .. } => {}
-        CensusMessage::ServiceMessage { payload } => {
-            thread::spawn(move || receive_events(payload, &state.active_players));
+    }
+
+    /// Parse and handle a text frame. Returns whether Census reported the connection as
+    /// connected, after which the subscription has to be sent.
+    pub fn handle_text(&self, text: &str) -> bool {
+        counter!("niumside_realtime_upstream_messages", "upstream" => self.upstream.clone())
+            .increment(1);
+        // Parsed once, for the payload hash of the deduplicator and then as a Census message
+        let message: Value = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(error) => {
+                ParseFailure::new(text, &error).report(text, self.state.dead_letters.as_deref());
+                return false;
+            }
+        };
+        if self.is_duplicate(&message) {
+            return false;
         }
-        CensusMessage::Subscription { subscription } => {
-            debug!("Subscribed: {:?}", subscription);
+
+        let failure = match CensusMessage::deserialize(&message) {
+            Ok(CensusMessage::ServiceMessage {
+                payload: Event::Unknown,
+            }) => ParseFailure::unknown_event(text),
+            Ok(message) => return self.handle_census_msg(message),
+            Err(error) => match SubscriptionAcknowledgement::deserialize(&message) {
+                Ok(acknowledgement) => {
+                    return self.handle_subscription(acknowledgement.subscription);
+                }
+                Err(_) => ParseFailure::new(text, &error),
+            },
+        };
+
+        failure.report(text, self.state.dead_letters.as_deref());
+        false
+    }
+
+    fn handle_census_msg(&self, message: CensusMessage) -> bool {
+        match message {
+            CensusMessage::ConnectionStateChanged { connected } => {
+                return self.handle_connection_state(connected);
+            }
+            CensusMessage::Heartbeat { online } => {
+                counter!("realtime_messages_received_heartbeat").increment(1);
+                update_health(&self.health, |health| {
+                    health.last_heartbeat = Some(Instant::now());
+                });
+                server_health::update(&self.state.server_health, |server_health| {
+                    server_health.heartbeat(online, Utc::now());
+                });
+            }
+            CensusMessage::ServiceStateChanged { online, detail } => {
+                if let Some(endpoint) = server_health::parse_endpoint(&detail) {
+                    server_health::update(&self.state.server_health, |server_health| {
+                        server_health.online.insert(endpoint, online);
+                    });
+                }
+            }
+            CensusMessage::ServiceMessage { payload } => {
+                update_health(&self.health, |health| {
+                    health.last_event = Some(Instant::now());
+                });
+                self.state.events.send(payload);
+            }
+            CensusMessage::Subscription { subscription } => {
+                return self.handle_subscription(subscription);
+            }
         }
+
+        false
     }
 
-    Ok(())
+    /// Compare the subscription Census acknowledged with the one that was sent. Returns whether
+    /// the subscription has to be sent again.
+    fn handle_subscription(&self, acknowledged: Subscription) -> bool {
+        let Some(connection) = &self.connection else {
+            debug!("Subscribed: {:?}", acknowledged);
+            return false;
+        };
+
+        let mismatches = connection.subscription.mismatches(&acknowledged);
+        server_health::update(&self.state.server_health, |server_health| {
+            server_health.subscribed(ActiveSubscription {
+                environment: connection.environment,
+                upstream: self.upstream.clone(),
+                event_names: acknowledged.event_names,
+                worlds: acknowledged.worlds,
+                logical_and_characters_with_worlds: acknowledged.logical_and_characters_with_worlds,
+                character_count: acknowledged.character_count,
+                mismatches: mismatches.iter().map(|field| (*field).to_owned()).collect(),
+                acknowledged: Utc::now(),
+            });
+        });
+
+        if mismatches.is_empty() {
+            info!(
+                "Census acknowledged the subscription to {} on {}",
+                connection.environment, self.upstream
+            );
+            return false;
+        }
+
+        counter!(
+            "niumside_subscription_mismatches",
+            "environment" => connection.environment.to_string(),
+            "upstream" => self.upstream.clone()
+        )
+        .increment(1);
+
+        let mut retry = false;
+        update_health(&self.health, |health| {
+            retry = health.subscription_retries < MAX_SUBSCRIPTION_RETRIES;
+            if retry {
+                health.subscription_retries += 1;
+            }
+        });
+
+        if retry {
+            warn!(
+                "Census acknowledged a different subscription to {} on {} ({mismatches:?}), subscribing again",
+                connection.environment, self.upstream
+            );
+        } else {
+            error!(
+                "Census keeps acknowledging a different subscription to {} on {} ({mismatches:?})",
+                connection.environment, self.upstream
+            );
+        }
+        retry
+    }
+
+    fn handle_connection_state(&self, connected: bool) -> bool {
+        if !connected {
+            error!("Disconnected from Census!");
+            update_health(&self.health, |health| health.disconnected = true);
+            return false;
+        }
+
+        info!("Connected to Census!");
+        counter!("realtime_total_connections").increment(1);
+        true
+    }
+}
+
+impl CensusRealtimeClient {
+    fn subscribe(&mut self) -> Result<(), RealtimeError> {
+        let Some(connection) = &self.handler.connection else {
+            return Ok(());
+        };
+        send_subscription(&self.client, &connection.subscription)?;
+
+        if self.resubscribe {
+            counter!("realtime_total_resubscriptions").increment(1);
+        }
+        self.resubscribe = true;
+        update_health(&self.handler.health, |health| health.subscribed = true);
+
+        Ok(())
+    }
 }
 
 fn close_connection(client: &ezsockets::Client<CensusRealtimeClient>) {
@@ -122,8 +462,7 @@ fn send_subscription(
 
     match client.text(subscription_json) {
         Ok(_) => {
-            info!("Subscribed to Census!");
-            // TODO: Handle subscription response
+            info!("Sent the subscription to Census");
             Ok(())
         }
         Err(err) => {
@@ -134,45 +473,103 @@ fn send_subscription(
     }
 }
 
-fn handle_connection_state(
-    connected: bool,
-    subscription: &SubscriptionSettings,
-    client: &ezsockets::Client<CensusRealtimeClient>,
-) -> Result<(), RealtimeError> {
-    if !connected {
-        error!("Disconnected from Census!");
-        return Ok(());
-    }
-
-    info!("Connected to Census!");
-    counter!("realtime_total_connections").increment(1);
-
-    send_subscription(client, subscription)
-}
-
-fn get_census_address(config: RealtimeClientConfig) -> String {
+/// The name of an upstream in logs and metrics, which tells the deduplicator where an event came
+/// from. It is the address of the connection without the service ID, so upstreams on the same host
+/// with a different path or environment are told apart.
+fn upstream_name(config: &RealtimeClientConfig) -> String {
     let base_url = config
         .realtime_url
-        .unwrap_or_else(|| REALTIME_URL.clone())
-        .to_string();
+        .clone()
+        .unwrap_or_else(|| REALTIME_URL.clone());
+    format!("{base_url}?environment={}", config.environment)
+}
+
+fn get_census_address(config: &RealtimeClientConfig) -> String {
     format!(
-        "{}?environment={}&service-id=s:{}",
-        base_url, config.environment, config.service_id
+        "{}&service-id=s:{}",
+        upstream_name(config),
+        config.service_id
     )
 }
 
-pub fn get_subscription_settings() -> SubscriptionSettings {
+/// The events the event handlers need
+pub fn default_event_names() -> Vec<EventNames> {
+    PopulationTracker::EVENT_NAMES
+        .iter()
+        .chain(MetagameTracker::EVENT_NAMES)
+        .cloned()
+        .collect()
+}
+
+pub fn get_subscription_settings(config: &RealtimeClientConfig) -> SubscriptionSettings {
+    let event_names = config
+        .event_names
+        .clone()
+        .unwrap_or_else(default_event_names);
+
+    let characters = config
+        .characters
+        .clone()
+        .map_or(CharacterSubscription::All, CharacterSubscription::Ids);
+
+    let worlds = if config.worlds.is_empty() {
+        WorldSubscription::All
+    } else {
+        WorldSubscription::Ids(config.worlds.clone())
+    };
+
     SubscriptionSettings {
-        event_names: Some(EventSubscription::Ids(vec![EventNames::GainExperience])),
-        characters: Some(CharacterSubscription::All),
-        worlds: Some(WorldSubscription::All),
+        event_names: Some(EventSubscription::Ids(event_names)),
+        characters: Some(characters),
+        worlds: Some(worlds),
         logical_and_characters_with_worlds: Some(true),
         ..SubscriptionSettings::default()
     }
 }
 
+/// How long a connection may go without events before it is replaced. Only the experience of
+/// every character on every world arrives constantly, a filtered subscription or a quiet world can
+/// legitimately go without events for longer, so only its heartbeats are checked.
+fn event_timeout(subscription: &SubscriptionSettings) -> Option<Duration> {
+    let all_characters = matches!(
+        subscription.characters,
+        None | Some(CharacterSubscription::All)
+    );
+    let all_worlds = matches!(subscription.worlds, None | Some(WorldSubscription::All));
+    let experience = match &subscription.event_names {
+        None | Some(EventSubscription::All) => true,
+        Some(EventSubscription::Ids(event_names)) => {
+            event_names.contains(&EventNames::GainExperience)
+        }
+    };
+
+    (all_characters && all_worlds && experience).then_some(EVENT_TIMEOUT)
+}
+
+/// Wait until the connection has to be replaced
+async fn watchdog(health: &Mutex<ConnectionHealth>) -> ConnectionLoss {
+    let mut interval = tokio::time::interval(WATCHDOG_INTERVAL);
+    loop {
+        interval.tick().await;
+        let loss = health.lock().map_or_else(
+            |_| {
+                error!("Failed to lock the realtime connection health");
+                Some(ConnectionLoss::Closed)
+            },
+            |guard| guard.check(Instant::now()),
+        );
+
+        if let Some(loss) = loss {
+            return loss;
+        }
+    }
+}
+
 pub async fn client(realtime_client_config: RealtimeClientConfig, state: State) {
-    let url = match Url::parse(&get_census_address(realtime_client_config.clone())) {
+    let environment = realtime_client_config.environment;
+    let subscription = get_subscription_settings(&realtime_client_config);
+    let upstream = upstream_name(&realtime_client_config);
+    let url = match Url::parse(&get_census_address(&realtime_client_config)) {
         Ok(url) => url,
         Err(err) => {
             error!("Failed to parse URL: {:?}", err);
@@ -181,36 +578,389 @@ pub async fn client(realtime_client_config: RealtimeClientConfig, state: State)
         }
     };
 
-    let config = ClientConfig::new(url);
+    let mut backoff = Backoff::default();
+    let mut resubscribe = false;
 
-    info!("Setting up Census websocket client");
+    loop {
+        let config = ClientConfig::new(url.clone()).max_initial_connect_attempts(1);
+        let health = Arc::new(Mutex::new(ConnectionHealth::new(
+            Instant::now(),
+            event_timeout(&subscription),
+        )));
 
-    let (_handle, future) = ezsockets::connect(
-        move |client| CensusRealtimeClient {
-            client,
-            subscription: get_subscription_settings(),
-            state,
-        },
-        config,
-    )
-    .await;
-    tokio::spawn(future);
-
-    // loop {
-    //     let msg = match socket.read() {
-    //         Ok(msg) => msg,
-    //         Err(err) => {
-    //             handle_websocket_error(&err);
-    //             continue;
-    //         }
-    //     };
-    //
-    //     match handle_ws_msg(&mut socket, state.clone(), get_subscription_settings(), msg) {
-    //         Ok(()) => {}
-    //         Err(err) => {
-    //             increment_counter!("realtime_messages_received_total_errored");
-    //             error!("{:?}", err);
-    //         }
-    //     }
-    // }
+        info!("Setting up Census websocket client for {environment} on {upstream}");
+
+        let connection = Connection {
+            environment,
+            subscription: subscription.clone(),
+        };
+        let client_state = state.clone();
+        let client_health = health.clone();
+        let client_upstream = upstream.clone();
+        let (handle, future) = ezsockets::connect(
+            move |client| CensusRealtimeClient {
+                client,
+                handler: MessageHandler {
+                    state: client_state,
+                    health: client_health,
+                    upstream: client_upstream,
+                    connection: Some(connection),
+                },
+                resubscribe,
+            },
+            config,
+        )
+        .await;
+
+        let loss = tokio::select! {
+            result = future => {
+                if let Err(err) = result {
+                    error!("Census websocket client stopped: {err}");
+                }
+                ConnectionLoss::Closed
+            }
+            loss = watchdog(&health) => {
+                close_connection(&handle);
+                loss
+            }
+        };
+
+        let health = health.lock().map_or_else(
+            |_| ConnectionHealth::new(Instant::now(), None),
+            |guard| *guard,
+        );
+        resubscribe |= health.subscribed;
+        // Only keep backing off while connections don't deliver any events
+        if health.last_event.is_some() {
+            backoff.reset();
+        }
+
+        server_health::update(&state.server_health, |server_health| {
+            server_health.mark_offline(environment);
+        });
+
+        let delay = backoff.next_delay();
+        counter!(
+            "realtime_total_disconnects",
+            "reason" => loss.as_str(),
+            "upstream" => upstream.clone()
+        )
+        .increment(1);
+        warn!(
+            "Census realtime connection to {environment} on {upstream} lost ({}), reconnecting in {delay:?}",
+            loss.as_str()
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::mock::MockCensus;
+    use crate::census::server_health::ServerHealth;
+
+    const GAIN_EXPERIENCE: &str = r#"{"amount":"28","character_id":"5429573939285739921","event_name":"GainExperience","experience_id":"140","loadout_id":"20","other_id":"34360508066","team_id":"1","timestamp":"1728117291","world_id":"13","zone_id":"8"}"#;
+
+    fn realtime_client_config() -> RealtimeClientConfig {
+        RealtimeClientConfig {
+            environment: Environment::Ps2,
+            service_id: "example".to_owned(),
+            realtime_url: None,
+            worlds: Vec::new(),
+            event_names: None,
+            characters: None,
+        }
+    }
+
+    #[test]
+    fn test_subscription_defaults_to_everything() {
+        let subscription = get_subscription_settings(&realtime_client_config());
+
+        assert_eq!(
+            serde_json::to_value(Action::Subscribe(subscription)).unwrap(),
+            serde_json::json!({
+                "action": "subscribe",
+                "eventNames": [
+                    "GainExperience",
+                    "PlayerLogin",
+                    "PlayerLogout",
+                    "Death",
+                    "VehicleDestroy",
+                    "ContinentLock",
+                    "ContinentUnlock",
+                    "MetagameEvent",
+                    "FacilityControl"
+                ],
+                "characters": ["all"],
+                "logicalAndCharactersWithWorlds": true,
+                "worlds": ["all"],
+                "service": "event"
+            })
+        );
+    }
+
+    #[test]
+    fn test_default_events_cover_handlers() {
+        let subscription = get_subscription_settings(&realtime_client_config());
+        let Some(EventSubscription::Ids(event_names)) = subscription.event_names else {
+            panic!("No events are subscribed to by default");
+        };
+
+        for event_name in PopulationTracker::EVENT_NAMES
+            .iter()
+            .chain(MetagameTracker::EVENT_NAMES)
+        {
+            assert!(
+                event_names.contains(event_name),
+                "{event_name:?} is not subscribed to by default"
+            );
+        }
+    }
+
+    #[test]
+    fn test_upstreams_are_named_by_address() {
+        let config = realtime_client_config();
+        let other_path = RealtimeClientConfig {
+            realtime_url: Some(Url::parse("wss://push.planetside2.com/other").unwrap()),
+            ..realtime_client_config()
+        };
+        let other_environment = RealtimeClientConfig {
+            environment: Environment::Ps2Ps4Us,
+            ..realtime_client_config()
+        };
+
+        assert_eq!(
+            upstream_name(&config),
+            "wss://push.planetside2.com/streaming?environment=ps2"
+        );
+        assert_ne!(upstream_name(&config), upstream_name(&other_path));
+        assert_ne!(upstream_name(&config), upstream_name(&other_environment));
+        assert_eq!(
+            get_census_address(&config),
+            "wss://push.planetside2.com/streaming?environment=ps2&service-id=s:example"
+        );
+    }
+
+    #[test]
+    fn test_subscription_from_config() {
+        let config = RealtimeClientConfig {
+            worlds: vec![WorldID::Miller, WorldID::Cobalt],
+            event_names: Some(vec![EventNames::Death]),
+            characters: Some(vec![5_429_573_939_285_739_921]),
+            ..realtime_client_config()
+        };
+        let subscription = get_subscription_settings(&config);
+
+        assert_eq!(
+            serde_json::to_value(Action::Subscribe(subscription)).unwrap(),
+            serde_json::json!({
+                "action": "subscribe",
+                "eventNames": ["Death"],
+                "characters": ["5429573939285739921"],
+                "logicalAndCharactersWithWorlds": true,
+                "worlds": ["10", "13"],
+                "service": "event"
+            })
+        );
+    }
+
+    #[test]
+    fn test_backoff_doubles_until_max() {
+        let mut backoff = Backoff::default();
+
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(2));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(4));
+
+        for _ in 0..40 {
+            backoff.next_delay();
+        }
+        assert_eq!(backoff.next_delay(), RECONNECT_MAX_DELAY);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), RECONNECT_INITIAL_DELAY);
+    }
+
+    #[test]
+    fn test_health_detects_missing_heartbeat() {
+        let started = Instant::now();
+        let mut health = ConnectionHealth::new(started, Some(EVENT_TIMEOUT));
+
+        assert_eq!(health.check(started + Duration::from_mins(1)), None);
+        assert_eq!(
+            health.check(started + HEARTBEAT_TIMEOUT + Duration::from_secs(1)),
+            Some(ConnectionLoss::MissingHeartbeat)
+        );
+
+        health.last_heartbeat = Some(started + Duration::from_mins(1));
+        assert_eq!(
+            health.check(started + HEARTBEAT_TIMEOUT + Duration::from_secs(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_health_detects_stale_events() {
+        let started = Instant::now();
+        let now = started + EVENT_TIMEOUT + Duration::from_secs(1);
+        let mut health = ConnectionHealth::new(started, Some(EVENT_TIMEOUT));
+        health.last_heartbeat = Some(now);
+
+        assert_eq!(health.check(now), Some(ConnectionLoss::NoEvents));
+
+        health.last_event = Some(now);
+        assert_eq!(health.check(now), None);
+
+        health.disconnected = true;
+        assert_eq!(health.check(now), Some(ConnectionLoss::Disconnected));
+    }
+
+    #[test]
+    fn test_event_timeout_only_for_unfiltered_subscriptions() {
+        let subscription = get_subscription_settings(&realtime_client_config());
+        assert_eq!(event_timeout(&subscription), Some(EVENT_TIMEOUT));
+
+        for config in [
+            RealtimeClientConfig {
+                characters: Some(vec![5_429_573_939_285_739_921]),
+                ..realtime_client_config()
+            },
+            RealtimeClientConfig {
+                worlds: vec![WorldID::Miller],
+                ..realtime_client_config()
+            },
+            RealtimeClientConfig {
+                event_names: Some(vec![EventNames::MetagameEvent]),
+                ..realtime_client_config()
+            },
+        ] {
+            assert_eq!(event_timeout(&get_subscription_settings(&config)), None);
+        }
+
+        // A quiet connection without an event timeout is kept while heartbeats arrive
+        let started = Instant::now();
+        let now = started + EVENT_TIMEOUT * 3;
+        let mut health = ConnectionHealth::new(started, None);
+        health.last_heartbeat = Some(now);
+        assert_eq!(health.check(now), None);
+    }
+
+    #[tokio::test]
+    async fn test_client_handles_census_messages() {
+        let mut census = MockCensus::start().await;
+        let (events, mut receiver) = EventPipeline::new(10);
+        let server_health = Arc::new(Mutex::new(ServerHealth::default()));
+        tokio::spawn(client(
+            census.realtime_config(),
+            State {
+                events,
+                server_health: server_health.clone(),
+                recording: None,
+                dead_letters: None,
+                deduplicator: None,
+            },
+        ));
+
+        let subscription = census.next_subscription().await.unwrap();
+        assert_eq!(subscription["eventNames"][0], "GainExperience");
+        assert_eq!(subscription["worlds"], serde_json::json!(["all"]));
+
+        census.push_heartbeat(&[("EventServerEndpoint_Cobalt_13", true)]);
+        census.push_event(GAIN_EXPERIENCE);
+
+        let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(event, Event::GainExperience(_)));
+        let server_health = server_health.lock().unwrap().clone();
+        assert_eq!(server_health.world_online(WorldID::Cobalt), Some(true));
+        let subscriptions = server_health.subscriptions();
+        assert_eq!(subscriptions.len(), 1);
+        assert_eq!(subscriptions[0].environment, Environment::Ps2);
+        assert!(subscriptions[0].mismatches.is_empty());
+    }
+
+    #[test]
+    fn test_subscription_mismatch_is_retried() {
+        let (events, _receiver) = EventPipeline::new(10);
+        let server_health = Arc::new(Mutex::new(ServerHealth::default()));
+        let handler = MessageHandler {
+            upstream: "example".to_owned(),
+            connection: Some(Connection {
+                environment: Environment::Ps2,
+                subscription: get_subscription_settings(&realtime_client_config()),
+            }),
+            ..MessageHandler::new(State {
+                events,
+                server_health: server_health.clone(),
+                recording: None,
+                dead_letters: None,
+                deduplicator: None,
+            })
+        };
+        let acknowledgement = |event_names: &str| {
+            format!(
+                r#"{{"subscription":{{"characterCount":0,"eventNames":{event_names},"logicalAndCharactersWithWorlds":true,"worlds":["all"]}}}}"#
+            )
+        };
+
+        for _ in 0..MAX_SUBSCRIPTION_RETRIES {
+            assert!(handler.handle_text(&acknowledgement(r#"["PlayerLogin"]"#)));
+        }
+        assert!(!handler.handle_text(&acknowledgement(r#"["PlayerLogin"]"#)));
+        assert_eq!(
+            server_health.lock().unwrap().subscriptions()[0].mismatches,
+            vec!["eventNames"]
+        );
+
+        assert!(!handler.handle_text(&acknowledgement(r#"["all"]"#)));
+        assert!(server_health.lock().unwrap().subscriptions()[0]
+            .mismatches
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_upstreams_are_deduplicated() {
+        let mut first = MockCensus::start().await;
+        let mut second = MockCensus::start().await;
+        let (events, mut receiver) = EventPipeline::new(10);
+        let state = State {
+            events,
+            server_health: Arc::new(Mutex::new(ServerHealth::default())),
+            recording: None,
+            dead_letters: None,
+            deduplicator: Some(Arc::new(Deduplicator::default())),
+        };
+        tokio::spawn(client(first.realtime_config(), state.clone()));
+        tokio::spawn(client(second.realtime_config(), state));
+        first.next_subscription().await.unwrap();
+        second.next_subscription().await.unwrap();
+
+        first.push_event(GAIN_EXPERIENCE);
+        second.push_event(GAIN_EXPERIENCE);
+        // Only the second upstream delivers this one, as if the first went silent
+        second.push_event(&GAIN_EXPERIENCE.replace(r#""amount":"28""#, r#""amount":"10""#));
+
+        let mut amounts = Vec::new();
+        for _ in 0..2 {
+            let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            let Event::GainExperience(gain_experience) = event else {
+                panic!("Expected a GainExperience event, got {event}");
+            };
+            amounts.push(gain_experience.amount);
+        }
+        amounts.sort_unstable();
+        assert_eq!(amounts, vec![10, 28]);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), receiver.recv())
+                .await
+                .is_err()
+        );
+    }
 }
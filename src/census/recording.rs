@@ -0,0 +1,315 @@
+use crate::census::realtime::{MessageHandler, State};
+use chrono::{DateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use metrics::counter;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// How often the recording is flushed, so a recording of a process that was killed is still usable
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A text frame as it was received from Census
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecordedFrame {
+    pub timestamp: DateTime<Utc>,
+    pub text: String,
+}
+
+pub type RecordingSender = Sender<RecordedFrame>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum RecordingError {
+    #[error("Failed to read or write the recording: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to parse a recorded frame: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+/// Queue a frame for the recorder, which only fails when the recorder stopped
+pub fn record(recording: &RecordingSender, text: &str) {
+    let frame = RecordedFrame {
+        timestamp: Utc::now(),
+        text: text.to_owned(),
+    };
+
+    if recording.send(frame).is_err() {
+        counter!("niumside_recording_send_failed").increment(1);
+        error!("Unable to send frame to the recorder");
+    }
+}
+
+/// Write the frames to `writer` as gzip compressed NDJSON until every sender is dropped
+fn write_frames<W: Write>(receiver: &Receiver<RecordedFrame>, writer: W) -> std::io::Result<W> {
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    let mut last_flush = Instant::now();
+
+    loop {
+        match receiver.recv_timeout(FLUSH_INTERVAL) {
+            Ok(frame) => {
+                serde_json::to_writer(&mut encoder, &frame)?;
+                encoder.write_all(b"\n")?;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return encoder.finish(),
+        }
+
+        if last_flush.elapsed() >= FLUSH_INTERVAL {
+            encoder.flush()?;
+            last_flush = Instant::now();
+        }
+    }
+}
+
+/// Start recording frames to `path` on a separate thread. An existing recording is appended to,
+/// since every start adds its own gzip member.
+pub fn start_recording(path: &Path) -> Result<RecordingSender, RecordingError> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let (sender, receiver) = mpsc::channel();
+    let path = path.to_path_buf();
+
+    std::thread::Builder::new()
+        .name("recorder".to_owned())
+        .spawn(move || {
+            info!("Recording the realtime stream to {}", path.display());
+            if let Err(e) = write_frames(&receiver, BufWriter::new(file)) {
+                counter!("niumside_recording_failed_writes").increment(1);
+                error!("Stopped recording to {}: {e}", path.display());
+            }
+        })?;
+
+    Ok(sender)
+}
+
+/// Read the frames of a recording, a frame that was only partially written ends the recording
+pub fn read_frames(
+    reader: impl Read,
+) -> impl Iterator<Item = Result<RecordedFrame, RecordingError>> {
+    BufReader::new(MultiGzDecoder::new(reader))
+        .lines()
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+}
+
+/// Maps the time in a recording to the time it is replayed at
+#[derive(Debug, Clone, Copy)]
+struct ReplayClock {
+    recording_start: DateTime<Utc>,
+    replay_start: DateTime<Utc>,
+    speed: f64,
+}
+
+impl ReplayClock {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+    fn replay_time(&self, recorded: DateTime<Utc>) -> DateTime<Utc> {
+        let elapsed = (recorded - self.recording_start).num_milliseconds() as f64 / self.speed;
+        self.replay_start + chrono::Duration::milliseconds(elapsed as i64)
+    }
+
+    /// Move the timestamp of an event to the time it is replayed at, so players aren't removed
+    /// as inactive straight away. Returns `None` for messages without a timestamp.
+    fn shift_timestamp(&self, text: &str) -> Option<String> {
+        let mut message: serde_json::Value = serde_json::from_str(text).ok()?;
+        let timestamp = message.pointer_mut("/payload/timestamp")?;
+        let recorded = DateTime::from_timestamp(timestamp.as_str()?.parse().ok()?, 0)?;
+
+        *timestamp = self.replay_time(recorded).timestamp().to_string().into();
+        Some(message.to_string())
+    }
+}
+
+/// Feed a recording through the same handling as the live stream, `speed` times as fast as it
+/// was recorded
+pub async fn replay(path: PathBuf, speed: f64, state: State) {
+    let speed = if speed.is_finite() && speed > 0.0 {
+        speed
+    } else {
+        warn!("Invalid replay speed {speed}, replaying at real speed");
+        1.0
+    };
+
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            error!("Unable to open recording {}: {e}", path.display());
+            return;
+        }
+    };
+
+    info!("Replaying {} at {speed}x speed", path.display());
+    let handler = MessageHandler::new(state);
+    let mut clock: Option<ReplayClock> = None;
+    let mut replayed: u64 = 0;
+
+    for frame in read_frames(file) {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(e) => {
+                warn!("Stopped replaying {}: {e}", path.display());
+                break;
+            }
+        };
+
+        let clock = *clock.get_or_insert_with(|| ReplayClock {
+            recording_start: frame.timestamp,
+            replay_start: Utc::now(),
+            speed,
+        });
+
+        if let Ok(delay) = (clock.replay_time(frame.timestamp) - Utc::now()).to_std() {
+            tokio::time::sleep(delay).await;
+        }
+
+        let text = clock.shift_timestamp(&frame.text).unwrap_or(frame.text);
+        handler.handle_text(&text);
+        replayed += 1;
+    }
+
+    info!("Replayed {replayed} frames from {}", path.display());
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::store::ShardedActivePlayers;
+    use crate::active_players::{ActivePlayerDb, VehicleExperience};
+    use crate::census::constants::{Faction, Loadout, WorldID, ZoneID};
+    use crate::census::server_health::ServerHealth;
+    use crate::event_handlers::pipeline::{self, EventHandlers, EventPipeline};
+    use crate::event_handlers::PopulationTracker;
+    use crate::kill_stats::KillStatsDb;
+    use std::sync::{Arc, Mutex};
+
+    const GAIN_EXPERIENCE: &str = r#"{"payload":{"amount":"28","character_id":"5429573939285739921","event_name":"GainExperience","experience_id":"140","loadout_id":"20","other_id":"34360508066","team_id":"1","timestamp":"1728117291","world_id":"13","zone_id":"8"},"service":"event","type":"serviceMessage"}"#;
+    const DEATH: &str = r#"{"payload":{"attacker_character_id":"5428010618015189713","attacker_fire_mode_id":"7401","attacker_loadout_id":"6","attacker_team_id":"2","attacker_vehicle_id":"0","attacker_weapon_id":"7169","character_id":"5429573939285739922","character_loadout_id":"20","event_name":"Death","is_critical":"0","is_headshot":"1","team_id":"1","timestamp":"1728117292","vehicle_id":"0","world_id":"13","zone_id":"8"},"service":"event","type":"serviceMessage"}"#;
+    const HEARTBEAT: &str = r#"{"online":{"EventServerEndpoint_Connery_1":"true"},"service":"event","type":"heartbeat"}"#;
+
+    fn timestamp(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    fn recording(frames: &[(i64, &str)]) -> Vec<u8> {
+        let (sender, receiver) = mpsc::channel();
+        for (seconds, text) in frames {
+            sender
+                .send(RecordedFrame {
+                    timestamp: timestamp(*seconds),
+                    text: (*text).to_owned(),
+                })
+                .unwrap();
+        }
+        drop(sender);
+
+        write_frames(&receiver, Vec::new()).unwrap()
+    }
+
+    #[test]
+    fn test_frames_roundtrip() {
+        let mut bytes = recording(&[(1_728_117_291, GAIN_EXPERIENCE)]);
+        // A restarted recorder appends a second gzip member
+        bytes.extend(recording(&[(1_728_117_300, HEARTBEAT)]));
+
+        let frames: Vec<RecordedFrame> =
+            read_frames(bytes.as_slice()).map(Result::unwrap).collect();
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].timestamp, timestamp(1_728_117_291));
+        assert_eq!(frames[0].text, GAIN_EXPERIENCE);
+        assert_eq!(frames[1].text, HEARTBEAT);
+    }
+
+    #[test]
+    fn test_replay_clock_shifts_timestamps() {
+        let clock = ReplayClock {
+            recording_start: timestamp(1_728_117_291),
+            replay_start: timestamp(1_800_000_000),
+            speed: 10.0,
+        };
+
+        assert_eq!(
+            clock.replay_time(timestamp(1_728_117_391)),
+            timestamp(1_800_000_010)
+        );
+
+        let shifted: serde_json::Value =
+            serde_json::from_str(&clock.shift_timestamp(DEATH).unwrap()).unwrap();
+        assert_eq!(shifted["payload"]["timestamp"], "1800000000");
+        assert_eq!(shifted["payload"]["character_id"], "5429573939285739922");
+
+        assert_eq!(clock.shift_timestamp(HEARTBEAT), None);
+    }
+
+    #[tokio::test]
+    async fn test_replay_tracks_population() {
+        let path =
+            std::env::temp_dir().join(format!("niumside-replay-{}.ndjson.gz", std::process::id()));
+        std::fs::write(
+            &path,
+            recording(&[
+                (1_728_117_291, GAIN_EXPERIENCE),
+                (1_728_117_292, DEATH),
+                (1_728_117_293, HEARTBEAT),
+            ]),
+        )
+        .unwrap();
+
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let (character_sessions, _character_sessions) = tokio::sync::mpsc::unbounded_channel();
+        let (events, receiver) = EventPipeline::new(100);
+        pipeline::spawn_workers(
+            receiver,
+            EventHandlers::default().register(PopulationTracker {
+                active_players: active_players.clone(),
+                character_sessions,
+                kill_stats: KillStatsDb::default(),
+                vehicle_experience: VehicleExperience::new(),
+            }),
+            1,
+        );
+        let state = State {
+            events: events.clone(),
+            server_health: Arc::new(Mutex::new(ServerHealth::default())),
+            recording: None,
+            dead_letters: None,
+            deduplicator: None,
+        };
+
+        replay(path.clone(), 1_000.0, state).await;
+        std::fs::remove_file(&path).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while active_players.len() < 3 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let counts = active_players.counts();
+        assert_eq!(
+            counts.get(&(
+                WorldID::Cobalt,
+                ZoneID(8),
+                Faction::VS,
+                Loadout::VSHeavyAssault
+            )),
+            Some(&2)
+        );
+        assert_eq!(
+            counts.get(&(
+                WorldID::Cobalt,
+                ZoneID(8),
+                Faction::NC,
+                Loadout::NCHeavyAssault
+            )),
+            Some(&1)
+        );
+        // Replayed events happen now, so the players are still active
+        assert_eq!(active_players.retain_active(Utc::now()), 0);
+    }
+}
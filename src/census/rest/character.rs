@@ -70,17 +70,58 @@ impl CensusRequestableObject for Character {
 }
 
 #[cfg(test)]
+#[allow(clippy::unwrap_used)]
 mod tests {
     use super::*;
+    use crate::census::mock::MockCensus;
     use crate::census::structs::character::Character;
 
     const CHARACTER_ID: u64 = 5_428_830_384_575_692_145;
     const CHARACTER_NAME: &str = "brakenium";
     const CHARACTER_CREATION_TIMESTAMP: i64 = 1_549_564_351;
 
+    fn character_list() -> serde_json::Value {
+        serde_json::json!({
+            "character_list": [{
+                "character_id": CHARACTER_ID.to_string(),
+                "name": {"first": CHARACTER_NAME, "first_lower": CHARACTER_NAME},
+                "faction_id": "2",
+                "times": {
+                    "creation": CHARACTER_CREATION_TIMESTAMP.to_string(),
+                    "last_save": "1728117291",
+                    "last_login": "1728110000",
+                    "login_count": "1234",
+                    "minutes_played": "98765"
+                }
+            }],
+            "returned": 1
+        })
+    }
+
+    async fn census() -> MockCensus {
+        let census = MockCensus::start().await;
+        census.respond_collection(
+            CensusCollections::Character,
+            Some(&format!("character_id={CHARACTER_ID}")),
+            &character_list(),
+        );
+        census.respond_collection(
+            CensusCollections::Character,
+            Some(&format!("name.first_lower={CHARACTER_NAME}")),
+            &character_list(),
+        );
+        census.respond_collection(
+            CensusCollections::Character,
+            None,
+            &serde_json::json!({"character_list": [], "returned": 0}),
+        );
+        census
+    }
+
     #[tokio::test]
     async fn test_get_by_id() {
-        let client = CensusRestClient::default();
+        let census = census().await;
+        let client = census.rest_client();
 
         let character = Character::get_by_id(&client, CHARACTER_ID).await.unwrap();
         assert_eq!(character.character_id, CHARACTER_ID);
@@ -93,7 +134,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_by_name() {
-        let client = CensusRestClient::default();
+        let census = census().await;
+        let client = census.rest_client();
 
         let character = Character::get_by_name(&client, CHARACTER_NAME)
             .await
@@ -105,4 +147,15 @@ mod tests {
             CHARACTER_CREATION_TIMESTAMP
         );
     }
+
+    #[tokio::test]
+    async fn test_get_unknown_character() {
+        let census = census().await;
+        let client = census.rest_client();
+
+        assert!(matches!(
+            Character::get_by_id(&client, 1).await,
+            Err(CensusRequestError::NotFound)
+        ));
+    }
 }
@@ -1,3 +1,4 @@
+use crate::census::constants::Environment;
 use crate::census::CENSUS_URL;
 use crate::storage::configuration::CensusConfig;
 use rocket::serde::{Deserialize, Serialize};
@@ -10,6 +11,8 @@ pub struct CensusRestClient {
     pub(crate) census_url: Url,
     // lithafalcon_url: Url,
     pub(crate) service_id: String,
+    /// The environment whose namespace is requested
+    pub(crate) environment: Environment,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash)]
@@ -42,26 +45,20 @@ impl From<CensusRequestType> for &str {
     }
 }
 
-pub enum CensusNamespaces {
-    Ps2V2,
-}
-
-impl From<CensusNamespaces> for &str {
-    fn from(namespace: CensusNamespaces) -> &'static str {
-        match namespace {
-            CensusNamespaces::Ps2V2 => "ps2:v2",
-        }
-    }
-}
-
 pub enum CensusCollections {
     Character,
+    Map,
+    Outfit,
+    OutfitMember,
 }
 
 impl From<CensusCollections> for &str {
     fn from(val: CensusCollections) -> Self {
         match val {
             CensusCollections::Character => "character",
+            CensusCollections::Map => "map",
+            CensusCollections::Outfit => "outfit",
+            CensusCollections::OutfitMember => "outfit_member",
         }
     }
 }
@@ -83,6 +80,7 @@ impl From<CensusConfig> for CensusRestClient {
         Self {
             census_url: config.census_base_url,
             service_id: config.service_id,
+            environment: Environment::Ps2,
         }
     }
 }
@@ -92,11 +90,21 @@ impl Default for CensusRestClient {
         Self {
             census_url: CENSUS_URL.clone(),
             service_id: "example".to_string(),
+            environment: Environment::Ps2,
         }
     }
 }
 
 impl CensusRestClient {
+    /// The same client, requesting the namespace of another environment
+    #[must_use]
+    pub fn for_environment(&self, environment: Environment) -> Self {
+        Self {
+            environment,
+            ..self.clone()
+        }
+    }
+
     pub fn get_request_url(
         &self,
         request_type: CensusRequestType,
@@ -105,8 +113,7 @@ impl CensusRestClient {
         let request_type: &str = Into::<&str>::into(request_type);
 
         let census_namespace: String =
-            form_urlencoded::byte_serialize(Into::<&str>::into(CensusNamespaces::Ps2V2).as_bytes())
-                .collect();
+            form_urlencoded::byte_serialize(self.environment.namespace().as_bytes()).collect();
 
         let service_id: String =
             form_urlencoded::byte_serialize(format!("s:{}", self.service_id).as_bytes()).collect();
@@ -125,6 +132,7 @@ impl CensusRestClient {
 }
 
 #[cfg(test)]
+#[allow(clippy::unwrap_used)]
 mod tests {
     use super::*;
 
@@ -138,5 +146,14 @@ mod tests {
             url.as_str(),
             "https://census.daybreakgames.com/s%3Aexample/get/ps2%3Av2/character"
         );
+
+        let url = client
+            .for_environment(Environment::Ps2Ps4Eu)
+            .get_request_url(CensusRequestType::Get, CensusCollections::Map)
+            .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://census.daybreakgames.com/s%3Aexample/get/ps2ps4eu%3Av2/map"
+        );
     }
 }
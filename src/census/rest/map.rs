@@ -0,0 +1,162 @@
+use crate::census::constants::{DefinitionID, FacilityID, Faction, WorldID};
+use crate::census::rest::client::{
+    CensusCollections, CensusRequestError, CensusRequestType, CensusRestClient,
+};
+use crate::census::utils::deserialize_from_str;
+use serde::Deserialize;
+use tracing::debug;
+
+/// Joins the facility of each map region, since realtime events refer to facilities, not regions
+const MAP_REGION_JOIN: &str = "map_region^on:Regions.Row.RowData.RegionId^to:map_region_id^inject_at:map_region^show:facility_id'facility_name'facility_type_id";
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+struct MapRegion {
+    #[serde(rename = "facility_id", deserialize_with = "deserialize_from_str")]
+    id: FacilityID,
+    #[serde(rename = "facility_name")]
+    name: String,
+    #[serde(rename = "facility_type_id", deserialize_with = "deserialize_from_str")]
+    type_id: u16,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct RowData {
+    #[serde(deserialize_with = "deserialize_from_str")]
+    faction_id: Faction,
+    #[serde(rename = "map_region")]
+    map_region: Option<MapRegion>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Row {
+    row_data: RowData,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct Regions {
+    row: Vec<Row>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct ZoneMapResponse {
+    #[serde(deserialize_with = "deserialize_from_str")]
+    zone_id: DefinitionID,
+    regions: Regions,
+}
+
+#[derive(Deserialize, Debug)]
+struct MapResponse {
+    map_list: Vec<ZoneMapResponse>,
+}
+
+/// A facility and the faction that currently owns it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapFacility {
+    pub facility_id: FacilityID,
+    pub name: String,
+    pub facility_type_id: u16,
+    pub faction: Faction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneMap {
+    pub zone_id: DefinitionID,
+    pub facilities: Vec<MapFacility>,
+}
+
+impl From<ZoneMapResponse> for ZoneMap {
+    fn from(response: ZoneMapResponse) -> Self {
+        Self {
+            zone_id: response.zone_id,
+            // Regions without a facility can't be captured
+            facilities: response
+                .regions
+                .row
+                .into_iter()
+                .filter_map(|row| {
+                    let map_region = row.row_data.map_region?;
+                    Some(MapFacility {
+                        facility_id: map_region.id,
+                        name: map_region.name,
+                        facility_type_id: map_region.type_id,
+                        faction: row.row_data.faction_id,
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Get the owner of every facility on the given zones of a world
+///
+/// # Arguments
+///
+/// * `client` - The Census REST client to use
+/// * `world_id` - The world to get the map of
+/// * `zones` - The zones to get the map of
+///
+/// # Returns
+///
+/// * `Ok(Vec<ZoneMap>)` - The facilities of each zone
+/// * `Err(CensusRequestError)` - The error returned while requesting or parsing the map
+pub async fn get_map(
+    client: &CensusRestClient,
+    world_id: WorldID,
+    zones: &[DefinitionID],
+) -> Result<Vec<ZoneMap>, CensusRequestError> {
+    let mut url = client
+        .for_environment(world_id.environment())
+        .get_request_url(CensusRequestType::Get, CensusCollections::Map)?;
+
+    let zone_ids = zones
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    url.set_query(Some(&format!(
+        "world_id={}&zone_ids={zone_ids}&c:join={MAP_REGION_JOIN}",
+        world_id as u16
+    )));
+
+    debug!("Getting map of {} using url: {}", world_id, url);
+
+    let response: MapResponse = reqwest::get(url).await?.json().await?;
+
+    Ok(response.map_list.into_iter().map(ZoneMap::from).collect())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_map() {
+        let response: MapResponse = serde_json::from_str(
+            r#"{"map_list":[{"ZoneId":"2","Regions":{"IsList":"1","Row":[
+                {"RowData":{"RegionId":"2201","FactionId":"2","map_region":{"facility_id":"222280","facility_name":"Crossroads Watchtower","facility_type_id":"3"}}},
+                {"RowData":{"RegionId":"2202","FactionId":"1"}}
+            ]}}],"returned":1}"#,
+        )
+        .unwrap();
+
+        let zone_maps: Vec<ZoneMap> = response.map_list.into_iter().map(ZoneMap::from).collect();
+
+        assert_eq!(
+            zone_maps,
+            vec![ZoneMap {
+                zone_id: DefinitionID(2),
+                facilities: vec![MapFacility {
+                    facility_id: 222_280,
+                    name: "Crossroads Watchtower".to_string(),
+                    facility_type_id: 3,
+                    faction: Faction::NC,
+                }],
+            }]
+        );
+    }
+}
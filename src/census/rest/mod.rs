@@ -1,3 +1,5 @@
 mod character;
 pub mod client;
+pub mod map;
+pub mod outfit;
 pub mod update_data;
@@ -0,0 +1,145 @@
+use crate::census::constants::{CharacterID, OutfitID};
+use crate::census::rest::client::{
+    CensusCollections, CensusRequestError, CensusRequestType, CensusRestClient,
+};
+use crate::census::utils::deserialize_from_str;
+use serde::Deserialize;
+use tracing::debug;
+
+/// The most characters or outfits to request at once, which keeps the URL reasonably short
+pub const MAX_IDS_PER_REQUEST: usize = 100;
+
+/// A character that is a member of an outfit, characters without an outfit have no membership
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutfitMember {
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub character_id: CharacterID,
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub outfit_id: OutfitID,
+}
+
+#[derive(Deserialize, Debug)]
+struct OutfitMemberResponse {
+    outfit_member_list: Vec<OutfitMember>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Outfit {
+    #[serde(rename = "outfit_id", deserialize_with = "deserialize_from_str")]
+    pub id: OutfitID,
+    pub name: String,
+    /// Outfits aren't required to have an alias
+    #[serde(default)]
+    pub alias: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct OutfitResponse {
+    outfit_list: Vec<Outfit>,
+}
+
+fn join_ids(ids: &[u64]) -> String {
+    ids.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Get the outfit memberships of characters
+///
+/// # Arguments
+///
+/// * `client` - The Census REST client to use
+/// * `character_ids` - The characters to look up, at most `MAX_IDS_PER_REQUEST`
+///
+/// # Returns
+///
+/// * `Ok(Vec<OutfitMember>)` - The memberships of the characters that are in an outfit
+/// * `Err(CensusRequestError)` - The error returned while requesting or parsing the memberships
+pub async fn get_outfit_members(
+    client: &CensusRestClient,
+    character_ids: &[CharacterID],
+) -> Result<Vec<OutfitMember>, CensusRequestError> {
+    let mut url =
+        client.get_request_url(CensusRequestType::Get, CensusCollections::OutfitMember)?;
+
+    url.set_query(Some(&format!(
+        "character_id={}&c:show=character_id,outfit_id&c:limit={}",
+        join_ids(character_ids),
+        character_ids.len()
+    )));
+
+    debug!(
+        "Getting outfits of {} characters using url: {}",
+        character_ids.len(),
+        url
+    );
+
+    let response: OutfitMemberResponse = reqwest::get(url).await?.json().await?;
+
+    Ok(response.outfit_member_list)
+}
+
+/// Get the name and alias of outfits
+///
+/// # Arguments
+///
+/// * `client` - The Census REST client to use
+/// * `outfit_ids` - The outfits to look up, at most `MAX_IDS_PER_REQUEST`
+///
+/// # Returns
+///
+/// * `Ok(Vec<Outfit>)` - The outfits that still exist
+/// * `Err(CensusRequestError)` - The error returned while requesting or parsing the outfits
+pub async fn get_outfits(
+    client: &CensusRestClient,
+    outfit_ids: &[OutfitID],
+) -> Result<Vec<Outfit>, CensusRequestError> {
+    let mut url = client.get_request_url(CensusRequestType::Get, CensusCollections::Outfit)?;
+
+    url.set_query(Some(&format!(
+        "outfit_id={}&c:show=outfit_id,name,alias&c:limit={}",
+        join_ids(outfit_ids),
+        outfit_ids.len()
+    )));
+
+    debug!("Getting {} outfits using url: {}", outfit_ids.len(), url);
+
+    let response: OutfitResponse = reqwest::get(url).await?.json().await?;
+
+    Ok(response.outfit_list)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_outfit_members() {
+        let response: OutfitMemberResponse = serde_json::from_str(
+            r#"{"outfit_member_list":[{"character_id":"5428010618015189713","outfit_id":"37570391403474491"}],"returned":1}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            response.outfit_member_list,
+            vec![OutfitMember {
+                character_id: 5_428_010_618_015_189_713,
+                outfit_id: 37_570_391_403_474_491,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_outfits() {
+        let response: OutfitResponse = serde_json::from_str(
+            r#"{"outfit_list":[{"outfit_id":"37570391403474491","name":"Niumside","alias":"NIUM"},{"outfit_id":"37570391403474492","name":"No Alias"}],"returned":2}"#,
+        )
+        .unwrap();
+
+        assert_eq!(response.outfit_list[0].alias, "NIUM");
+        assert_eq!(response.outfit_list[1].alias, "");
+        assert_eq!(response.outfit_list[1].id, 37_570_391_403_474_492);
+    }
+}
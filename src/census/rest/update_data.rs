@@ -34,21 +34,18 @@ struct ZoneResponse {
     zone_list: Vec<CensusZoneResponse>,
 }
 
-pub async fn update_from_lithafalcon(db_pool: &PgPool) {
-    let request_url = format!("{LITHAFALCON_BASE_URL}/get/PS2/zone?c:censusJSON=false&c:lang=en&c:show=zone_id,name,description");
-    let request = match reqwest::get(request_url).await {
-        Ok(response) => response,
-        Err(e) => {
-            error!("Error while requesting zones from lithafalcon: {e}");
-            return;
-        }
-    }
-    .json::<ZoneResponse>()
-    .await;
+async fn get_zones(base_url: &str) -> Result<Vec<CensusZoneResponse>, reqwest::Error> {
+    let request_url = format!(
+        "{base_url}/get/PS2/zone?c:censusJSON=false&c:lang=en&c:show=zone_id,name,description"
+    );
+    let response: ZoneResponse = reqwest::get(request_url).await?.json().await?;
 
-    match request {
-        Ok(response) => {
-            let zones = response.zone_list;
+    Ok(response.zone_list)
+}
+
+pub async fn update_from_lithafalcon(db_pool: &PgPool) {
+    match get_zones(LITHAFALCON_BASE_URL).await {
+        Ok(zones) => {
             info!("Got {} zones from lithafalcon", zones.len());
 
             let mut transaction = match db_pool.begin().await {
@@ -164,21 +161,29 @@ pub async fn run(db_pool: &PgPool, census_rest_client: &CensusRestClient) {
 }
 
 #[cfg(test)]
-#[allow(clippy::expect_used)]
+#[allow(clippy::unwrap_used)]
 mod tests {
     use super::*;
+    use crate::census::mock::MockCensus;
 
     #[tokio::test]
     async fn test_parsing_from_lithafalcon() {
-        let request_url = format!("{LITHAFALCON_BASE_URL}/get/PS2/zone?c:censusJSON=false&c:lang=en&c:show=zone_id,name,description");
-        match reqwest::get(request_url).await {
-            Ok(response) => response,
-            Err(e) => {
-                panic!("Error while requesting zones from lithafalcon: {e}");
-            }
-        }
-        .json::<ZoneResponse>()
-        .await
-        .expect("Unable to parse JSON");
+        let census = MockCensus::start().await;
+        census.respond(
+            "/get/PS2/zone",
+            &serde_json::json!({
+                "zone_list": [
+                    {"zone_id": 2, "name": {"en": "Indar"}, "description": {"en": "The arid continent of Indar"}},
+                    {"zone_id": 96}
+                ]
+            }),
+        );
+        let base_url = census.census_url.as_str().trim_end_matches('/');
+
+        let zones = get_zones(base_url).await.unwrap();
+        assert_eq!(zones.len(), 2);
+        assert_eq!(zones[0].zone_id, ZoneID(2));
+        assert_eq!(zones[0].name.as_ref().unwrap().en.as_deref(), Some("Indar"));
+        assert!(zones[1].name.is_none());
     }
 }
@@ -0,0 +1,261 @@
+use crate::census::constants::{Environment, EventServerEndpoint, WorldID};
+use chrono::{DateTime, Utc};
+use metrics::{counter, gauge};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tracing::error;
+use utoipa::ToSchema;
+
+/// Whether the event server of each world is online, as reported by Census heartbeats
+pub type EventServerStatus = HashMap<(EventServerEndpoint, WorldID), bool>;
+
+#[derive(Debug, Clone, Default)]
+pub struct ServerHealth {
+    pub last_heartbeat: Option<DateTime<Utc>>,
+    pub online: EventServerStatus,
+    /// The last subscription Census acknowledged, by environment and upstream
+    pub subscriptions: HashMap<(Environment, String), ActiveSubscription>,
+}
+
+pub type ServerHealthDb = Arc<Mutex<ServerHealth>>;
+
+/// The status of a single event server as shown in the population API
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventStream {
+    pub endpoint: EventServerEndpoint,
+    pub world_id: WorldID,
+    pub online: bool,
+}
+
+/// The subscription Census acknowledged on a realtime connection
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct ActiveSubscription {
+    pub environment: Environment,
+    pub upstream: String,
+    pub event_names: Vec<String>,
+    pub worlds: Vec<String>,
+    pub logical_and_characters_with_worlds: bool,
+    pub character_count: u64,
+    /// The requested fields that the acknowledged subscription doesn't include, events are
+    /// missed while this isn't empty
+    pub mismatches: Vec<String>,
+    pub acknowledged: DateTime<Utc>,
+}
+
+/// Parse an event server name as used by Census, such as `EventServerEndpoint_Connery_1`
+pub fn parse_endpoint(name: &str) -> Option<(EventServerEndpoint, WorldID)> {
+    let mut parts = name.split('_');
+
+    if parts.next()? != "EventServerEndpoint" {
+        return None;
+    }
+
+    let endpoint = parts.next()?.parse().ok()?;
+    let world = parts.next()?.parse().ok()?;
+
+    parts.next().is_none().then_some((endpoint, world))
+}
+
+impl ServerHealth {
+    /// Replace the status of the event servers of the environments in a heartbeat, every
+    /// environment sends its own heartbeats
+    pub fn heartbeat(&mut self, online: EventServerStatus, timestamp: DateTime<Utc>) {
+        let environments: HashSet<Environment> = online
+            .keys()
+            .map(|(_, world)| world.environment())
+            .collect();
+
+        self.online
+            .retain(|(_, world), _| !environments.contains(&world.environment()));
+        self.online.extend(online);
+        self.last_heartbeat = Some(timestamp);
+    }
+
+    /// Mark the known event servers of an environment as offline, used when the connection to
+    /// Census is lost
+    pub fn mark_offline(&mut self, environment: Environment) {
+        self.online
+            .iter_mut()
+            .filter(|((_, world), _)| world.environment() == environment)
+            .for_each(|(_, online)| *online = false);
+    }
+
+    /// Whether the event stream of a world is online, `None` if Census never reported on it
+    pub fn world_online(&self, world: WorldID) -> Option<bool> {
+        self.online
+            .iter()
+            .filter(|((_, world_id), _)| *world_id == world)
+            .map(|(_, online)| *online)
+            .reduce(|a, b| a && b)
+    }
+
+    pub fn subscribed(&mut self, subscription: ActiveSubscription) {
+        self.subscriptions.insert(
+            (subscription.environment, subscription.upstream.clone()),
+            subscription,
+        );
+    }
+
+    pub fn subscriptions(&self) -> Vec<ActiveSubscription> {
+        let mut subscriptions: Vec<ActiveSubscription> =
+            self.subscriptions.values().cloned().collect();
+
+        subscriptions
+            .sort_by(|a, b| (a.environment, &a.upstream).cmp(&(b.environment, &b.upstream)));
+        subscriptions
+    }
+
+    pub fn event_streams(&self) -> Vec<EventStream> {
+        let mut event_streams: Vec<EventStream> = self
+            .online
+            .iter()
+            .map(|((endpoint, world_id), online)| EventStream {
+                endpoint: *endpoint,
+                world_id: *world_id,
+                online: *online,
+            })
+            .collect();
+
+        event_streams.sort_by_key(|stream| (stream.world_id, stream.endpoint));
+        event_streams
+    }
+
+    fn report(&self) {
+        for ((endpoint, world), online) in &self.online {
+            gauge!(
+                "realtime_event_server_online",
+                "endpoint" => endpoint.to_string(),
+                "world" => world.to_string()
+            )
+            .set(f64::from(u8::from(*online)));
+        }
+
+        for subscription in self.subscriptions.values() {
+            gauge!(
+                "niumside_subscription_mismatch",
+                "environment" => subscription.environment.to_string(),
+                "upstream" => subscription.upstream.clone()
+            )
+            .set(f64::from(u8::from(!subscription.mismatches.is_empty())));
+        }
+    }
+}
+
+/// Apply a change to the shared server health and update the gauges
+pub fn update(server_health: &ServerHealthDb, change: impl FnOnce(&mut ServerHealth)) {
+    server_health.lock().map_or_else(
+        |_| {
+            counter!("niumside_server_health_lock_failed").increment(1);
+            error!("Failed to lock server_health");
+        },
+        |mut guard| {
+            change(&mut guard);
+            guard.report();
+        },
+    );
+}
+
+/// Get the status of all event servers for the API
+pub fn event_streams(server_health: &ServerHealthDb) -> Vec<EventStream> {
+    server_health.lock().map_or_else(
+        |_| {
+            counter!("niumside_server_health_lock_failed").increment(1);
+            error!("Failed to lock server_health");
+            Vec::new()
+        },
+        |guard| guard.event_streams(),
+    )
+}
+
+/// Get the subscriptions Census acknowledged for the API
+pub fn subscriptions(server_health: &ServerHealthDb) -> Vec<ActiveSubscription> {
+    server_health.lock().map_or_else(
+        |_| {
+            counter!("niumside_server_health_lock_failed").increment(1);
+            error!("Failed to lock server_health");
+            Vec::new()
+        },
+        |guard| guard.subscriptions(),
+    )
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::CensusMessage;
+
+    #[test]
+    fn test_parse_endpoint() {
+        assert_eq!(
+            parse_endpoint("EventServerEndpoint_Connery_1"),
+            Some((EventServerEndpoint::Connery, WorldID::Connery))
+        );
+        assert_eq!(
+            parse_endpoint("EventServerEndpoint_Cobalt_13"),
+            Some((EventServerEndpoint::Cobalt, WorldID::Cobalt))
+        );
+        assert_eq!(parse_endpoint("EventServerEndpoint_Unknown_1"), None);
+        assert_eq!(parse_endpoint("EventServerEndpoint_Connery_1_2"), None);
+        assert_eq!(parse_endpoint("Connery_1"), None);
+    }
+
+    #[test]
+    fn test_world_online() {
+        let mut server_health = ServerHealth::default();
+        server_health.heartbeat(
+            HashMap::from([
+                ((EventServerEndpoint::Connery, WorldID::Connery), true),
+                ((EventServerEndpoint::Miller, WorldID::Miller), false),
+            ]),
+            Utc::now(),
+        );
+
+        assert_eq!(server_health.world_online(WorldID::Connery), Some(true));
+        assert_eq!(server_health.world_online(WorldID::Miller), Some(false));
+        assert_eq!(server_health.world_online(WorldID::Emerald), None);
+
+        server_health.mark_offline(Environment::Ps2);
+        assert_eq!(server_health.world_online(WorldID::Connery), Some(false));
+        assert_eq!(server_health.event_streams().len(), 2);
+    }
+
+    #[test]
+    fn test_environments_are_kept_apart() {
+        let mut server_health = ServerHealth::default();
+        server_health.heartbeat(
+            HashMap::from([((EventServerEndpoint::Connery, WorldID::Connery), true)]),
+            Utc::now(),
+        );
+        server_health.heartbeat(
+            HashMap::from([((EventServerEndpoint::Genudine, WorldID::Genudine), true)]),
+            Utc::now(),
+        );
+
+        assert_eq!(server_health.world_online(WorldID::Connery), Some(true));
+        assert_eq!(server_health.world_online(WorldID::Genudine), Some(true));
+
+        server_health.mark_offline(Environment::Ps2Ps4Us);
+        assert_eq!(server_health.world_online(WorldID::Connery), Some(true));
+        assert_eq!(server_health.world_online(WorldID::Genudine), Some(false));
+    }
+
+    #[test]
+    fn test_heartbeat_skips_unknown_worlds() {
+        let message: CensusMessage = serde_json::from_str(
+            r#"{"online":{"EventServerEndpoint_Cobalt_13":"true","EventServerEndpoint_Miller_10":"false","EventServerEndpoint_Palos_1001":"true"},"service":"event","type":"heartbeat"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            message,
+            CensusMessage::Heartbeat {
+                online: HashMap::from([
+                    ((EventServerEndpoint::Cobalt, WorldID::Cobalt), true),
+                    ((EventServerEndpoint::Miller, WorldID::Miller), false),
+                ]),
+            }
+        );
+    }
+}
@@ -4,7 +4,7 @@ use crate::census::utils::{
 
 use crate::census::constants::{CharacterID, WorldID};
 use crate::census::event::EventNames;
-use crate::census::Service;
+use crate::census::{Service, Subscription};
 use serde::Serialize;
 
 #[derive(Serialize, Clone, Debug)]
@@ -63,3 +63,102 @@ impl Default for SubscriptionSettings {
         }
     }
 }
+
+impl SubscriptionSettings {
+    /// The fields of an acknowledged subscription that don't include what was requested.
+    /// Subscriptions on a connection add up, so the acknowledgement may include more.
+    pub fn mismatches(&self, acknowledged: &Subscription) -> Vec<&'static str> {
+        let mut mismatches = Vec::new();
+
+        if !includes(self.event_names.as_ref(), &acknowledged.event_names) {
+            mismatches.push("eventNames");
+        }
+        if !includes(self.worlds.as_ref(), &acknowledged.worlds) {
+            mismatches.push("worlds");
+        }
+        if self
+            .logical_and_characters_with_worlds
+            .is_some_and(|logical_and| {
+                logical_and != acknowledged.logical_and_characters_with_worlds
+            })
+        {
+            mismatches.push("logicalAndCharactersWithWorlds");
+        }
+
+        mismatches
+    }
+}
+
+/// Whether the acknowledged names include every requested name as Census receives them,
+/// `all` includes every name
+fn includes(requested: Option<&impl Serialize>, acknowledged: &[String]) -> bool {
+    let is_acknowledged = |name: &str| {
+        acknowledged
+            .iter()
+            .any(|acknowledged| acknowledged.eq_ignore_ascii_case(name))
+    };
+    if is_acknowledged("all") {
+        return true;
+    }
+
+    let Some(requested) = requested else {
+        return true;
+    };
+    let Ok(serde_json::Value::Array(requested)) = serde_json::to_value(requested) else {
+        return false;
+    };
+
+    requested
+        .iter()
+        .all(|name| name.as_str().is_some_and(is_acknowledged))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn acknowledged(event_names: &[&str], worlds: &[&str]) -> Subscription {
+        Subscription {
+            character_count: 0,
+            event_names: event_names.iter().map(|name| (*name).to_owned()).collect(),
+            logical_and_characters_with_worlds: true,
+            worlds: worlds.iter().map(|world| (*world).to_owned()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_subscription_mismatches() {
+        let settings = SubscriptionSettings {
+            event_names: Some(EventSubscription::Ids(vec![
+                EventNames::PlayerLogin,
+                EventNames::GainExperienceId(1234),
+            ])),
+            worlds: Some(WorldSubscription::Ids(vec![WorldID::Miller])),
+            logical_and_characters_with_worlds: Some(true),
+            ..SubscriptionSettings::default()
+        };
+
+        let matching = acknowledged(
+            &["PlayerLogin", "GainExperience_experience_id_1234", "Death"],
+            &["10"],
+        );
+        assert!(settings.mismatches(&matching).is_empty());
+
+        let everything = acknowledged(&["all"], &["all"]);
+        assert!(settings.mismatches(&everything).is_empty());
+
+        let mut missing = acknowledged(&["PlayerLogin"], &["13"]);
+        missing.logical_and_characters_with_worlds = false;
+        assert_eq!(
+            settings.mismatches(&missing),
+            vec!["eventNames", "worlds", "logicalAndCharactersWithWorlds"]
+        );
+
+        let all_worlds = SubscriptionSettings::default();
+        assert_eq!(
+            all_worlds.mismatches(&acknowledged(&["all"], &["10"])),
+            vec!["worlds"]
+        );
+    }
+}
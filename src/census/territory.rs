@@ -0,0 +1,310 @@
+use crate::census::constants::{DefinitionID, Environment, FacilityID, Faction, WorldID};
+use crate::census::rest::client::CensusRestClient;
+use crate::census::rest::map::{self, ZoneMap};
+use crate::controllers::facility;
+use crate::storage::configuration::WorldConfig;
+use metrics::counter;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use strum::IntoEnumIterator;
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+/// The zones to seed when a world doesn't configure its zones: Indar, Hossin, Amerish, Esamir and
+/// Oshur. Other zones are instanced or have no territory.
+const MAIN_CONTINENTS: [DefinitionID; 5] = [
+    DefinitionID(2),
+    DefinitionID(4),
+    DefinitionID(6),
+    DefinitionID(8),
+    DefinitionID(344),
+];
+
+/// The faction that owns each facility of a zone
+pub type FacilityOwners = HashMap<FacilityID, Faction>;
+
+#[derive(Debug, Clone, Default)]
+pub struct Territory {
+    pub zones: HashMap<(WorldID, DefinitionID), FacilityOwners>,
+}
+
+pub type TerritoryDb = Arc<Mutex<Territory>>;
+
+/// The share of the facilities of a zone each faction owns, as shown in the population API
+#[derive(Serialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+pub struct ZoneTerritory {
+    pub world_id: WorldID,
+    pub zone_id: DefinitionID,
+    pub facilities: usize,
+    pub vs: f32,
+    pub nc: f32,
+    pub tr: f32,
+}
+
+impl Territory {
+    /// Replace the owners of every facility of a zone with the ones reported by Census
+    pub fn seed(&mut self, world_id: WorldID, zone_map: &ZoneMap) {
+        self.zones.insert(
+            (world_id, zone_map.zone_id),
+            zone_map
+                .facilities
+                .iter()
+                .map(|facility| (facility.facility_id, facility.faction))
+                .collect(),
+        );
+    }
+
+    /// Set the owner of a facility, returning the previous owner if it was known
+    pub fn capture(
+        &mut self,
+        world_id: WorldID,
+        zone_id: DefinitionID,
+        facility_id: FacilityID,
+        faction: Faction,
+    ) -> Option<Faction> {
+        self.zones
+            .entry((world_id, zone_id))
+            .or_default()
+            .insert(facility_id, faction)
+    }
+
+    /// Facilities that aren't owned by any faction, such as the warpgates of a locked continent,
+    /// don't count towards the territory of any faction
+    pub fn zone_territory(
+        &self,
+        world_id: WorldID,
+        zone_id: DefinitionID,
+    ) -> Option<ZoneTerritory> {
+        let owners = self.zones.get(&(world_id, zone_id))?;
+
+        let owned = |faction| owners.values().filter(|owner| **owner == faction).count();
+        let (vs, nc, tr) = (owned(Faction::VS), owned(Faction::NC), owned(Faction::TR));
+        let facilities = vs + nc + tr;
+
+        #[allow(clippy::cast_precision_loss)]
+        let percentage = |count: usize| {
+            if facilities == 0 {
+                0.0
+            } else {
+                count as f32 * 100.0 / facilities as f32
+            }
+        };
+
+        Some(ZoneTerritory {
+            world_id,
+            zone_id,
+            facilities,
+            vs: percentage(vs),
+            nc: percentage(nc),
+            tr: percentage(tr),
+        })
+    }
+
+    pub fn territories(&self, worlds: Option<&[WorldID]>) -> Vec<ZoneTerritory> {
+        let mut territories: Vec<ZoneTerritory> = self
+            .zones
+            .keys()
+            .filter(|(world_id, _)| worlds.is_none_or(|worlds| worlds.contains(world_id)))
+            .filter_map(|(world_id, zone_id)| self.zone_territory(*world_id, *zone_id))
+            .collect();
+
+        territories.sort_by_key(|territory| (territory.world_id, territory.zone_id));
+        territories
+    }
+}
+
+/// Apply a change to the shared territory
+pub fn update<T>(territory: &TerritoryDb, change: impl FnOnce(&mut Territory) -> T) -> Option<T> {
+    territory.lock().map_or_else(
+        |_| {
+            counter!("niumside_territory_lock_failed").increment(1);
+            error!("Failed to lock territory");
+            None
+        },
+        |mut guard| Some(change(&mut guard)),
+    )
+}
+
+/// Get the territory of every known zone for the API
+pub fn territories(territory: &TerritoryDb, worlds: Option<&[WorldID]>) -> Vec<ZoneTerritory> {
+    update(territory, |territory| territory.territories(worlds)).unwrap_or_default()
+}
+
+/// The zones to seed for each world, all worlds of the environments and main continents when no
+/// worlds are configured
+fn seeded_zones(
+    worlds: &[WorldConfig],
+    environments: &[Environment],
+) -> Vec<(WorldID, Vec<DefinitionID>)> {
+    if worlds.is_empty() {
+        return WorldID::iter()
+            .filter(|world_id| {
+                *world_id != WorldID::Unknown && environments.contains(&world_id.environment())
+            })
+            .map(|world_id| (world_id, MAIN_CONTINENTS.to_vec()))
+            .collect();
+    }
+
+    worlds
+        .iter()
+        .map(|world| {
+            (
+                world.id,
+                world
+                    .zones
+                    .clone()
+                    .unwrap_or_else(|| MAIN_CONTINENTS.to_vec()),
+            )
+        })
+        .collect()
+}
+
+async fn seed(
+    territory: &TerritoryDb,
+    db_pool: &PgPool,
+    census_rest_client: &CensusRestClient,
+    world_id: WorldID,
+    zones: &[DefinitionID],
+) {
+    let zone_maps = match map::get_map(census_rest_client, world_id, zones).await {
+        Ok(zone_maps) => zone_maps,
+        Err(e) => {
+            counter!("niumside_territory_seed_failed", "world" => world_id.to_string())
+                .increment(1);
+            error!("Error while requesting the map of {world_id}: {e}");
+            return;
+        }
+    };
+
+    for zone_map in &zone_maps {
+        if let Err(e) = facility::upsert_facilities(db_pool, zone_map).await {
+            error!(
+                "Error while storing the facilities of zone {}: {e}",
+                zone_map.zone_id.0
+            );
+        }
+    }
+
+    update(territory, |territory| {
+        for zone_map in &zone_maps {
+            territory.seed(world_id, zone_map);
+        }
+    });
+    info!(
+        "Seeded territory of {} zones on {world_id}",
+        zone_maps.len()
+    );
+}
+
+/// Seed the territory from the Census map, and reseed it regularly in case captures were missed
+pub async fn run(
+    territory: TerritoryDb,
+    db_pool: PgPool,
+    census_rest_client: CensusRestClient,
+    worlds: Vec<WorldConfig>,
+    environments: Vec<Environment>,
+) {
+    let seeded_zones = seeded_zones(&worlds, &environments);
+    loop {
+        for (world_id, zones) in &seeded_zones {
+            seed(&territory, &db_pool, &census_rest_client, *world_id, zones).await;
+        }
+        tokio::time::sleep(Duration::from_mins(30)).await;
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::rest::map::MapFacility;
+
+    fn map_facility(facility_id: FacilityID, faction: Faction) -> MapFacility {
+        MapFacility {
+            facility_id,
+            name: String::new(),
+            facility_type_id: 0,
+            faction,
+        }
+    }
+
+    #[test]
+    fn test_zone_territory() {
+        let mut territory = Territory::default();
+        territory.seed(
+            WorldID::Miller,
+            &ZoneMap {
+                zone_id: DefinitionID(2),
+                facilities: vec![
+                    map_facility(1, Faction::VS),
+                    map_facility(2, Faction::NC),
+                    map_facility(3, Faction::NC),
+                    map_facility(4, Faction::TR),
+                    map_facility(5, Faction::Unknown),
+                ],
+            },
+        );
+
+        let zone_territory = territory
+            .zone_territory(WorldID::Miller, DefinitionID(2))
+            .unwrap();
+        assert_eq!(zone_territory.facilities, 4);
+        assert!((zone_territory.vs - 25.0).abs() < f32::EPSILON);
+        assert!((zone_territory.nc - 50.0).abs() < f32::EPSILON);
+        assert!((zone_territory.tr - 25.0).abs() < f32::EPSILON);
+
+        assert_eq!(
+            territory.capture(WorldID::Miller, DefinitionID(2), 3, Faction::TR),
+            Some(Faction::NC)
+        );
+        let zone_territory = territory
+            .zone_territory(WorldID::Miller, DefinitionID(2))
+            .unwrap();
+        assert!((zone_territory.tr - 50.0).abs() < f32::EPSILON);
+
+        assert!(territory
+            .zone_territory(WorldID::Cobalt, DefinitionID(2))
+            .is_none());
+        assert_eq!(territory.territories(Some(&[WorldID::Cobalt])), vec![]);
+        assert_eq!(territory.territories(None).len(), 1);
+    }
+
+    #[test]
+    fn test_seeded_zones() {
+        let pc_worlds = seeded_zones(&[], &[Environment::Ps2]);
+        assert!(pc_worlds
+            .iter()
+            .any(|(world_id, _)| *world_id == WorldID::Miller));
+        assert!(!pc_worlds
+            .iter()
+            .any(|(world_id, _)| *world_id == WorldID::Ceres));
+        assert_eq!(
+            seeded_zones(&[], &[Environment::Ps2, Environment::Ps2Ps4Eu]).len(),
+            pc_worlds.len() + 1
+        );
+
+        let zones = seeded_zones(
+            &[
+                WorldConfig {
+                    id: WorldID::Miller,
+                    zones: None,
+                },
+                WorldConfig {
+                    id: WorldID::Cobalt,
+                    zones: Some(vec![DefinitionID(2)]),
+                },
+            ],
+            &[Environment::Ps2],
+        );
+        assert_eq!(
+            zones,
+            vec![
+                (WorldID::Miller, MAIN_CONTINENTS.to_vec()),
+                (WorldID::Cobalt, vec![DefinitionID(2)]),
+            ]
+        );
+    }
+}
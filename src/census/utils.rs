@@ -1,7 +1,11 @@
 use crate::census::constants::{CharacterID, WorldID};
+use crate::census::server_health::{parse_endpoint, EventServerStatus};
 use chrono::Duration;
+use metrics::counter;
 use serde::{Deserialize, Serialize, Serializer};
 use serde_json::json;
+use std::collections::HashMap;
+use tracing::debug;
 
 #[allow(clippy::trivially_copy_pass_by_ref)]
 #[allow(clippy::ref_option)]
@@ -49,7 +53,7 @@ where
 {
     let mut ids = Vec::with_capacity(value.len());
     for id in value {
-        ids.push((*id as u8).to_string());
+        ids.push((*id as u16).to_string());
     }
 
     serializer.collect_seq(ids.iter())
@@ -77,6 +81,48 @@ where
         .map_err(serde::de::Error::custom)
 }
 
+/// Deserialize a constant that Census may add values to, such as a loadout, using the `Unknown`
+/// default for values that aren't known yet instead of failing the entire event
+pub fn deserialize_or_unknown<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: std::str::FromStr + Default,
+    D: serde::Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+
+    Ok(value.parse().unwrap_or_else(|_| {
+        let type_name = std::any::type_name::<T>()
+            .rsplit("::")
+            .next()
+            .unwrap_or_default();
+        counter!("niumside_unknown_values", "type" => type_name).increment(1);
+        debug!("Unknown {type_name} value: {value}");
+        T::default()
+    }))
+}
+
+/// Deserialize the `online` map of a heartbeat. Event servers that aren't known yet are skipped,
+/// so a new world doesn't make every heartbeat fail to parse.
+pub fn deserialize_event_server_status<'de, D>(
+    deserializer: D,
+) -> Result<EventServerStatus, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let mut status = EventServerStatus::new();
+
+    for (name, online) in HashMap::<String, String>::deserialize(deserializer)? {
+        let Some(endpoint) = parse_endpoint(&name) else {
+            debug!("Skipping unknown event server in heartbeat: {name}");
+            continue;
+        };
+
+        status.insert(endpoint, online.parse().map_err(serde::de::Error::custom)?);
+    }
+
+    Ok(status)
+}
+
 pub fn deserialize_duration_from_str<'de, D>(deserializer: D) -> Result<Duration, D::Error>
 where
     D: serde::Deserializer<'de>,
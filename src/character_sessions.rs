@@ -0,0 +1,347 @@
+use crate::census::constants::{CharacterID, OutfitID, WorldID};
+use crate::controllers::character_session;
+use chrono::{DateTime, Utc};
+use metrics::{counter, gauge};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tracing::{error, info};
+
+/// Minutes without activity after which an open session is closed, in case the logout was missed
+const SESSION_TIMEOUT_MINUTES: i64 = 60;
+
+/// Activity of a character that is relevant to their session, sent by the event handlers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEvent {
+    Login {
+        character_id: CharacterID,
+        world_id: WorldID,
+        timestamp: DateTime<Utc>,
+    },
+    Logout {
+        character_id: CharacterID,
+        timestamp: DateTime<Utc>,
+    },
+    Activity {
+        character_id: CharacterID,
+        world_id: WorldID,
+        timestamp: DateTime<Utc>,
+    },
+    Outfit {
+        character_id: CharacterID,
+        outfit_id: OutfitID,
+    },
+}
+
+pub type SessionSender = UnboundedSender<SessionEvent>;
+
+/// Send session activity to the tracker, which only fails when the tracker stopped
+pub fn send(character_sessions: &SessionSender, event: SessionEvent) {
+    if character_sessions.send(event).is_err() {
+        counter!("niumside_character_sessions_send_failed").increment(1);
+        error!("Unable to send event to the character session tracker");
+    }
+}
+
+/// A change that has to be written to the `character_session` table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionChange {
+    Open {
+        character_id: CharacterID,
+        world_id: WorldID,
+        session_start: DateTime<Utc>,
+    },
+    Close {
+        character_id: CharacterID,
+        session_end: DateTime<Utc>,
+    },
+    SetOutfit {
+        character_id: CharacterID,
+        outfit_id: OutfitID,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct OpenSession {
+    last_seen: DateTime<Utc>,
+    outfit_id: Option<OutfitID>,
+}
+
+/// Keeps track of open sessions in memory, so not every event needs a database round trip
+#[derive(Debug, Default)]
+pub struct SessionTracker {
+    open_sessions: HashMap<CharacterID, OpenSession>,
+}
+
+impl SessionTracker {
+    pub fn apply(&mut self, event: SessionEvent) -> Vec<SessionChange> {
+        let mut changes = Vec::new();
+
+        match event {
+            SessionEvent::Login {
+                character_id,
+                world_id,
+                timestamp,
+            } => {
+                // A second login means the previous logout was missed
+                if let Some(session) = self.open_sessions.remove(&character_id) {
+                    changes.push(SessionChange::Close {
+                        character_id,
+                        session_end: session.last_seen,
+                    });
+                }
+
+                self.open(character_id, world_id, timestamp, &mut changes);
+            }
+            SessionEvent::Logout {
+                character_id,
+                timestamp,
+            } => {
+                if self.open_sessions.remove(&character_id).is_some() {
+                    changes.push(SessionChange::Close {
+                        character_id,
+                        session_end: timestamp,
+                    });
+                }
+            }
+            SessionEvent::Activity {
+                character_id,
+                world_id,
+                timestamp,
+            } => match self.open_sessions.get_mut(&character_id) {
+                Some(session) => session.last_seen = session.last_seen.max(timestamp),
+                // The character logged in before we started listening
+                None => self.open(character_id, world_id, timestamp, &mut changes),
+            },
+            SessionEvent::Outfit {
+                character_id,
+                outfit_id,
+            } => {
+                if let Some(session) = self.open_sessions.get_mut(&character_id) {
+                    if outfit_id != 0 && session.outfit_id != Some(outfit_id) {
+                        session.outfit_id = Some(outfit_id);
+                        changes.push(SessionChange::SetOutfit {
+                            character_id,
+                            outfit_id,
+                        });
+                    }
+                }
+            }
+        }
+
+        changes
+    }
+
+    /// Close the sessions of characters that haven't been seen for `SESSION_TIMEOUT_MINUTES`
+    pub fn close_idle(&mut self, now: DateTime<Utc>) -> Vec<SessionChange> {
+        let timeout = chrono::Duration::minutes(SESSION_TIMEOUT_MINUTES);
+        let mut changes = Vec::new();
+
+        self.open_sessions.retain(|character_id, session| {
+            let active = session.last_seen + timeout > now;
+            if !active {
+                changes.push(SessionChange::Close {
+                    character_id: *character_id,
+                    session_end: session.last_seen,
+                });
+            }
+            active
+        });
+
+        changes
+    }
+
+    pub fn open_sessions(&self) -> usize {
+        self.open_sessions.len()
+    }
+
+    fn open(
+        &mut self,
+        character_id: CharacterID,
+        world_id: WorldID,
+        session_start: DateTime<Utc>,
+        changes: &mut Vec<SessionChange>,
+    ) {
+        self.open_sessions.insert(
+            character_id,
+            OpenSession {
+                last_seen: session_start,
+                outfit_id: None,
+            },
+        );
+        changes.push(SessionChange::Open {
+            character_id,
+            world_id,
+            session_start,
+        });
+    }
+}
+
+async fn store(changes: Vec<SessionChange>, db_pool: &PgPool) {
+    for change in changes {
+        let result = match change {
+            SessionChange::Open {
+                character_id,
+                world_id,
+                session_start,
+            } => character_session::open(db_pool, character_id, world_id, session_start).await,
+            SessionChange::Close {
+                character_id,
+                session_end,
+            } => character_session::close(db_pool, character_id, session_end).await,
+            SessionChange::SetOutfit {
+                character_id,
+                outfit_id,
+            } => character_session::set_outfit(db_pool, character_id, outfit_id).await,
+        };
+
+        if let Err(e) = result {
+            counter!("niumside_character_sessions_failed_writes").increment(1);
+            error!("Failed to store character session change: {e}");
+        }
+    }
+}
+
+pub async fn run(mut receiver: UnboundedReceiver<SessionEvent>, db_pool: &PgPool) -> Option<()> {
+    match character_session::close_dangling(db_pool).await {
+        Ok(closed) => info!("Closed {closed} dangling character sessions"),
+        Err(e) => error!("Failed to close dangling character sessions: {e}"),
+    }
+
+    let mut tracker = SessionTracker::default();
+    let mut idle_check = tokio::time::interval(Duration::from_secs(30));
+
+    loop {
+        tokio::select! {
+            event = receiver.recv() => {
+                let event = event?;
+                store(tracker.apply(event), db_pool).await;
+            }
+            _ = idle_check.tick() => {
+                store(tracker.close_idle(Utc::now()), db_pool).await;
+                #[allow(clippy::cast_precision_loss)]
+                gauge!("niumside_character_sessions_open").set(tracker.open_sessions() as f64);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    const CHARACTER_ID: CharacterID = 5_429_573_939_285_739_921;
+
+    fn timestamp(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn test_login_and_logout() {
+        let mut tracker = SessionTracker::default();
+
+        let changes = tracker.apply(SessionEvent::Login {
+            character_id: CHARACTER_ID,
+            world_id: WorldID::Miller,
+            timestamp: timestamp(0),
+        });
+        assert_eq!(
+            changes,
+            vec![SessionChange::Open {
+                character_id: CHARACTER_ID,
+                world_id: WorldID::Miller,
+                session_start: timestamp(0),
+            }]
+        );
+
+        let changes = tracker.apply(SessionEvent::Logout {
+            character_id: CHARACTER_ID,
+            timestamp: timestamp(600),
+        });
+        assert_eq!(
+            changes,
+            vec![SessionChange::Close {
+                character_id: CHARACTER_ID,
+                session_end: timestamp(600),
+            }]
+        );
+        assert_eq!(tracker.open_sessions(), 0);
+    }
+
+    #[test]
+    fn test_activity_opens_session_once() {
+        let mut tracker = SessionTracker::default();
+        let activity = |seconds| SessionEvent::Activity {
+            character_id: CHARACTER_ID,
+            world_id: WorldID::Miller,
+            timestamp: timestamp(seconds),
+        };
+
+        assert_eq!(tracker.apply(activity(0)).len(), 1);
+        assert!(tracker.apply(activity(30)).is_empty());
+        assert_eq!(tracker.open_sessions(), 1);
+    }
+
+    #[test]
+    fn test_logout_without_session_is_ignored() {
+        let mut tracker = SessionTracker::default();
+
+        let changes = tracker.apply(SessionEvent::Logout {
+            character_id: CHARACTER_ID,
+            timestamp: timestamp(0),
+        });
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn test_outfit_is_recorded_once() {
+        let mut tracker = SessionTracker::default();
+        let outfit = SessionEvent::Outfit {
+            character_id: CHARACTER_ID,
+            outfit_id: 37_570_391_403_474_491,
+        };
+
+        assert!(tracker.apply(outfit).is_empty());
+
+        tracker.apply(SessionEvent::Login {
+            character_id: CHARACTER_ID,
+            world_id: WorldID::Miller,
+            timestamp: timestamp(0),
+        });
+        assert_eq!(
+            tracker.apply(outfit),
+            vec![SessionChange::SetOutfit {
+                character_id: CHARACTER_ID,
+                outfit_id: 37_570_391_403_474_491,
+            }]
+        );
+        assert!(tracker.apply(outfit).is_empty());
+    }
+
+    #[test]
+    fn test_close_idle_uses_last_activity() {
+        let mut tracker = SessionTracker::default();
+        tracker.apply(SessionEvent::Login {
+            character_id: CHARACTER_ID,
+            world_id: WorldID::Miller,
+            timestamp: timestamp(0),
+        });
+        tracker.apply(SessionEvent::Activity {
+            character_id: CHARACTER_ID,
+            world_id: WorldID::Miller,
+            timestamp: timestamp(120),
+        });
+
+        assert!(tracker.close_idle(timestamp(600)).is_empty());
+        assert_eq!(
+            tracker.close_idle(timestamp(120 + SESSION_TIMEOUT_MINUTES * 60)),
+            vec![SessionChange::Close {
+                character_id: CHARACTER_ID,
+                session_end: timestamp(120),
+            }]
+        );
+        assert_eq!(tracker.open_sessions(), 0);
+    }
+}
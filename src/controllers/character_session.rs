@@ -0,0 +1,191 @@
+use crate::census::constants::{CharacterID, OutfitID, WorldID};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// Open a new session for a character
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `character_id` - The character that started playing
+/// * `world_id` - The world the character is playing on
+/// * `session_start` - When the session started
+///
+/// # Returns
+///
+/// * `Ok(())` - The session was opened
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+#[allow(clippy::cast_possible_wrap)]
+pub async fn open(
+    db_pool: &PgPool,
+    character_id: CharacterID,
+    world_id: WorldID,
+    session_start: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO \"character\" (character_id) VALUES ($1) ON CONFLICT DO NOTHING",
+        character_id as i64
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO world (world_id) VALUES ($1) ON CONFLICT DO NOTHING",
+        world_id as i32
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO character_session (character_id, world_id, session_start)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (character_id, session_start) DO NOTHING",
+        character_id as i64,
+        world_id as i32,
+        session_start.naive_utc()
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await
+}
+
+/// Close the open session of a character
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `character_id` - The character that stopped playing
+/// * `session_end` - When the session ended
+///
+/// # Returns
+///
+/// * `Ok(())` - The session was closed or no session was open
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+#[allow(clippy::cast_possible_wrap)]
+pub async fn close(
+    db_pool: &PgPool,
+    character_id: CharacterID,
+    session_end: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE character_session
+        SET session_end = GREATEST(session_start, $2)
+        WHERE character_id = $1 AND session_end IS NULL",
+        character_id as i64,
+        session_end.naive_utc()
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record the outfit of a character in their open session
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `character_id` - The character to update
+/// * `outfit_id` - The outfit the character is a member of
+///
+/// # Returns
+///
+/// * `Ok(())` - The outfit was recorded or no session was open
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+#[allow(clippy::cast_possible_wrap)]
+pub async fn set_outfit(
+    db_pool: &PgPool,
+    character_id: CharacterID,
+    outfit_id: OutfitID,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO outfit (outfit_id) VALUES ($1) ON CONFLICT DO NOTHING",
+        outfit_id as i64
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE character_session
+        SET outfit_id = $2
+        WHERE character_id = $1 AND session_end IS NULL",
+        character_id as i64,
+        outfit_id as i64
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await
+}
+
+/// Close sessions that were left open when the application stopped unexpectedly.
+/// The last population snapshot is used as the best guess of when the application stopped.
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+///
+/// # Returns
+///
+/// * `Ok(u64)` - The amount of sessions that were closed
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn close_dangling(db_pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE character_session
+        SET session_end = GREATEST(
+            session_start,
+            COALESCE((SELECT MAX(p.timestamp) FROM population p), session_start)
+        )
+        WHERE session_end IS NULL"
+    )
+    .execute(db_pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Get the total time a character played within a time range
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `character_id` - The character to get the play time for
+/// * `from` - The start of the time range
+/// * `to` - The end of the time range
+///
+/// # Returns
+///
+/// * `Ok(chrono::Duration)` - The total play time, open sessions count up to `to`
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+#[allow(clippy::cast_possible_wrap)]
+pub async fn get_play_time(
+    db_pool: &PgPool,
+    character_id: CharacterID,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<chrono::Duration, sqlx::Error> {
+    let play_time = sqlx::query!(
+        "SELECT COALESCE(
+            EXTRACT(EPOCH FROM SUM(
+                LEAST(COALESCE(session_end, $3), $3) - GREATEST(session_start, $2)
+            ))::BIGINT,
+            0
+        ) AS seconds
+        FROM character_session
+        WHERE character_id = $1
+            AND session_start < $3
+            AND (session_end IS NULL OR session_end > $2)",
+        character_id as i64,
+        from.naive_utc(),
+        to.naive_utc()
+    )
+    .fetch_one(db_pool)
+    .await?;
+
+    Ok(chrono::Duration::seconds(play_time.seconds.unwrap_or(0)))
+}
@@ -0,0 +1,199 @@
+use crate::census::constants::{DefinitionID, FacilityID, Faction, OutfitID, WorldID};
+use crate::census::rest::map::ZoneMap;
+use crate::metagame::FacilityChange;
+use crate::serde::naivedatetime;
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use sqlx::postgres::types::PgInterval;
+use sqlx::PgPool;
+use tracing::error;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct FacilityCapture {
+    pub world_id: WorldID,
+    pub zone_id: DefinitionID,
+    pub facility_id: FacilityID,
+    /// `None` when the facility wasn't seen on the map yet
+    pub facility_name: Option<String>,
+    pub old_faction: Faction,
+    pub new_faction: Faction,
+    pub outfit_id: Option<OutfitID>,
+    /// `None` when the name of the outfit wasn't fetched yet
+    pub outfit_name: Option<String>,
+    #[serde(with = "naivedatetime")]
+    pub timestamp: NaiveDateTime,
+}
+
+/// Record a facility being captured or defended
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `change` - The capture or defense of the facility
+///
+/// # Returns
+///
+/// * `Ok(())` - The change was recorded
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn record_capture(db_pool: &PgPool, change: &FacilityChange) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO world (world_id) VALUES ($1) ON CONFLICT DO NOTHING",
+        change.world_id as i32
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO zone (zone_id) VALUES ($1) ON CONFLICT DO NOTHING",
+        i32::from(change.zone_id.0)
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    #[allow(clippy::cast_possible_wrap)]
+    let outfit_id = change.outfit_id.map(|outfit_id| outfit_id as i64);
+
+    if let Some(outfit_id) = outfit_id {
+        sqlx::query!(
+            "INSERT INTO outfit (outfit_id) VALUES ($1) ON CONFLICT DO NOTHING",
+            outfit_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    let duration_held = PgInterval::try_from(change.duration_held).map_err(sqlx::Error::Encode)?;
+
+    sqlx::query!(
+        "INSERT INTO facility_capture
+        (world_id, zone_id, zone_instance_id, facility_id, old_faction, new_faction, outfit_id, duration_held, timestamp)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        change.world_id as i32,
+        i32::from(change.zone_id.0),
+        i32::from(change.zone_instance_id.0),
+        i64::from(change.facility_id),
+        change.old_faction as i16,
+        change.new_faction as i16,
+        outfit_id,
+        duration_held,
+        change.timestamp.naive_utc()
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await
+}
+
+/// Store the names and zones of the facilities on a map
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `zone_map` - The map of the zone the facilities are on
+///
+/// # Returns
+///
+/// * `Ok(())` - The facilities were stored
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn upsert_facilities(db_pool: &PgPool, zone_map: &ZoneMap) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO zone (zone_id) VALUES ($1) ON CONFLICT DO NOTHING",
+        i32::from(zone_map.zone_id.0)
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    let now = Utc::now().naive_utc();
+    for facility in &zone_map.facilities {
+        #[allow(clippy::cast_possible_wrap)]
+        sqlx::query!(
+            "INSERT INTO facility (facility_id, zone_id, name, facility_type_id, last_update)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (facility_id) DO UPDATE SET
+                zone_id = EXCLUDED.zone_id,
+                name = EXCLUDED.name,
+                facility_type_id = EXCLUDED.facility_type_id,
+                last_update = EXCLUDED.last_update",
+            i64::from(facility.facility_id),
+            i32::from(zone_map.zone_id.0),
+            facility.name,
+            facility.facility_type_id as i16,
+            now
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    transaction.commit().await
+}
+
+/// Get the facilities that were captured most recently, leaving out facilities being defended
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `worlds` - The world IDs to check
+/// * `limit` - The maximum number of captures to return
+///
+/// # Returns
+///
+/// * `Ok(Vec<FacilityCapture>)` - The captures, newest first
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn get_recent_captures(
+    db_pool: &PgPool,
+    worlds: Option<&[i32]>,
+    limit: i64,
+) -> Result<Vec<FacilityCapture>, sqlx::Error> {
+    let captures = sqlx::query!(
+        "SELECT fc.world_id, fc.zone_id, fc.facility_id, f.name AS \"facility_name?\", fc.old_faction, fc.new_faction, fc.outfit_id, o.name AS \"outfit_name?\", fc.timestamp
+        FROM facility_capture fc
+        LEFT JOIN facility f ON f.facility_id = fc.facility_id
+        LEFT JOIN outfit o ON o.outfit_id = fc.outfit_id
+        WHERE fc.old_faction <> fc.new_faction
+            AND ($1::INTEGER[] IS NULL OR fc.world_id = ANY($1::INTEGER[]))
+        ORDER BY fc.timestamp DESC
+        LIMIT $2",
+        worlds,
+        limit
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(captures
+        .into_iter()
+        .filter_map(|record| {
+            #[allow(clippy::cast_possible_truncation)]
+            #[allow(clippy::cast_sign_loss)]
+            let Ok(world_id) = WorldID::try_from(record.world_id as u16) else {
+                error!(
+                    "Invalid world ID is not defined in auraxis-rs: {}",
+                    record.world_id
+                );
+                return None;
+            };
+
+            #[allow(clippy::cast_sign_loss)]
+            let faction =
+                |faction: i16| Faction::try_from(faction as u16).unwrap_or(Faction::Unknown);
+
+            #[allow(clippy::cast_possible_truncation)]
+            #[allow(clippy::cast_sign_loss)]
+            Some(FacilityCapture {
+                world_id,
+                zone_id: DefinitionID(record.zone_id as u16),
+                facility_id: record.facility_id as FacilityID,
+                facility_name: record.facility_name,
+                old_faction: faction(record.old_faction),
+                new_faction: faction(record.new_faction),
+                outfit_id: record.outfit_id.map(|outfit_id| outfit_id as OutfitID),
+                outfit_name: record.outfit_name,
+                timestamp: record.timestamp,
+            })
+        })
+        .collect())
+}
@@ -0,0 +1,263 @@
+use crate::census::constants::{DefinitionID, Faction, WorldID};
+use crate::kill_stats::KillCounts;
+use crate::storage::configuration::PopulationLayout;
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::error;
+use utoipa::ToSchema;
+
+/// How well a faction is doing in the fight on a zone, over the window of the latest population
+#[derive(Serialize, ToSchema, Clone, Copy, Debug, PartialEq)]
+pub struct FactionKills {
+    pub world_id: WorldID,
+    pub zone_id: DefinitionID,
+    pub faction: Faction,
+    pub kills: u32,
+    pub deaths: u32,
+    pub headshots: u32,
+    /// The active players of the faction on the zone, as counted in the population
+    pub players: u32,
+    /// Kills per minute per active player, 0 when there are no active players
+    pub kpm: f32,
+    /// Kills per death, the number of kills when there were no deaths
+    pub kd: f32,
+    /// The share of kills that were headshots, 0 when there were no kills
+    pub headshot_ratio: f32,
+}
+
+impl FactionKills {
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(
+        world_id: WorldID,
+        zone_id: DefinitionID,
+        faction: Faction,
+        counts: KillCounts,
+        players: u32,
+        window_seconds: u32,
+    ) -> Self {
+        let ratio = |a: u32, b: u32| if b == 0 { 0.0 } else { a as f32 / b as f32 };
+        let minutes = window_seconds as f32 / 60.0;
+
+        Self {
+            world_id,
+            zone_id,
+            faction,
+            kills: counts.kills,
+            deaths: counts.deaths,
+            headshots: counts.headshots,
+            players,
+            kpm: if players == 0 || window_seconds == 0 {
+                0.0
+            } else {
+                counts.kills as f32 / minutes / players as f32
+            },
+            kd: ratio(counts.kills, counts.deaths.max(1)),
+            headshot_ratio: ratio(counts.headshots, counts.kills),
+        }
+    }
+}
+
+/// Get the kills and deaths of each faction per zone, stored with the latest population
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `population_layout` - Whether to count the players in the normalized levels or in
+///   `population_snapshot`
+/// * `worlds` - The world IDs to check
+///
+/// # Returns
+///
+/// * `Ok(Vec<FactionKills>)` - The kill statistics, ordered by world, zone and faction
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn get_current_kill_stats(
+    db_pool: &PgPool,
+    population_layout: PopulationLayout,
+    worlds: Option<&[i32]>,
+) -> Result<Vec<FactionKills>, sqlx::Error> {
+    let kill_stats = sqlx::query!(
+        "WITH latest AS (
+            SELECT
+                w.world_id,
+                (
+                    SELECT MAX(ks.population_id)
+                    FROM kill_stats ks
+                    WHERE ks.world_id = w.world_id
+                ) AS population_id
+            FROM world w
+            WHERE $1::INTEGER[] IS NULL OR w.world_id = ANY($1::INTEGER[])
+        ),
+        players AS (
+            SELECT ps.world_id, ps.zone_id, ps.team_id, SUM(ps.amount) AS players
+            FROM latest l
+            CROSS JOIN LATERAL (
+                SELECT * FROM population_snapshot s
+                WHERE $2 AND s.world_id = l.world_id AND s.population_id = l.population_id
+                UNION ALL
+                SELECT * FROM population_loadout pl
+                WHERE NOT $2 AND pl.world_id = l.world_id AND pl.population_id = l.population_id
+            ) ps
+            GROUP BY ps.world_id, ps.zone_id, ps.team_id
+        )
+        SELECT
+            ks.world_id,
+            ks.zone_id,
+            ks.faction_id,
+            SUM(ks.kills)::INTEGER AS \"kills!\",
+            SUM(ks.deaths)::INTEGER AS \"deaths!\",
+            SUM(ks.headshots)::INTEGER AS \"headshots!\",
+            MAX(ks.window_seconds) AS \"window_seconds!\",
+            COALESCE(MAX(p.players), 0)::INTEGER AS \"players!\"
+        FROM latest l
+        JOIN kill_stats ks ON ks.world_id = l.world_id AND ks.population_id = l.population_id
+        LEFT JOIN players p ON p.world_id = ks.world_id AND p.zone_id = ks.zone_id AND p.team_id = ks.faction_id
+        GROUP BY ks.world_id, ks.zone_id, ks.faction_id
+        ORDER BY ks.world_id, ks.zone_id, ks.faction_id",
+        worlds,
+        population_layout.is_flat()
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(kill_stats
+        .into_iter()
+        .filter_map(|record| {
+            #[allow(clippy::cast_possible_truncation)]
+            #[allow(clippy::cast_sign_loss)]
+            let Ok(world_id) = WorldID::try_from(record.world_id as u16) else {
+                error!(
+                    "Invalid world ID is not defined in auraxis-rs: {}",
+                    record.world_id
+                );
+                return None;
+            };
+
+            #[allow(clippy::cast_sign_loss)]
+            let faction = Faction::try_from(record.faction_id as u16).unwrap_or(Faction::Unknown);
+
+            #[allow(clippy::cast_possible_truncation)]
+            #[allow(clippy::cast_sign_loss)]
+            Some(FactionKills::new(
+                world_id,
+                DefinitionID(record.zone_id as u16),
+                faction,
+                KillCounts {
+                    kills: record.kills as u32,
+                    deaths: record.deaths as u32,
+                    headshots: record.headshots as u32,
+                },
+                record.players as u32,
+                record.window_seconds as u32,
+            ))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::snapshot::store_pop;
+    use crate::census::constants::{InstanceID, Loadout};
+    use crate::controllers::population::WorldBreakdown;
+    use std::collections::HashMap;
+
+    /// Store a snapshot of a world with VS players on Indar, and the kills counted with it
+    async fn store_window(db_pool: &PgPool, world_id: WorldID, players: u16, kills: i32) {
+        let teams = HashMap::from([(Faction::VS, HashMap::from([(Loadout::VSMAX, players)]))]);
+        let instances = HashMap::from([(InstanceID(0), teams)]);
+        let loadout_breakdown: WorldBreakdown =
+            HashMap::from([(world_id, HashMap::from([(DefinitionID(2), instances)]))]);
+        let population_id = store_pop(
+            &loadout_breakdown,
+            &HashMap::new(),
+            &HashMap::new(),
+            PopulationLayout::Flat,
+            db_pool,
+        )
+        .await
+        .unwrap();
+
+        sqlx::query(
+            "INSERT INTO kill_stats
+            (population_id, world_id, zone_id, faction_id, loadout_id, kills, deaths, headshots, window_seconds)
+            VALUES ($1, $2, 2, $3, $4, $5, 0, 0, 30)",
+        )
+        .bind(population_id)
+        .bind(world_id as i32)
+        .bind(Faction::VS as i16)
+        .bind(Loadout::VSMAX as i16)
+        .bind(kills)
+        .execute(db_pool)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_current_kill_stats_of_each_world(db_pool: PgPool) {
+        store_window(&db_pool, WorldID::Miller, 3, 1).await;
+        store_window(&db_pool, WorldID::Cobalt, 4, 2).await;
+        store_window(&db_pool, WorldID::Miller, 5, 3).await;
+
+        for population_layout in [PopulationLayout::Normalized, PopulationLayout::Flat] {
+            let kill_stats = get_current_kill_stats(&db_pool, population_layout, None)
+                .await
+                .unwrap();
+            let worlds: Vec<(WorldID, u32, u32)> = kill_stats
+                .iter()
+                .map(|stats| (stats.world_id, stats.kills, stats.players))
+                .collect();
+            assert_eq!(
+                worlds,
+                vec![(WorldID::Miller, 3, 5), (WorldID::Cobalt, 2, 4)]
+            );
+
+            let cobalt = [WorldID::Cobalt as i32];
+            let kill_stats = get_current_kill_stats(&db_pool, population_layout, Some(&cobalt))
+                .await
+                .unwrap();
+            assert_eq!(kill_stats.len(), 1);
+            assert_eq!(kill_stats[0].kills, 2);
+        }
+    }
+
+    #[test]
+    fn test_faction_kills() {
+        let faction_kills = FactionKills::new(
+            WorldID::Miller,
+            DefinitionID(2),
+            Faction::NC,
+            KillCounts {
+                kills: 60,
+                deaths: 40,
+                headshots: 15,
+            },
+            20,
+            30,
+        );
+
+        assert!((faction_kills.kpm - 6.0).abs() < f32::EPSILON);
+        assert!((faction_kills.kd - 1.5).abs() < f32::EPSILON);
+        assert!((faction_kills.headshot_ratio - 0.25).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_faction_kills_without_players_or_deaths() {
+        let faction_kills = FactionKills::new(
+            WorldID::Miller,
+            DefinitionID(2),
+            Faction::NC,
+            KillCounts {
+                kills: 3,
+                deaths: 0,
+                headshots: 0,
+            },
+            0,
+            30,
+        );
+
+        assert!(faction_kills.kpm.abs() < f32::EPSILON);
+        assert!((faction_kills.kd - 3.0).abs() < f32::EPSILON);
+        assert!(faction_kills.headshot_ratio.abs() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::census::constants::{DefinitionID, Faction, InstanceID, MetagameEventState, WorldID};
+use crate::metagame::{AlertChange, ContinentChange};
+use crate::serde::naivedatetime;
+use chrono::{NaiveDateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::error;
+use utoipa::ToSchema;
+
+/// Alerts without an end for longer than this are assumed to have ended while we weren't listening
+const ALERT_TIMEOUT_MINUTES: i64 = 120;
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct Alert {
+    pub world_id: WorldID,
+    pub zone_id: DefinitionID,
+    pub zone_instance_id: InstanceID,
+    /// The ID Census gives the alert, unique per world
+    pub instance_id: u32,
+    pub metagame_event_id: u8,
+    pub experience_bonus: f32,
+    /// Territory control of each faction in percent, as of the last update of the alert
+    pub faction_vs: f32,
+    pub faction_nc: f32,
+    pub faction_tr: f32,
+    /// `None` when the alert started before it was being tracked
+    #[serde(serialize_with = "naivedatetime::serialize_option")]
+    pub started_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct ContinentStatus {
+    pub world_id: WorldID,
+    pub zone_id: DefinitionID,
+    pub locked: bool,
+    /// The faction that locked or unlocked the continent
+    pub triggering_faction: Faction,
+    #[serde(with = "naivedatetime")]
+    pub since: NaiveDateTime,
+}
+
+/// Record a continent being locked or unlocked
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `change` - The lock or unlock of the continent
+///
+/// # Returns
+///
+/// * `Ok(())` - The change was recorded
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn record_continent(
+    db_pool: &PgPool,
+    change: &ContinentChange,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO world (world_id) VALUES ($1) ON CONFLICT DO NOTHING",
+        change.world_id as i32
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO zone (zone_id) VALUES ($1) ON CONFLICT DO NOTHING",
+        i32::from(change.zone_id.0)
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    #[allow(clippy::cast_possible_wrap)]
+    sqlx::query!(
+        "INSERT INTO continent_lock
+        (world_id, zone_id, locked, triggering_faction, previous_faction, vs_population, nc_population, tr_population, metagame_event_id, timestamp)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+        change.world_id as i32,
+        i32::from(change.zone_id.0),
+        change.locked,
+        change.triggering_faction as i16,
+        change.previous_faction as i16,
+        change.vs_population as i16,
+        change.nc_population as i16,
+        change.tr_population as i16,
+        i16::from(change.metagame_event_id),
+        change.timestamp.naive_utc()
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await
+}
+
+/// Record an alert starting, ending or changing its experience bonus. Alerts that were already
+/// running when tracking started are recorded without a start.
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `change` - The change of the alert
+///
+/// # Returns
+///
+/// * `Ok(())` - The change was recorded
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn record_alert(db_pool: &PgPool, change: &AlertChange) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    sqlx::query!(
+        "INSERT INTO world (world_id) VALUES ($1) ON CONFLICT DO NOTHING",
+        change.world_id as i32
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO zone (zone_id) VALUES ($1) ON CONFLICT DO NOTHING",
+        i32::from(change.zone_id.0)
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    let timestamp = change.timestamp.naive_utc();
+    let (started_at, ended_at) = match change.state {
+        MetagameEventState::Started | MetagameEventState::Restarted => (Some(timestamp), None),
+        MetagameEventState::Canceled | MetagameEventState::Ended => (None, Some(timestamp)),
+        MetagameEventState::ExperienceBonusChanged => (None, None),
+    };
+
+    sqlx::query!(
+        "INSERT INTO alert
+        (world_id, instance_id, zone_id, zone_instance_id, metagame_event_id, state, experience_bonus, faction_vs, faction_nc, faction_tr, started_at, ended_at, last_update)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        ON CONFLICT (world_id, instance_id) DO UPDATE SET
+            state = EXCLUDED.state,
+            experience_bonus = EXCLUDED.experience_bonus,
+            faction_vs = EXCLUDED.faction_vs,
+            faction_nc = EXCLUDED.faction_nc,
+            faction_tr = EXCLUDED.faction_tr,
+            started_at = COALESCE(EXCLUDED.started_at, alert.started_at),
+            ended_at = EXCLUDED.ended_at,
+            last_update = EXCLUDED.last_update",
+        change.world_id as i32,
+        i64::from(change.instance_id),
+        i32::from(change.zone_id.0),
+        i32::from(change.zone_instance_id.0),
+        i16::from(change.metagame_event_id),
+        i16::from(u8::from(change.state)),
+        change.experience_bonus,
+        change.faction_vs,
+        change.faction_nc,
+        change.faction_tr,
+        started_at,
+        ended_at,
+        timestamp
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await
+}
+
+/// Get the alerts that are currently running
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `worlds` - The world IDs to check
+///
+/// # Returns
+///
+/// * `Ok(Vec<Alert>)` - The running alerts, oldest first
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn get_current_alerts(
+    db_pool: &PgPool,
+    worlds: Option<&[i32]>,
+) -> Result<Vec<Alert>, sqlx::Error> {
+    let cutoff = (Utc::now() - chrono::Duration::minutes(ALERT_TIMEOUT_MINUTES)).naive_utc();
+
+    let alerts = sqlx::query!(
+        "SELECT world_id, zone_id, zone_instance_id, instance_id, metagame_event_id, experience_bonus, faction_vs, faction_nc, faction_tr, started_at
+        FROM alert
+        WHERE ended_at IS NULL
+            AND last_update > $2
+            AND ($1::INTEGER[] IS NULL OR world_id = ANY($1::INTEGER[]))
+        ORDER BY started_at NULLS FIRST",
+        worlds,
+        cutoff
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(alerts
+        .into_iter()
+        .filter_map(|record| {
+            #[allow(clippy::cast_possible_truncation)]
+            #[allow(clippy::cast_sign_loss)]
+            let Ok(world_id) = WorldID::try_from(record.world_id as u16) else {
+                error!(
+                    "Invalid world ID is not defined in auraxis-rs: {}",
+                    record.world_id
+                );
+                return None;
+            };
+
+            #[allow(clippy::cast_possible_truncation)]
+            #[allow(clippy::cast_sign_loss)]
+            Some(Alert {
+                world_id,
+                zone_id: DefinitionID(record.zone_id as u16),
+                zone_instance_id: InstanceID(record.zone_instance_id as u16),
+                instance_id: record.instance_id as u32,
+                metagame_event_id: record.metagame_event_id as u8,
+                experience_bonus: record.experience_bonus,
+                faction_vs: record.faction_vs,
+                faction_nc: record.faction_nc,
+                faction_tr: record.faction_tr,
+                started_at: record.started_at,
+            })
+        })
+        .collect())
+}
+
+/// Get whether each continent is locked or unlocked, based on the last lock or unlock event
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `worlds` - The world IDs to check
+///
+/// # Returns
+///
+/// * `Ok(Vec<ContinentStatus>)` - The status of every continent that was seen locking or unlocking
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn get_continent_status(
+    db_pool: &PgPool,
+    worlds: Option<&[i32]>,
+) -> Result<Vec<ContinentStatus>, sqlx::Error> {
+    let continents = sqlx::query!(
+        "SELECT DISTINCT ON (world_id, zone_id) world_id, zone_id, locked, triggering_faction, timestamp
+        FROM continent_lock
+        WHERE $1::INTEGER[] IS NULL OR world_id = ANY($1::INTEGER[])
+        ORDER BY world_id, zone_id, timestamp DESC",
+        worlds
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(continents
+        .into_iter()
+        .filter_map(|record| {
+            #[allow(clippy::cast_possible_truncation)]
+            #[allow(clippy::cast_sign_loss)]
+            let Ok(world_id) = WorldID::try_from(record.world_id as u16) else {
+                error!(
+                    "Invalid world ID is not defined in auraxis-rs: {}",
+                    record.world_id
+                );
+                return None;
+            };
+
+            #[allow(clippy::cast_sign_loss)]
+            let triggering_faction =
+                Faction::try_from(record.triggering_faction as u16).unwrap_or(Faction::Unknown);
+
+            #[allow(clippy::cast_possible_truncation)]
+            #[allow(clippy::cast_sign_loss)]
+            Some(ContinentStatus {
+                world_id,
+                zone_id: DefinitionID(record.zone_id as u16),
+                locked: record.locked,
+                triggering_faction,
+                since: record.timestamp,
+            })
+        })
+        .collect())
+}
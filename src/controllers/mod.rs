@@ -2,8 +2,14 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 pub mod character;
+pub mod character_session;
+pub mod facility;
 pub mod faction;
+pub mod kill_stats;
+pub mod metagame;
+pub mod outfit;
 pub mod population;
+pub mod population_history;
 pub mod user;
 pub mod world;
 pub mod zone;
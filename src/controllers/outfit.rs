@@ -0,0 +1,328 @@
+use crate::census::constants::{CharacterID, DefinitionID, OutfitID, WorldID};
+use crate::census::rest::outfit::Outfit;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::error;
+use utoipa::ToSchema;
+
+/// The outfit of a character as stored in the database, `None` when they aren't in an outfit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoredMembership {
+    pub character_id: CharacterID,
+    pub outfit_id: Option<OutfitID>,
+    pub last_update: NaiveDateTime,
+}
+
+/// The online members of an outfit on a zone in the latest population
+#[derive(Serialize, ToSchema, Clone, Debug, PartialEq, Eq)]
+pub struct PopOutfit {
+    pub world_id: WorldID,
+    pub zone_id: DefinitionID,
+    pub outfit_id: OutfitID,
+    /// `None` when the outfit wasn't fetched yet
+    pub name: Option<String>,
+    pub alias: Option<String>,
+    pub outfit_population: u32,
+}
+
+/// Get the outfits of characters that were fetched after `fetched_after`
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `character_ids` - The characters to look up
+/// * `fetched_after` - Memberships fetched before this are considered outdated
+///
+/// # Returns
+///
+/// * `Ok(Vec<StoredMembership>)` - The memberships that are still up to date
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+#[allow(clippy::cast_possible_wrap)]
+pub async fn get_memberships(
+    db_pool: &PgPool,
+    character_ids: &[CharacterID],
+    fetched_after: NaiveDateTime,
+) -> Result<Vec<StoredMembership>, sqlx::Error> {
+    let character_ids: Vec<i64> = character_ids.iter().map(|id| *id as i64).collect();
+
+    let memberships = sqlx::query!(
+        "SELECT character_id, outfit_id, last_update AS \"last_update!\"
+        FROM \"character\"
+        WHERE character_id = ANY($1::BIGINT[]) AND last_update > $2",
+        &character_ids,
+        fetched_after
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    #[allow(clippy::cast_sign_loss)]
+    Ok(memberships
+        .into_iter()
+        .map(|record| StoredMembership {
+            character_id: record.character_id as CharacterID,
+            outfit_id: record.outfit_id.map(|outfit_id| outfit_id as OutfitID),
+            last_update: record.last_update,
+        })
+        .collect())
+}
+
+/// Store the outfits of characters, including the characters that aren't in an outfit
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `memberships` - The characters and their outfit
+/// * `fetched` - When the memberships were fetched from Census
+///
+/// # Returns
+///
+/// * `Ok(())` - The memberships were stored
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+#[allow(clippy::cast_possible_wrap)]
+pub async fn store_memberships(
+    db_pool: &PgPool,
+    memberships: &[(CharacterID, Option<OutfitID>)],
+    fetched: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    for (character_id, outfit_id) in memberships {
+        let outfit_id = outfit_id.map(|outfit_id| outfit_id as i64);
+
+        if let Some(outfit_id) = outfit_id {
+            sqlx::query!(
+                "INSERT INTO outfit (outfit_id) VALUES ($1) ON CONFLICT DO NOTHING",
+                outfit_id
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        sqlx::query!(
+            "INSERT INTO \"character\" (character_id, outfit_id, last_update)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (character_id) DO UPDATE SET
+                outfit_id = EXCLUDED.outfit_id,
+                last_update = EXCLUDED.last_update",
+            *character_id as i64,
+            outfit_id,
+            fetched
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    transaction.commit().await
+}
+
+/// Get the outfits that were never fetched or were last fetched before `fetched_after`
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `outfit_ids` - The outfits to check
+/// * `fetched_after` - Outfits fetched before this are considered outdated
+///
+/// # Returns
+///
+/// * `Ok(Vec<OutfitID>)` - The outfits that have to be fetched
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+#[allow(clippy::cast_possible_wrap)]
+pub async fn get_outdated_outfits(
+    db_pool: &PgPool,
+    outfit_ids: &[OutfitID],
+    fetched_after: NaiveDateTime,
+) -> Result<Vec<OutfitID>, sqlx::Error> {
+    let outfit_ids: Vec<i64> = outfit_ids.iter().map(|id| *id as i64).collect();
+
+    let outdated = sqlx::query!(
+        "SELECT ids.outfit_id AS \"outfit_id!\"
+        FROM UNNEST($1::BIGINT[]) AS ids(outfit_id)
+        WHERE NOT EXISTS (
+            SELECT 1 FROM outfit o
+            WHERE o.outfit_id = ids.outfit_id AND o.last_fetch > $2
+        )",
+        &outfit_ids,
+        fetched_after
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    #[allow(clippy::cast_sign_loss)]
+    Ok(outdated
+        .into_iter()
+        .map(|record| record.outfit_id as OutfitID)
+        .collect())
+}
+
+/// Store the names and aliases of outfits
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `outfits` - The outfits as returned by Census
+/// * `fetched` - When the outfits were fetched from Census
+///
+/// # Returns
+///
+/// * `Ok(())` - The outfits were stored
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+#[allow(clippy::cast_possible_wrap)]
+pub async fn upsert_outfits(
+    db_pool: &PgPool,
+    outfits: &[Outfit],
+    fetched: NaiveDateTime,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    for outfit in outfits {
+        sqlx::query!(
+            "INSERT INTO outfit (outfit_id, name, alias, last_fetch)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (outfit_id) DO UPDATE SET
+                name = EXCLUDED.name,
+                alias = EXCLUDED.alias,
+                last_fetch = EXCLUDED.last_fetch",
+            outfit.id as i64,
+            outfit.name,
+            (!outfit.alias.is_empty()).then_some(&outfit.alias),
+            fetched
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    transaction.commit().await
+}
+
+/// Get the online members of each outfit per zone from the latest population
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `worlds` - The world IDs to check
+/// * `zones` - The zone definition IDs to check
+/// * `outfits` - The outfit IDs to check
+///
+/// # Returns
+///
+/// * `Ok(Vec<PopOutfit>)` - The outfits ordered by world and zone, largest outfit first
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn get_current_outfits(
+    db_pool: &PgPool,
+    worlds: Option<&[i32]>,
+    zones: Option<&[i32]>,
+    outfits: Option<&[i64]>,
+) -> Result<Vec<PopOutfit>, sqlx::Error> {
+    let outfits = sqlx::query!(
+        "WITH latest AS (
+            SELECT
+                w.world_id,
+                (
+                    SELECT MAX(wp.population_id)
+                    FROM world_population wp
+                    WHERE wp.world_id = w.world_id
+                ) AS population_id
+            FROM world w
+            WHERE $1::INTEGER[] IS NULL OR w.world_id = ANY($1::INTEGER[])
+        )
+        SELECT wp.world_id, op.zone_id, op.outfit_id, o.name AS \"name?\", o.alias AS \"alias?\", op.amount
+        FROM latest l
+        JOIN world_population wp ON wp.world_id = l.world_id
+            AND wp.population_id = l.population_id
+        JOIN outfit_population op ON op.world_population_id = wp.world_population_id
+        LEFT JOIN outfit o ON o.outfit_id = op.outfit_id
+        WHERE ($2::INTEGER[] IS NULL OR op.zone_id = ANY($2::INTEGER[]))
+            AND ($3::BIGINT[] IS NULL OR op.outfit_id = ANY($3::BIGINT[]))
+        ORDER BY wp.world_id, op.zone_id, op.amount DESC, op.outfit_id",
+        worlds,
+        zones,
+        outfits
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    Ok(outfits
+        .into_iter()
+        .filter_map(|record| {
+            #[allow(clippy::cast_possible_truncation)]
+            #[allow(clippy::cast_sign_loss)]
+            let Ok(world_id) = WorldID::try_from(record.world_id as u16) else {
+                error!(
+                    "Invalid world ID is not defined in auraxis-rs: {}",
+                    record.world_id
+                );
+                return None;
+            };
+
+            #[allow(clippy::cast_possible_truncation)]
+            #[allow(clippy::cast_sign_loss)]
+            Some(PopOutfit {
+                world_id,
+                zone_id: DefinitionID(record.zone_id as u16),
+                outfit_id: record.outfit_id as OutfitID,
+                name: record.name,
+                alias: record.alias,
+                outfit_population: record.amount as u32,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::snapshot::store_pop;
+    use crate::census::constants::{Faction, InstanceID, Loadout};
+    use crate::controllers::population::{WorldBreakdown, WorldOutfitBreakdown};
+    use crate::storage::configuration::PopulationLayout;
+    use std::collections::HashMap;
+
+    const OUTFIT_ID: OutfitID = 37_509_488_620_604_883;
+
+    /// Store a snapshot of a world with the members of an outfit on Indar
+    async fn store_outfit_snapshot(db_pool: &PgPool, world_id: WorldID, amount: u16) {
+        let teams = HashMap::from([(Faction::VS, HashMap::from([(Loadout::VSMAX, amount)]))]);
+        let instances = HashMap::from([(InstanceID(0), teams)]);
+        let loadout_breakdown: WorldBreakdown =
+            HashMap::from([(world_id, HashMap::from([(DefinitionID(2), instances)]))]);
+        let outfits = HashMap::from([(OUTFIT_ID, amount)]);
+        let outfit_breakdown: WorldOutfitBreakdown =
+            HashMap::from([(world_id, HashMap::from([(DefinitionID(2), outfits)]))]);
+
+        store_pop(
+            &loadout_breakdown,
+            &HashMap::new(),
+            &outfit_breakdown,
+            PopulationLayout::Normalized,
+            db_pool,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_current_outfits_of_each_world(db_pool: PgPool) {
+        store_outfit_snapshot(&db_pool, WorldID::Miller, 3).await;
+        store_outfit_snapshot(&db_pool, WorldID::Cobalt, 4).await;
+        store_outfit_snapshot(&db_pool, WorldID::Miller, 5).await;
+
+        let outfits = get_current_outfits(&db_pool, None, None, None)
+            .await
+            .unwrap();
+        let amounts: Vec<(WorldID, u32)> = outfits
+            .iter()
+            .map(|outfit| (outfit.world_id, outfit.outfit_population))
+            .collect();
+        assert_eq!(amounts, vec![(WorldID::Miller, 5), (WorldID::Cobalt, 4)]);
+
+        let cobalt = [WorldID::Cobalt as i32];
+        let outfits = get_current_outfits(&db_pool, Some(&cobalt), None, None)
+            .await
+            .unwrap();
+        assert_eq!(outfits.len(), 1);
+        assert_eq!(outfits[0].outfit_population, 4);
+    }
+}
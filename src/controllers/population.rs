@@ -1,6 +1,10 @@
-use crate::census::constants::{Loadout, TeamID, WorldID, ZoneID};
+use crate::census::constants::{
+    DefinitionID, Environment, InstanceID, Loadout, OutfitID, TeamID, VehicleID, WorldID,
+};
+use crate::census::server_health::EventStream;
 use crate::controllers::zone::Zone;
 use crate::serde::naivedatetime;
+use crate::storage::configuration::PopulationLayout;
 use serde::Serialize;
 use sqlx::PgPool;
 use std::collections::HashMap;
@@ -13,12 +17,36 @@ pub type LoadoutBreakdown = HashMap<Loadout, PopulationAmount>;
 
 pub type TeamBreakdown = HashMap<TeamID, LoadoutBreakdown>;
 
-pub type ZoneBreakdown = HashMap<ZoneID, TeamBreakdown>;
+/// The population of each instance of a zone, continents that aren't instanced only have instance 0
+pub type InstanceBreakdown = HashMap<InstanceID, TeamBreakdown>;
+
+pub type ZoneBreakdown = HashMap<DefinitionID, InstanceBreakdown>;
 
 pub type WorldBreakdown = HashMap<WorldID, ZoneBreakdown>;
 
+/// The players in each vehicle, players on foot aren't included
+pub type VehicleBreakdown = HashMap<VehicleID, PopulationAmount>;
+
+pub type TeamVehicleBreakdown = HashMap<TeamID, VehicleBreakdown>;
+
+/// Vehicles are counted per zone definition, combining every instance of the zone
+pub type ZoneVehicleBreakdown = HashMap<DefinitionID, TeamVehicleBreakdown>;
+
+pub type WorldVehicleBreakdown = HashMap<WorldID, ZoneVehicleBreakdown>;
+
+/// The online members of each outfit, players without a known outfit aren't included
+pub type OutfitBreakdown = HashMap<OutfitID, PopulationAmount>;
+
+/// Outfits are counted per zone definition, combining every instance of the zone
+pub type ZoneOutfitBreakdown = HashMap<DefinitionID, OutfitBreakdown>;
+
+pub type WorldOutfitBreakdown = HashMap<WorldID, ZoneOutfitBreakdown>;
+
 pub struct PopBreakdown {
+    /// The newest snapshot of the worlds
     pub timestamp: chrono::NaiveDateTime,
+    /// The latest snapshot of each world, worlds that stopped updating have an older snapshot
+    pub world_timestamps: HashMap<WorldID, chrono::NaiveDateTime>,
     pub worlds: WorldBreakdown,
 }
 
@@ -27,20 +55,38 @@ pub struct PopulationApiResponse {
     #[serde(with = "naivedatetime")]
     pub timestamp: chrono::NaiveDateTime,
     pub worlds: Vec<PopWorld>,
+    /// Whether the Census event stream of each world is online. Population of worlds whose
+    /// stream is offline is unreliable.
+    pub event_streams: Vec<EventStream>,
 }
 
 #[derive(Serialize, ToSchema, Clone)]
 pub struct PopWorld {
     pub world_id: WorldID,
+    pub environment: Environment,
+    /// The snapshot the population of this world is from
+    #[serde(with = "naivedatetime")]
+    pub timestamp: chrono::NaiveDateTime,
     pub world_population: u16,
     pub zones: Vec<PopZone>,
 }
 
 #[derive(Serialize, ToSchema, Clone)]
 pub struct PopZone {
-    pub zone_id: ZoneID,
+    pub zone_id: DefinitionID,
     pub full_zone_data: Option<Zone>,
     pub zone_population: u16,
+    /// The population of all instances of the zone combined
+    pub teams: Vec<PopTeam>,
+    /// The population of each instance, only set for instanced zones such as Koltyr or Sanctuary
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instances: Option<Vec<PopInstance>>,
+}
+
+#[derive(Serialize, ToSchema, Clone)]
+pub struct PopInstance {
+    pub instance_id: InstanceID,
+    pub instance_population: u16,
     pub teams: Vec<PopTeam>,
 }
 
@@ -57,15 +103,43 @@ pub struct PopLoadout {
     pub loadout_population: u16,
 }
 
+#[derive(Serialize, ToSchema, Clone)]
+pub struct PopVehicleZone {
+    pub world_id: WorldID,
+    pub zone_id: DefinitionID,
+    pub vehicle_population: u16,
+    pub teams: Vec<PopVehicleTeam>,
+}
+
+#[derive(Serialize, ToSchema, Clone)]
+pub struct PopVehicleTeam {
+    pub team_id: TeamID,
+    pub team_population: u16,
+    pub vehicles: Vec<PopVehicle>,
+}
+
+#[derive(Serialize, ToSchema, Copy, Clone)]
+pub struct PopVehicle {
+    pub vehicle_id: VehicleID,
+    pub vehicle_population: u16,
+}
+
 // Get the current population from the database as a tree
 //
 // # Arguments
 //
 // * `db_pool` - The database pool to use
+// * `population_layout` - Whether to read the normalized levels or `population_snapshot`
 // * `worlds` - The world IDs to check
-// * `zones` - The zone IDs to check
+// * `zones` - The zone definition IDs to check, which include every instance of the zone
 // * `teams` - The team IDs to check
 // * `loadouts` - The loadout IDs to check
+// * `environments` - The environments to check, such as `ps2ps4eu`
+//
+// Every world is read from its own latest snapshot, so worlds that were last stored in different
+// snapshots are all included. The snapshot is joined laterally, so each half of the union looks
+// up the rows of a world by index, and Postgres only runs the half of the selected layout since
+// the condition on `$6` doesn't depend on the rows.
 //
 // # Returns
 //
@@ -73,39 +147,56 @@ pub struct PopLoadout {
 // * `Err(sqlx::Error)` - The error returned by sqlx
 pub async fn get_current(
     db_pool: &PgPool,
+    population_layout: PopulationLayout,
     worlds: Option<&[i32]>,
     zones: Option<&[i32]>,
     teams: Option<&[i16]>,
     loadouts: Option<&[i16]>,
+    environments: Option<&[String]>,
 ) -> Option<PopBreakdown> {
     let Ok(population) = sqlx::query!(
-        "SELECT
-            p.timestamp,
-            wp.world_id,
-            zp.zone_id,
-            tp.team_id,
-            lp.loadout_id,
-            lp.amount
-        FROM population p
-        JOIN world_population wp ON p.population_id = wp.population_id
-        JOIN zone_population zp ON wp.world_population_id = zp.world_population_id
-        JOIN team_population tp ON zp.zone_population_id = tp.zone_population_id
-        JOIN loadout_population lp ON tp.team_population_id = lp.team_population_id
-        WHERE p.population_id = (
-                SELECT MAX(wp2.population_id) FROM world_population wp2 WHERE wp2.world_id = ANY($1::INTEGER[])
-            )
-            AND ($1::INTEGER[] IS NULL OR wp.world_id = ANY($1::INTEGER[]))
-            AND ($2::INTEGER[] IS NULL OR zp.zone_id = ANY($2::INTEGER[]))
-            AND ($3::SMALLINT[] IS NULL OR tp.team_id = ANY($3::SMALLINT[]))
-            AND ($4::SMALLINT[] IS NULL OR lp.loadout_id = ANY($4::SMALLINT[]))
-        ORDER BY p.timestamp",
+        "WITH latest AS (
+            SELECT
+                w.world_id,
+                (
+                    SELECT MAX(wp.population_id)
+                    FROM world_population wp
+                    WHERE wp.world_id = w.world_id
+                ) AS population_id
+            FROM world w
+            WHERE ($1::INTEGER[] IS NULL OR w.world_id = ANY($1::INTEGER[]))
+                AND ($5::TEXT[] IS NULL OR w.environment = ANY($5::TEXT[]))
+        )
+        SELECT
+            p.timestamp AS \"timestamp!\",
+            p.world_id AS \"world_id!\",
+            p.zone_id AS \"zone_id!\",
+            p.instance_id AS \"instance_id!\",
+            p.team_id AS \"team_id!\",
+            p.loadout_id AS \"loadout_id!\",
+            p.amount AS \"amount!\"
+        FROM latest l
+        CROSS JOIN LATERAL (
+            SELECT * FROM population_snapshot ps
+            WHERE $6 AND ps.world_id = l.world_id AND ps.population_id = l.population_id
+            UNION ALL
+            SELECT * FROM population_loadout pl
+            WHERE NOT $6 AND pl.world_id = l.world_id AND pl.population_id = l.population_id
+        ) p
+        WHERE ($2::INTEGER[] IS NULL OR p.zone_id = ANY($2::INTEGER[]))
+            AND ($3::SMALLINT[] IS NULL OR p.team_id = ANY($3::SMALLINT[]))
+            AND ($4::SMALLINT[] IS NULL OR p.loadout_id = ANY($4::SMALLINT[]))
+        ORDER BY p.timestamp DESC",
         worlds,
         zones,
         teams,
         loadouts,
+        environments,
+        population_layout.is_flat(),
     )
-        .fetch_all(db_pool)
-        .await else {
+    .fetch_all(db_pool)
+    .await
+    else {
         return None;
     };
 
@@ -114,6 +205,7 @@ pub async fn get_current(
     }
 
     let mut world_breakdown: WorldBreakdown = HashMap::new();
+    let mut world_timestamps = HashMap::new();
 
     let timestamp = population[0].timestamp;
 
@@ -144,15 +236,22 @@ pub async fn get_current(
             continue;
         };
 
+        #[allow(clippy::cast_possible_truncation)]
+        #[allow(clippy::cast_sign_loss)]
+        let zone_id = DefinitionID(record.zone_id as u16);
+
+        #[allow(clippy::cast_possible_truncation)]
         #[allow(clippy::cast_sign_loss)]
-        let zone_id = ZoneID(record.zone_id as u32);
+        let instance_id = InstanceID(record.instance_id as u16);
 
         #[allow(clippy::cast_sign_loss)]
         let amount = record.amount as u16;
 
+        world_timestamps.insert(world_id, record.timestamp);
         let world = world_breakdown.entry(world_id).or_default();
         let zone = world.entry(zone_id).or_default();
-        let team = zone.entry(team_id).or_default();
+        let instance = zone.entry(instance_id).or_default();
+        let team = instance.entry(team_id).or_default();
         let loadout = team.entry(loadout_id).or_insert(0);
 
         *loadout += amount;
@@ -160,10 +259,87 @@ pub async fn get_current(
 
     Some(PopBreakdown {
         timestamp,
+        world_timestamps,
         worlds: world_breakdown,
     })
 }
 
+/// Get `PopTeam` from `TeamBreakdown`
+///
+/// # Arguments
+///
+/// * `team_breakdown` - The `TeamBreakdown` to convert
+///
+/// # Returns
+///
+/// * `Vec<PopTeam>` - The converted `TeamBreakdown`
+fn get_pop_teams_from_team_breakdown(team_breakdown: TeamBreakdown) -> Vec<PopTeam> {
+    let mut teams = Vec::new();
+    for (team_id, team_population) in team_breakdown {
+        let mut loadouts = Vec::new();
+        for (loadout_id, loadout_population) in team_population {
+            loadouts.push(PopLoadout {
+                loadout_id,
+                loadout_population,
+            });
+        }
+        teams.push(PopTeam {
+            team_id,
+            team_population: loadouts.iter().map(|l| l.loadout_population).sum(),
+            loadouts,
+        });
+    }
+    teams
+}
+
+/// Get `PopZone` from `InstanceBreakdown`, combining the population of all instances
+///
+/// # Arguments
+///
+/// * `zone_id` - The definition ID of the zone
+/// * `instance_breakdown` - The `InstanceBreakdown` to convert
+///
+/// # Returns
+///
+/// * `PopZone` - The converted `InstanceBreakdown`
+fn get_pop_zone_from_instance_breakdown(
+    zone_id: DefinitionID,
+    instance_breakdown: InstanceBreakdown,
+) -> PopZone {
+    let has_instances = instance_breakdown
+        .keys()
+        .any(|instance_id| *instance_id != InstanceID(0));
+
+    let mut combined: TeamBreakdown = HashMap::new();
+    let mut instances = Vec::new();
+    for (instance_id, team_breakdown) in instance_breakdown {
+        for (team_id, loadout_breakdown) in &team_breakdown {
+            let team = combined.entry(*team_id).or_default();
+            for (loadout_id, amount) in loadout_breakdown {
+                *team.entry(*loadout_id).or_insert(0) += amount;
+            }
+        }
+
+        if has_instances {
+            let teams = get_pop_teams_from_team_breakdown(team_breakdown);
+            instances.push(PopInstance {
+                instance_id,
+                instance_population: teams.iter().map(|t| t.team_population).sum(),
+                teams,
+            });
+        }
+    }
+
+    let teams = get_pop_teams_from_team_breakdown(combined);
+    PopZone {
+        zone_id,
+        full_zone_data: None,
+        zone_population: teams.iter().map(|t| t.team_population).sum(),
+        teams,
+        instances: has_instances.then_some(instances),
+    }
+}
+
 /// Get `PopWorld` from `WorldBreakdown`
 ///
 /// # Arguments
@@ -178,30 +354,19 @@ pub fn get_pop_worlds_from_world_breakdown(population: PopBreakdown) -> Populati
     for (world_id, world_population) in population.worlds {
         let mut zones = Vec::new();
         for (zone_id, zone_population) in world_population {
-            let mut teams = Vec::new();
-            for (team_id, team_population) in zone_population {
-                let mut loadouts = Vec::new();
-                for (loadout_id, loadout_population) in team_population {
-                    loadouts.push(PopLoadout {
-                        loadout_id,
-                        loadout_population,
-                    });
-                }
-                teams.push(PopTeam {
-                    team_id,
-                    team_population: loadouts.iter().map(|l| l.loadout_population).sum(),
-                    loadouts,
-                });
-            }
-            zones.push(PopZone {
+            zones.push(get_pop_zone_from_instance_breakdown(
                 zone_id,
-                full_zone_data: None,
-                zone_population: teams.iter().map(|t| t.team_population).sum(),
-                teams,
-            });
+                zone_population,
+            ));
         }
         result.push(PopWorld {
             world_id,
+            environment: world_id.environment(),
+            timestamp: population
+                .world_timestamps
+                .get(&world_id)
+                .copied()
+                .unwrap_or(population.timestamp),
             world_population: zones.iter().map(|z| z.zone_population).sum(),
             zones,
         });
@@ -209,6 +374,7 @@ pub fn get_pop_worlds_from_world_breakdown(population: PopBreakdown) -> Populati
     PopulationApiResponse {
         timestamp: population.timestamp,
         worlds: result,
+        event_streams: Vec::new(),
     }
 }
 
@@ -217,10 +383,12 @@ pub fn get_pop_worlds_from_world_breakdown(population: PopBreakdown) -> Populati
 /// # Arguments
 ///
 /// * `db_pool` - The database pool to use
+/// * `population_layout` - Whether to read the normalized levels or `population_snapshot`
 /// * `worlds` - The world IDs to check
-/// * `zones` - The zone IDs to check
+/// * `zones` - The zone definition IDs to check
 /// * `team_ids` - The team IDs to check
 /// * `loadouts` - The loadout IDs to check
+/// * `environments` - The environments to check
 ///
 /// # Returns
 ///
@@ -228,14 +396,468 @@ pub fn get_pop_worlds_from_world_breakdown(population: PopBreakdown) -> Populati
 /// * `Err(sqlx::Error)` - The error returned by sqlx
 pub async fn get_current_tree(
     db_pool: &PgPool,
+    population_layout: PopulationLayout,
     worlds: Option<&[i32]>,
     zones: Option<&[i32]>,
     teams: Option<&[i16]>,
     loadouts: Option<&[i16]>,
+    environments: Option<&[String]>,
 ) -> Option<PopulationApiResponse> {
-    let population = get_current(db_pool, worlds, zones, teams, loadouts).await?;
+    let population = get_current(
+        db_pool,
+        population_layout,
+        worlds,
+        zones,
+        teams,
+        loadouts,
+        environments,
+    )
+    .await?;
 
     let result = get_pop_worlds_from_world_breakdown(population);
 
     Some(result)
 }
+
+/// Get `PopVehicleZone` from `WorldVehicleBreakdown`, ordered by world and zone
+///
+/// # Arguments
+///
+/// * `vehicle_breakdown` - The `WorldVehicleBreakdown` to convert
+///
+/// # Returns
+///
+/// * `Vec<PopVehicleZone>` - The converted `WorldVehicleBreakdown`
+fn get_pop_vehicle_zones(vehicle_breakdown: WorldVehicleBreakdown) -> Vec<PopVehicleZone> {
+    let mut zones = Vec::new();
+    for (world_id, zone_breakdown) in vehicle_breakdown {
+        for (zone_id, team_breakdown) in zone_breakdown {
+            let mut teams = Vec::new();
+            for (team_id, vehicle_breakdown) in team_breakdown {
+                let vehicles: Vec<PopVehicle> = vehicle_breakdown
+                    .into_iter()
+                    .map(|(vehicle_id, vehicle_population)| PopVehicle {
+                        vehicle_id,
+                        vehicle_population,
+                    })
+                    .collect();
+                teams.push(PopVehicleTeam {
+                    team_id,
+                    team_population: vehicles.iter().map(|v| v.vehicle_population).sum(),
+                    vehicles,
+                });
+            }
+            zones.push(PopVehicleZone {
+                world_id,
+                zone_id,
+                vehicle_population: teams.iter().map(|t| t.team_population).sum(),
+                teams,
+            });
+        }
+    }
+
+    zones.sort_by_key(|zone| (zone.world_id, zone.zone_id));
+    zones
+}
+
+/// Get the players in each vehicle from the latest population
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `worlds` - The world IDs to check
+/// * `zones` - The zone definition IDs to check
+///
+/// # Returns
+///
+/// * `Ok(Vec<PopVehicleZone>)` - The vehicles of each zone, ordered by world and zone
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn get_current_vehicles(
+    db_pool: &PgPool,
+    worlds: Option<&[i32]>,
+    zones: Option<&[i32]>,
+) -> Result<Vec<PopVehicleZone>, sqlx::Error> {
+    let vehicles = sqlx::query!(
+        "WITH latest AS (
+            SELECT
+                w.world_id,
+                (
+                    SELECT MAX(wp.population_id)
+                    FROM world_population wp
+                    WHERE wp.world_id = w.world_id
+                ) AS population_id
+            FROM world w
+            WHERE $1::INTEGER[] IS NULL OR w.world_id = ANY($1::INTEGER[])
+        )
+        SELECT wp.world_id, vp.zone_id, vp.team_id, vp.vehicle_id, vp.amount
+        FROM latest l
+        JOIN world_population wp ON wp.world_id = l.world_id
+            AND wp.population_id = l.population_id
+        JOIN vehicle_population vp ON vp.world_population_id = wp.world_population_id
+        WHERE $2::INTEGER[] IS NULL OR vp.zone_id = ANY($2::INTEGER[])",
+        worlds,
+        zones
+    )
+    .fetch_all(db_pool)
+    .await?;
+
+    let mut vehicle_breakdown: WorldVehicleBreakdown = HashMap::new();
+    for record in vehicles {
+        #[allow(clippy::cast_possible_truncation)]
+        #[allow(clippy::cast_sign_loss)]
+        let Ok(world_id) = WorldID::try_from(record.world_id as u16) else {
+            error!(
+                "Invalid world ID is not defined in auraxis-rs: {}",
+                record.world_id
+            );
+            continue;
+        };
+        #[allow(clippy::cast_sign_loss)]
+        let Ok(team_id) = TeamID::try_from(record.team_id as u16) else {
+            error!(
+                "Invalid team ID (Faction enum) is not defined in auraxis-rs: {}",
+                record.team_id
+            );
+            continue;
+        };
+
+        #[allow(clippy::cast_possible_truncation)]
+        #[allow(clippy::cast_sign_loss)]
+        let zone_id = DefinitionID(record.zone_id as u16);
+        #[allow(clippy::cast_sign_loss)]
+        let (vehicle_id, amount) = (
+            record.vehicle_id as VehicleID,
+            record.amount as PopulationAmount,
+        );
+
+        *vehicle_breakdown
+            .entry(world_id)
+            .or_default()
+            .entry(zone_id)
+            .or_default()
+            .entry(team_id)
+            .or_default()
+            .entry(vehicle_id)
+            .or_insert(0) += amount;
+    }
+
+    Ok(get_pop_vehicle_zones(vehicle_breakdown))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::snapshot::store_pop;
+    use crate::controllers::population_history::{get_history, HistoryGroup};
+    use crate::storage::db_pool::backfill_population_snapshot;
+    use chrono::Utc;
+    use std::time::Instant;
+
+    fn team_breakdown(amount: PopulationAmount) -> TeamBreakdown {
+        HashMap::from([(TeamID::VS, HashMap::from([(Loadout::VSMAX, amount)]))])
+    }
+
+    /// Store a snapshot with the population of each world on a single zone
+    async fn store_snapshot(db_pool: &PgPool, worlds: &[(WorldID, PopulationAmount)]) {
+        let loadout_breakdown: WorldBreakdown = worlds
+            .iter()
+            .map(|(world_id, amount)| {
+                let instances = HashMap::from([(InstanceID(0), team_breakdown(*amount))]);
+                (*world_id, HashMap::from([(DefinitionID(2), instances)]))
+            })
+            .collect();
+
+        store_pop(
+            &loadout_breakdown,
+            &HashMap::new(),
+            &HashMap::new(),
+            PopulationLayout::Flat,
+            db_pool,
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Miller is stored in both snapshots, Cobalt only in the first one. Both are stored in both
+    /// layouts.
+    async fn store_snapshots(db_pool: &PgPool) {
+        store_snapshot(db_pool, &[(WorldID::Miller, 3), (WorldID::Cobalt, 4)]).await;
+        store_snapshot(db_pool, &[(WorldID::Miller, 5)]).await;
+    }
+
+    fn world_amounts(population: &PopBreakdown) -> Vec<(WorldID, PopulationAmount)> {
+        let mut amounts: Vec<(WorldID, PopulationAmount)> = population
+            .worlds
+            .iter()
+            .map(|(world_id, zones)| {
+                let amount = zones[&DefinitionID(2)][&InstanceID(0)][&TeamID::VS][&Loadout::VSMAX];
+                (*world_id, amount)
+            })
+            .collect();
+        amounts.sort_unstable();
+        amounts
+    }
+
+    #[sqlx::test]
+    async fn test_current_without_world_filter(db_pool: PgPool) {
+        store_snapshots(&db_pool).await;
+
+        for population_layout in [PopulationLayout::Normalized, PopulationLayout::Flat] {
+            let population = get_current(&db_pool, population_layout, None, None, None, None, None)
+                .await
+                .unwrap();
+
+            assert_eq!(
+                world_amounts(&population),
+                vec![(WorldID::Miller, 5), (WorldID::Cobalt, 4)]
+            );
+            assert!(
+                population.world_timestamps[&WorldID::Cobalt]
+                    < population.world_timestamps[&WorldID::Miller]
+            );
+            assert_eq!(
+                population.timestamp,
+                population.world_timestamps[&WorldID::Miller]
+            );
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_current_of_several_worlds(db_pool: PgPool) {
+        store_snapshots(&db_pool).await;
+
+        let worlds = [WorldID::Miller as i32, WorldID::Cobalt as i32];
+        for population_layout in [PopulationLayout::Normalized, PopulationLayout::Flat] {
+            let population = get_current(
+                &db_pool,
+                population_layout,
+                Some(&worlds),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(
+                world_amounts(&population),
+                vec![(WorldID::Miller, 5), (WorldID::Cobalt, 4)]
+            );
+        }
+    }
+
+    #[sqlx::test]
+    async fn test_current_of_one_world(db_pool: PgPool) {
+        store_snapshots(&db_pool).await;
+
+        for population_layout in [PopulationLayout::Normalized, PopulationLayout::Flat] {
+            let worlds = [WorldID::Cobalt as i32];
+            let population = get_current(
+                &db_pool,
+                population_layout,
+                Some(&worlds),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(world_amounts(&population), vec![(WorldID::Cobalt, 4)]);
+            assert_eq!(
+                population.timestamp,
+                population.world_timestamps[&WorldID::Cobalt]
+            );
+
+            let worlds = [WorldID::Emerald as i32];
+            assert!(get_current(
+                &db_pool,
+                population_layout,
+                Some(&worlds),
+                None,
+                None,
+                None,
+                None
+            )
+            .await
+            .is_none());
+        }
+    }
+
+    /// A day of snapshots every 30 seconds of four worlds in the normalized levels
+    const BENCH_SEED: &str = "
+        INSERT INTO world (world_id) VALUES (1), (10), (13), (17) ON CONFLICT DO NOTHING;
+        INSERT INTO zone (zone_id) VALUES (2), (4), (6), (8) ON CONFLICT DO NOTHING;
+        INSERT INTO faction (faction_id) VALUES (1), (2), (3) ON CONFLICT DO NOTHING;
+        INSERT INTO loadout (loadout_id)
+        SELECT generate_series(1, 8) ON CONFLICT DO NOTHING;
+        INSERT INTO population (timestamp)
+        SELECT generate_series(
+            (NOW() AT TIME ZONE 'UTC') - INTERVAL '1 day',
+            NOW() AT TIME ZONE 'UTC',
+            INTERVAL '30 seconds'
+        );
+        INSERT INTO world_population (world_id, population_id)
+        SELECT w, population_id FROM population, (VALUES (1), (10), (13), (17)) v(w);
+        INSERT INTO zone_population (zone_id, instance_id, world_population_id)
+        SELECT z, 0, world_population_id FROM world_population, (VALUES (2), (4), (6), (8)) v(z);
+        INSERT INTO team_population (zone_population_id, team_id)
+        SELECT zone_population_id, t FROM zone_population, (VALUES (1), (2), (3)) v(t);
+        INSERT INTO loadout_population (team_population_id, loadout_id, amount)
+        SELECT team_population_id, l, (team_population_id * 7 + l * 13) % 50
+        FROM team_population, generate_series(1, 8) l;";
+
+    /// Compares reading the current population and a day of history from the normalized levels
+    /// against `population_snapshot`. Run with
+    /// `cargo test --release bench_population_layouts -- --ignored --nocapture`
+    #[sqlx::test]
+    #[ignore = "benchmark"]
+    async fn bench_population_layouts(db_pool: PgPool) {
+        const READS: u32 = 20;
+
+        sqlx::raw_sql(BENCH_SEED).execute(&db_pool).await.unwrap();
+        backfill_population_snapshot(&db_pool).await.unwrap();
+        sqlx::raw_sql("ANALYZE").execute(&db_pool).await.unwrap();
+        let to = Utc::now().naive_utc();
+        let from = to - chrono::Duration::days(1);
+
+        for population_layout in [PopulationLayout::Normalized, PopulationLayout::Flat] {
+            let started = Instant::now();
+            for _ in 0..READS {
+                get_current(&db_pool, population_layout, None, None, None, None, None)
+                    .await
+                    .unwrap();
+            }
+            println!(
+                "current, {population_layout:?}: {:?}",
+                started.elapsed() / READS
+            );
+
+            let started = Instant::now();
+            for _ in 0..READS {
+                get_history(
+                    &db_pool,
+                    population_layout,
+                    None,
+                    None,
+                    from..to,
+                    300,
+                    HistoryGroup::Faction,
+                )
+                .await
+                .unwrap();
+            }
+            println!(
+                "history, {population_layout:?}: {:?}",
+                started.elapsed() / READS
+            );
+        }
+    }
+
+    /// Store a snapshot of a world with the VS players on Indar in vehicle 2
+    async fn store_vehicle_snapshot(db_pool: &PgPool, world_id: WorldID, amount: PopulationAmount) {
+        let instances = HashMap::from([(InstanceID(0), team_breakdown(amount))]);
+        let loadout_breakdown: WorldBreakdown =
+            HashMap::from([(world_id, HashMap::from([(DefinitionID(2), instances)]))]);
+        let vehicles = HashMap::from([(TeamID::VS, HashMap::from([(2, amount)]))]);
+        let vehicle_breakdown: WorldVehicleBreakdown =
+            HashMap::from([(world_id, HashMap::from([(DefinitionID(2), vehicles)]))]);
+
+        store_pop(
+            &loadout_breakdown,
+            &vehicle_breakdown,
+            &HashMap::new(),
+            PopulationLayout::Normalized,
+            db_pool,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_current_vehicles_of_each_world(db_pool: PgPool) {
+        store_vehicle_snapshot(&db_pool, WorldID::Miller, 3).await;
+        store_vehicle_snapshot(&db_pool, WorldID::Cobalt, 4).await;
+        store_vehicle_snapshot(&db_pool, WorldID::Miller, 5).await;
+
+        let vehicles = get_current_vehicles(&db_pool, None, None).await.unwrap();
+        let amounts: Vec<(WorldID, PopulationAmount)> = vehicles
+            .iter()
+            .map(|zone| (zone.world_id, zone.vehicle_population))
+            .collect();
+        assert_eq!(amounts, vec![(WorldID::Miller, 5), (WorldID::Cobalt, 4)]);
+
+        let cobalt = [WorldID::Cobalt as i32];
+        let vehicles = get_current_vehicles(&db_pool, Some(&cobalt), None)
+            .await
+            .unwrap();
+        assert_eq!(vehicles.len(), 1);
+        assert_eq!(vehicles[0].vehicle_population, 4);
+    }
+
+    fn pop_zone(instance_breakdown: InstanceBreakdown) -> PopZone {
+        let population = PopBreakdown {
+            timestamp: chrono::NaiveDateTime::default(),
+            world_timestamps: HashMap::new(),
+            worlds: HashMap::from([(
+                WorldID::Miller,
+                HashMap::from([(DefinitionID(14), instance_breakdown)]),
+            )]),
+        };
+
+        let mut response = get_pop_worlds_from_world_breakdown(population);
+        response.worlds.pop().unwrap().zones.pop().unwrap()
+    }
+
+    #[test]
+    fn test_instances_are_combined() {
+        let zone = pop_zone(HashMap::from([
+            (InstanceID(1), team_breakdown(3)),
+            (InstanceID(2), team_breakdown(4)),
+        ]));
+
+        assert_eq!(zone.zone_population, 7);
+        assert_eq!(zone.teams.len(), 1);
+        assert_eq!(zone.teams[0].loadouts[0].loadout_population, 7);
+
+        let mut instances = zone.instances.unwrap();
+        instances.sort_by_key(|i| i.instance_id);
+        assert_eq!(instances[0].instance_population, 3);
+        assert_eq!(instances[1].instance_population, 4);
+    }
+
+    #[test]
+    fn test_vehicle_zones() {
+        let zones = get_pop_vehicle_zones(HashMap::from([(
+            WorldID::Miller,
+            HashMap::from([(
+                DefinitionID(344),
+                HashMap::from([
+                    (TeamID::VS, HashMap::from([(4, 3), (2, 1)])),
+                    (TeamID::TR, HashMap::from([(4, 2)])),
+                ]),
+            )]),
+        )]));
+
+        assert_eq!(zones.len(), 1);
+        assert_eq!(zones[0].vehicle_population, 6);
+        let vs = zones[0]
+            .teams
+            .iter()
+            .find(|team| team.team_id == TeamID::VS)
+            .unwrap();
+        assert_eq!(vs.team_population, 4);
+        assert_eq!(vs.vehicles.len(), 2);
+    }
+
+    #[test]
+    fn test_continents_have_no_instances() {
+        let zone = pop_zone(HashMap::from([(InstanceID(0), team_breakdown(3))]));
+
+        assert_eq!(zone.zone_population, 3);
+        assert!(zone.instances.is_none());
+    }
+}
@@ -0,0 +1,538 @@
+use crate::census::constants::WorldID;
+use crate::serde::naivedatetime;
+use crate::storage::configuration::PopulationLayout;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use strum::{EnumIter, IntoEnumIterator};
+use tracing::error;
+use utoipa::ToSchema;
+
+/// The population is stored every 30 seconds, smaller buckets would be empty
+pub const MIN_BUCKET_SECONDS: i64 = 30;
+/// The most buckets a single request may return, so large ranges need larger buckets
+pub const MAX_BUCKETS: i64 = 5000;
+const HOUR_SECONDS: i64 = 60 * 60;
+const DAY_SECONDS: i64 = 24 * HOUR_SECONDS;
+
+/// What the population of a world is split by in the history
+#[derive(
+    Serialize,
+    ToSchema,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    strum::EnumString,
+    strum::IntoStaticStr,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum HistoryGroup {
+    #[default]
+    Faction,
+    Zone,
+    Loadout,
+}
+
+/// The population of a group during a bucket
+#[derive(Serialize, ToSchema, Clone, Debug, PartialEq)]
+pub struct PopHistoryBucket {
+    /// The start of the bucket
+    #[serde(with = "naivedatetime")]
+    pub timestamp: NaiveDateTime,
+    pub world_id: WorldID,
+    /// The faction, zone definition or loadout ID, depending on the group
+    pub key: i32,
+    pub average: f64,
+    pub minimum: i64,
+    pub maximum: i64,
+}
+
+#[derive(Serialize, ToSchema, Clone, Debug)]
+pub struct PopHistory {
+    pub group: HistoryGroup,
+    pub bucket_seconds: i64,
+    pub buckets: Vec<PopHistoryBucket>,
+}
+
+/// The bucket size for a range, a few hundred points for the last day, week or month when no
+/// size was requested
+///
+/// # Arguments
+///
+/// * `range_seconds` - The length of the requested range
+/// * `requested` - The bucket size that was requested, if any
+///
+/// # Returns
+///
+/// * `Some(i64)` - The bucket size in seconds
+/// * `None` - The requested size is too small or would return more than `MAX_BUCKETS` buckets
+pub const fn bucket_seconds(range_seconds: i64, requested: Option<i64>) -> Option<i64> {
+    let bucket_seconds = match requested {
+        Some(requested) => requested,
+        None if range_seconds <= DAY_SECONDS => 5 * 60,
+        None if range_seconds <= 7 * DAY_SECONDS => HOUR_SECONDS,
+        None => DAY_SECONDS,
+    };
+
+    if bucket_seconds < MIN_BUCKET_SECONDS || range_seconds / bucket_seconds > MAX_BUCKETS {
+        return None;
+    }
+
+    Some(bucket_seconds)
+}
+
+/// Where the population is read from, the raw snapshots or the aggregates the retention job
+/// rolls them up into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumIter, strum::IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum Resolution {
+    Raw,
+    FiveMinutes,
+    Hourly,
+    Daily,
+}
+
+impl Resolution {
+    /// The size of a bucket, for raw snapshots the interval they are stored at
+    pub const fn seconds(self) -> i64 {
+        match self {
+            Self::Raw => MIN_BUCKET_SECONDS,
+            Self::FiveMinutes => 5 * 60,
+            Self::Hourly => HOUR_SECONDS,
+            Self::Daily => DAY_SECONDS,
+        }
+    }
+
+    /// The resolution rolled up from, `None` for the raw snapshots
+    pub const fn finer(self) -> Option<Self> {
+        match self {
+            Self::Raw => None,
+            Self::FiveMinutes => Some(Self::Raw),
+            Self::Hourly => Some(Self::FiveMinutes),
+            Self::Daily => Some(Self::Hourly),
+        }
+    }
+
+    /// The aggregate resolution with buckets of this size
+    pub const fn from_seconds(seconds: i64) -> Option<Self> {
+        match seconds {
+            300 => Some(Self::FiveMinutes),
+            HOUR_SECONDS => Some(Self::Hourly),
+            DAY_SECONDS => Some(Self::Daily),
+            _ => None,
+        }
+    }
+}
+
+/// The buckets stored for a resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Level {
+    pub oldest: NaiveDateTime,
+    /// The end of the newest bucket, for raw snapshots the newest snapshot
+    pub until: NaiveDateTime,
+}
+
+/// The stored buckets of every resolution that has any
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `population_layout` - Whether the raw snapshots are read from the normalized levels or from
+///   `population_snapshot`
+///
+/// # Returns
+///
+/// * `Ok(BTreeMap<Resolution, Level>)` - The stored buckets per resolution
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn levels(
+    db_pool: &PgPool,
+    population_layout: PopulationLayout,
+) -> Result<BTreeMap<Resolution, Level>, sqlx::Error> {
+    let mut levels = BTreeMap::new();
+
+    // The snapshots themselves are kept for their vehicles, outfits and kill statistics, so only
+    // the ones that still have their loadouts count
+    let raw = sqlx::query!(
+        "SELECT
+            CASE WHEN $1 THEN (SELECT MIN(timestamp) FROM population_snapshot)
+                ELSE (SELECT MIN(timestamp) FROM population_loadout)
+            END AS oldest,
+            CASE WHEN $1 THEN (SELECT MAX(timestamp) FROM population_snapshot)
+                ELSE (SELECT MAX(timestamp) FROM population_loadout)
+            END AS newest",
+        population_layout.is_flat()
+    )
+    .fetch_one(db_pool)
+    .await?;
+    if let (Some(oldest), Some(until)) = (raw.oldest, raw.newest) {
+        levels.insert(Resolution::Raw, Level { oldest, until });
+    }
+
+    let aggregates = sqlx::query!(
+        "SELECT resolution, MIN(bucket) AS \"oldest!\", MAX(bucket) AS \"newest!\"
+        FROM population_aggregate_world
+        GROUP BY resolution"
+    )
+    .fetch_all(db_pool)
+    .await?;
+    for aggregate in aggregates {
+        let Some(resolution) = Resolution::from_seconds(i64::from(aggregate.resolution)) else {
+            error!(
+                "Unknown population aggregate resolution: {}",
+                aggregate.resolution
+            );
+            continue;
+        };
+
+        levels.insert(
+            resolution,
+            Level {
+                oldest: aggregate.oldest,
+                until: aggregate.newest + chrono::Duration::seconds(resolution.seconds()),
+            },
+        );
+    }
+
+    Ok(levels)
+}
+
+/// The resolution to read a range from, the coarsest one that fits in the buckets. When its data
+/// doesn't go back far enough, the finest coarser resolution that does is used instead.
+///
+/// # Arguments
+///
+/// * `from` - The start of the range
+/// * `bucket_seconds` - The requested bucket size
+/// * `levels` - The stored buckets per resolution
+///
+/// # Returns
+///
+/// * `(Resolution, i64)` - The resolution and the bucket size, raised to the size of its buckets
+pub fn pick_resolution(
+    from: NaiveDateTime,
+    bucket_seconds: i64,
+    levels: &BTreeMap<Resolution, Level>,
+) -> (Resolution, i64) {
+    let fitting = Resolution::iter()
+        .rev()
+        .find(|resolution| {
+            *resolution == Resolution::Raw
+                || (resolution.seconds() <= bucket_seconds
+                    && bucket_seconds % resolution.seconds() == 0)
+        })
+        .unwrap_or(Resolution::Raw);
+
+    let covers = |resolution: &Resolution| {
+        levels
+            .get(resolution)
+            .is_some_and(|level| level.oldest <= from)
+    };
+    let resolution = if covers(&fitting) {
+        fitting
+    } else {
+        Resolution::iter()
+            .filter(|resolution| *resolution > fitting)
+            .find(covers)
+            .unwrap_or(fitting)
+    };
+
+    (resolution, bucket_seconds.max(resolution.seconds()))
+}
+
+/// Where each aggregate resolution stops being read, as the coarsest resolution is read first and
+/// every finer resolution continues where the coarser one wasn't rolled up yet. The raw
+/// snapshots are read from the end of the five minute buckets.
+fn read_until(
+    resolution: Resolution,
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    levels: &BTreeMap<Resolution, Level>,
+) -> [NaiveDateTime; 3] {
+    let mut start = from;
+    [
+        Resolution::Daily,
+        Resolution::Hourly,
+        Resolution::FiveMinutes,
+    ]
+    .map(|level| {
+        if level <= resolution {
+            if let Some(stored) = levels.get(&level) {
+                start = start.max(stored.until.min(to));
+            }
+        }
+
+        start
+    })
+}
+
+/// A bucket as read from the database, before its world is checked
+struct HistoryRecord {
+    bucket: NaiveDateTime,
+    world_id: i32,
+    key: i32,
+    average: f64,
+    minimum: i64,
+    maximum: i64,
+}
+
+impl HistoryRecord {
+    fn into_bucket(self) -> Option<PopHistoryBucket> {
+        #[allow(clippy::cast_possible_truncation)]
+        #[allow(clippy::cast_sign_loss)]
+        let Ok(world_id) = WorldID::try_from(self.world_id as u16) else {
+            error!(
+                "Invalid world ID is not defined in auraxis-rs: {}",
+                self.world_id
+            );
+            return None;
+        };
+
+        Some(PopHistoryBucket {
+            timestamp: self.bucket,
+            world_id,
+            key: self.key,
+            average: self.average,
+            minimum: self.minimum,
+            maximum: self.maximum,
+        })
+    }
+}
+
+/// Get the population between two moments in buckets, with the average, minimum and maximum of
+/// every group. Snapshots without players of a group count as zero. Older ranges are read from
+/// the aggregates of the retention job, in buckets of at least the size of the aggregates.
+///
+/// # Arguments
+///
+/// * `db_pool` - The database pool to use
+/// * `population_layout` - Whether to read the newest snapshots from the normalized levels or
+///   from `population_snapshot`
+/// * `worlds` - The world IDs to check
+/// * `zones` - The zone definition IDs to check
+/// * `range` - The moments to read, the buckets are aligned to its start
+/// * `bucket_seconds` - The requested size of each bucket
+/// * `group` - What to split the population of each world by
+///
+/// # Returns
+///
+/// * `Ok(PopHistory)` - The buckets ordered by time, world and group
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn get_history(
+    db_pool: &PgPool,
+    population_layout: PopulationLayout,
+    worlds: Option<&[i32]>,
+    zones: Option<&[i32]>,
+    range: Range<NaiveDateTime>,
+    bucket_seconds: i64,
+    group: HistoryGroup,
+) -> Result<PopHistory, sqlx::Error> {
+    let levels = levels(db_pool, population_layout).await?;
+    let (resolution, bucket_seconds) = pick_resolution(range.start, bucket_seconds, &levels);
+    let until = read_until(resolution, range.start, range.end, &levels);
+
+    let group_by: &str = group.into();
+    let buckets = sqlx::query_as!(
+        HistoryRecord,
+        "WITH ranges (resolution, start, until) AS (
+            VALUES
+                (86400, $3::TIMESTAMP, ($7::TIMESTAMP[])[1]),
+                (3600, ($7::TIMESTAMP[])[1], ($7::TIMESTAMP[])[2]),
+                (300, ($7::TIMESTAMP[])[2], ($7::TIMESTAMP[])[3])
+        ),
+        points AS (
+            SELECT
+                p.timestamp AS sub_bucket,
+                p.world_id,
+                CASE $6::TEXT
+                    WHEN 'zone' THEN p.zone_id
+                    WHEN 'loadout' THEN p.loadout_id
+                    ELSE p.team_id
+                END AS key,
+                SUM(p.amount)::BIGINT AS total,
+                SUM(p.amount)::BIGINT AS minimum,
+                SUM(p.amount)::BIGINT AS maximum
+            FROM (
+                SELECT * FROM population_snapshot WHERE $8
+                UNION ALL
+                SELECT * FROM population_loadout WHERE NOT $8
+            ) p
+            WHERE p.timestamp >= ($7::TIMESTAMP[])[3] AND p.timestamp < $4
+                AND ($1::INTEGER[] IS NULL OR p.world_id = ANY($1::INTEGER[]))
+                AND ($2::INTEGER[] IS NULL OR p.zone_id = ANY($2::INTEGER[]))
+            GROUP BY p.population_id, p.timestamp, p.world_id, key
+            UNION ALL
+            SELECT a.bucket, a.world_id, a.key,
+                SUM(a.total)::BIGINT, SUM(a.minimum)::BIGINT, SUM(a.maximum)::BIGINT
+            FROM population_aggregate a
+            JOIN ranges r ON r.resolution = a.resolution
+                AND a.bucket >= r.start AND a.bucket < r.until
+            WHERE a.group_by = $6::TEXT
+                AND ($1::INTEGER[] IS NULL OR a.world_id = ANY($1::INTEGER[]))
+                AND (($2::INTEGER[] IS NULL AND (a.zone_id IS NULL OR $6::TEXT = 'zone'))
+                    OR a.zone_id = ANY($2::INTEGER[]))
+            GROUP BY a.bucket, a.world_id, a.key
+        ),
+        samples AS (
+            SELECT p.timestamp AS sub_bucket, wp.world_id, 1 AS snapshots
+            FROM population p
+            JOIN world_population wp ON p.population_id = wp.population_id
+            WHERE p.timestamp >= ($7::TIMESTAMP[])[3] AND p.timestamp < $4
+                AND ($1::INTEGER[] IS NULL OR wp.world_id = ANY($1::INTEGER[]))
+            UNION ALL
+            SELECT aw.bucket, aw.world_id, aw.snapshots
+            FROM population_aggregate_world aw
+            JOIN ranges r ON r.resolution = aw.resolution
+                AND aw.bucket >= r.start AND aw.bucket < r.until
+            WHERE $1::INTEGER[] IS NULL OR aw.world_id = ANY($1::INTEGER[])
+        ),
+        buckets AS (
+            SELECT date_bin($5::BIGINT * INTERVAL '1 second', sub_bucket, $3) AS bucket,
+                world_id, COUNT(*) AS sub_buckets, SUM(snapshots) AS snapshots
+            FROM samples
+            GROUP BY 1, world_id
+        )
+        SELECT
+            b.bucket AS \"bucket!\",
+            points.world_id AS \"world_id!\",
+            points.key AS \"key!\",
+            SUM(points.total)::FLOAT8 / b.snapshots AS \"average!\",
+            CASE WHEN COUNT(*) < b.sub_buckets THEN 0 ELSE MIN(points.minimum) END AS \"minimum!\",
+            MAX(points.maximum) AS \"maximum!\"
+        FROM points
+        JOIN buckets b ON b.world_id = points.world_id
+            AND b.bucket = date_bin($5::BIGINT * INTERVAL '1 second', points.sub_bucket, $3)
+        GROUP BY b.bucket, points.world_id, points.key, b.snapshots, b.sub_buckets
+        ORDER BY b.bucket, points.world_id, points.key",
+        worlds,
+        zones,
+        range.start,
+        range.end,
+        bucket_seconds,
+        group_by,
+        &until,
+        population_layout.is_flat()
+    )
+    .fetch_all(db_pool)
+    .await?
+    .into_iter()
+    .filter_map(HistoryRecord::into_bucket)
+    .collect();
+
+    Ok(PopHistory {
+        group,
+        bucket_seconds,
+        buckets,
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    #[test]
+    fn test_bucket_seconds() {
+        assert_eq!(bucket_seconds(DAY_SECONDS, None), Some(5 * 60));
+        assert_eq!(bucket_seconds(7 * DAY_SECONDS, None), Some(HOUR_SECONDS));
+        assert_eq!(bucket_seconds(30 * DAY_SECONDS, None), Some(DAY_SECONDS));
+        assert_eq!(bucket_seconds(HOUR_SECONDS, Some(60)), Some(60));
+        assert_eq!(bucket_seconds(HOUR_SECONDS, Some(10)), None);
+        assert_eq!(bucket_seconds(30 * DAY_SECONDS, Some(60)), None);
+    }
+
+    fn at(hours: i64) -> NaiveDateTime {
+        DateTime::from_timestamp(1_760_000_000 + hours * HOUR_SECONDS, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    fn levels() -> BTreeMap<Resolution, Level> {
+        BTreeMap::from([
+            (
+                Resolution::Raw,
+                Level {
+                    oldest: at(-48),
+                    until: at(0),
+                },
+            ),
+            (
+                Resolution::FiveMinutes,
+                Level {
+                    oldest: at(-30 * 24),
+                    until: at(0),
+                },
+            ),
+            (
+                Resolution::Hourly,
+                Level {
+                    oldest: at(-365 * 24),
+                    until: at(-1),
+                },
+            ),
+            (
+                Resolution::Daily,
+                Level {
+                    oldest: at(-365 * 24),
+                    until: at(-20),
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_pick_resolution() {
+        let levels = levels();
+
+        assert_eq!(pick_resolution(at(-24), 60, &levels), (Resolution::Raw, 60));
+        assert_eq!(
+            pick_resolution(at(-24), 300, &levels),
+            (Resolution::FiveMinutes, 300)
+        );
+        assert_eq!(
+            pick_resolution(at(-7 * 24), 7200, &levels),
+            (Resolution::Hourly, 7200)
+        );
+        // The raw snapshots of a week ago were deleted
+        assert_eq!(
+            pick_resolution(at(-7 * 24), 60, &levels),
+            (Resolution::FiveMinutes, 300)
+        );
+        assert_eq!(
+            pick_resolution(at(-60 * 24), 300, &levels),
+            (Resolution::Hourly, HOUR_SECONDS)
+        );
+        assert_eq!(
+            pick_resolution(at(-24), 60, &BTreeMap::new()),
+            (Resolution::Raw, 60)
+        );
+    }
+
+    #[test]
+    fn test_read_until() {
+        let levels = levels();
+
+        assert_eq!(
+            read_until(Resolution::Daily, at(-60 * 24), at(0), &levels),
+            [at(-20), at(-1), at(0)]
+        );
+        assert_eq!(
+            read_until(Resolution::Hourly, at(-60 * 24), at(0), &levels),
+            [at(-60 * 24), at(-1), at(0)]
+        );
+        assert_eq!(
+            read_until(Resolution::Hourly, at(-60 * 24), at(-30), &levels),
+            [at(-60 * 24), at(-30), at(-30)]
+        );
+        assert_eq!(
+            read_until(Resolution::Raw, at(-24), at(0), &levels),
+            [at(-24), at(-24), at(-24)]
+        );
+    }
+}
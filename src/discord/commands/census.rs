@@ -1,5 +1,5 @@
 use crate::census::constants::WorldID;
-use crate::controllers::{population, zone};
+use crate::controllers::{metagame, population, zone};
 use crate::discord::formatters;
 use crate::discord::{Context, Error};
 use poise::{serenity_prelude, CreateReply};
@@ -19,10 +19,12 @@ pub async fn population(
 
     let Some(mut population) = population::get_current_tree(
         &ctx.data().db_pool.clone(),
+        ctx.data().population_layout,
         Some(&[server]),
         None,
         None,
         None,
+        None,
     )
     .await
     else {
@@ -58,6 +60,57 @@ pub async fn population(
     Ok(())
 }
 
+/// Shows which continents are open and which alerts are running on a server
+#[poise::command(slash_command, track_edits)]
+pub async fn continents(
+    ctx: Context<'_>,
+    #[description = "The Planetside 2 server to show continents for"]
+    #[autocomplete = "world_id_autocomplete"]
+    server: i32,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    let Some(world_id) = WorldID::try_from(server as u16)
+        .ok()
+        .filter(|world_id| *world_id != WorldID::Unknown)
+    else {
+        return Err(Error::from("Unknown server"));
+    };
+
+    let db_pool = &ctx.data().db_pool;
+
+    let continents = metagame::get_continent_status(db_pool, Some(&[server]))
+        .await
+        .map_err(|e| {
+            error!("Failed to get continent status: {:?}", e);
+            Error::from("Failed to get continent status")
+        })?;
+
+    let alerts = metagame::get_current_alerts(db_pool, Some(&[server]))
+        .await
+        .map_err(|e| {
+            error!("Failed to get alerts: {:?}", e);
+            Error::from("Failed to get alerts")
+        })?;
+
+    let full_zone_data = match zone::get_all(db_pool).await {
+        Ok(zones) => Some(zones),
+        Err(e) => {
+            error!("Failed to get zone data: {:?}", e);
+            None
+        }
+    };
+
+    let embed =
+        formatters::census::continent_status_embed(world_id, &continents, &alerts, &full_zone_data);
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
 #[allow(clippy::unused_async)]
 async fn world_id_autocomplete<'a>(
     _ctx: Context<'_>,
@@ -65,11 +118,10 @@ async fn world_id_autocomplete<'a>(
 ) -> impl Iterator<Item = serenity_prelude::AutocompleteChoice> + 'a {
     // WorldID::iter().map(|v| serenity_prelude::AutocompleteChoice::new(format!("{v}"), v as i16))
     //     Use partial to search for World that contains the partial string
+    // Worlds are labelled with their environment, so "ps2ps4" finds every console world
     WorldID::iter()
-        .filter(move |v| {
-            v.to_string()
-                .to_lowercase()
-                .contains(&partial.to_lowercase())
-        })
-        .map(|v| serenity_prelude::AutocompleteChoice::new(format!("{v}"), v as i16))
+        .filter(|v| *v != WorldID::Unknown)
+        .map(|v| (format!("{v} ({})", v.environment()), v))
+        .filter(move |(label, _)| label.to_lowercase().contains(&partial.to_lowercase()))
+        .map(|(label, v)| serenity_prelude::AutocompleteChoice::new(label, v as i16))
 }
@@ -1,5 +1,7 @@
 #[cfg(feature = "census")]
-use crate::census::constants::Faction;
+use crate::census::constants::{DefinitionID, Faction, WorldID};
+#[cfg(feature = "census")]
+use crate::controllers::metagame::{Alert, ContinentStatus};
 #[cfg(feature = "census")]
 use crate::controllers::population::{PopWorld, PopulationApiResponse};
 use crate::controllers::zone::Zone;
@@ -85,7 +87,7 @@ fn get_total_population(world: &PopWorld) -> Vec<TotalPopulation> {
 fn create_population_embed_base() -> CreateEmbed {
     CreateEmbed::default()
         .thumbnail("https://www.planetside2.com/images/ps2-logo.png")
-        .description("This overview is based on active players earning XP.")
+        .description("This overview is based on logged in players and active players earning XP.")
 }
 
 fn add_timestamp_to_embed(mut embed: CreateEmbed, datetime: chrono::DateTime<Utc>) -> CreateEmbed {
@@ -136,14 +138,7 @@ pub fn single_world_breakdown_embed(
         sorted_teams.sort_by_key(|t| t.team_id);
 
         for team in sorted_teams {
-            let icon = Icons::try_from(team.team_id)
-                .unwrap_or(Icons::Ps2White)
-                .to_discord_emoji();
-
-            let team_icon: String = match icon {
-                Some(emoji) => emoji.to_string(),
-                None => team.team_id.to_string(),
-            };
+            let team_icon = faction_icon(team.team_id);
 
             let percentage = safe_percentage(team.team_population, zone.zone_population);
 
@@ -155,24 +150,11 @@ pub fn single_world_breakdown_embed(
 
         breakdown = format!("{}Total: {}\n", breakdown, zone.zone_population);
 
-        #[allow(clippy::cast_sign_loss)]
-        let zone_name = full_zone_data.as_ref().map_or_else(
-            || zone.zone_id.to_string(),
-            |zones| {
-                zones
-                    .iter()
-                    .find(|z| z.id as u32 == zone.zone_id.0)
-                    .map_or_else(
-                        || zone.zone_id.to_string(),
-                        |z| {
-                            z.name.as_ref().map_or_else(
-                                || zone.zone_id.to_string(),
-                                |name| name.en.clone().unwrap_or_else(|| zone.zone_id.to_string()),
-                            )
-                        },
-                    )
-            },
-        );
+        if let Some(instances) = &zone.instances {
+            breakdown = format!("{}Instances: {}\n", breakdown, instances.len());
+        }
+
+        let zone_name = zone_name(zone.zone_id, full_zone_data);
 
         let main_continent = [2, 4, 6, 8, 10, 344].contains(&zone.zone_id.0);
 
@@ -187,3 +169,91 @@ pub fn single_world_breakdown_embed(
 
     embed
 }
+
+fn faction_icon(faction: Faction) -> String {
+    let icon = Icons::try_from(faction)
+        .unwrap_or(Icons::Ps2White)
+        .to_discord_emoji();
+
+    icon.map_or_else(|| faction.to_string(), |emoji| emoji.to_string())
+}
+
+#[allow(clippy::ref_option)]
+fn zone_name(zone_id: DefinitionID, full_zone_data: &Option<Vec<Zone>>) -> String {
+    // Players that logged in but haven't earned any XP yet have no known zone
+    if zone_id == DefinitionID::UNKNOWN {
+        return "Unknown zone".to_string();
+    }
+
+    full_zone_data
+        .as_ref()
+        .and_then(|zones| zones.iter().find(|z| z.id == i32::from(zone_id.0)))
+        .and_then(|z| z.name.as_ref())
+        .and_then(|name| name.en.clone())
+        .unwrap_or_else(|| zone_id.to_string())
+}
+
+#[allow(clippy::ref_option)]
+pub fn continent_status_embed(
+    world_id: WorldID,
+    continents: &[ContinentStatus],
+    alerts: &[Alert],
+    full_zone_data: &Option<Vec<Zone>>,
+) -> CreateEmbed {
+    let mut open = String::new();
+    let mut locked = String::new();
+
+    for continent in continents {
+        let name = zone_name(continent.zone_id, full_zone_data);
+        if continent.locked {
+            locked = format!(
+                "{}{} {}\n",
+                locked,
+                faction_icon(continent.triggering_faction),
+                name
+            );
+        } else {
+            open = format!("{open}{name}\n");
+        }
+    }
+
+    let mut embed = CreateEmbed::default()
+        .thumbnail("https://www.planetside2.com/images/ps2-logo.png")
+        .title(format!("{world_id} Continents"))
+        .description("Based on the continent lock and alert events seen since tracking started.")
+        .field("Open", none_if_empty(open), true)
+        .field("Locked", none_if_empty(locked), true);
+
+    for alert in alerts {
+        let started = alert.started_at.map_or_else(String::new, |started_at| {
+            format!("Started <t:{}:R>\n", started_at.and_utc().timestamp())
+        });
+
+        let status = format!(
+            "{}: {:.1}%\n{}: {:.1}%\n{}: {:.1}%\n{}",
+            faction_icon(Faction::VS),
+            alert.faction_vs,
+            faction_icon(Faction::NC),
+            alert.faction_nc,
+            faction_icon(Faction::TR),
+            alert.faction_tr,
+            started
+        );
+
+        embed = embed.field(
+            format!("Alert on {}", zone_name(alert.zone_id, full_zone_data)),
+            status,
+            false,
+        );
+    }
+
+    add_timestamp_to_embed(embed, Utc::now())
+}
+
+fn none_if_empty(value: String) -> String {
+    if value.is_empty() {
+        "None".to_string()
+    } else {
+        value
+    }
+}
@@ -7,7 +7,7 @@ mod updaters;
 
 use crate::census::rest::client::CensusRestClient;
 use crate::discord::updaters::Updater;
-use crate::storage::configuration::{DiscordCalendarConfig, GoogleConfig};
+use crate::storage::configuration::{DiscordCalendarConfig, GoogleConfig, PopulationLayout};
 use poise::serenity_prelude as serenity;
 use poise::serenity_prelude::FullEvent;
 use poise::FrameworkBuilder;
@@ -20,6 +20,7 @@ use tracing::error;
 pub struct Data {
     #[cfg(feature = "database")]
     pub(crate) db_pool: PgPool,
+    pub(crate) population_layout: PopulationLayout,
     pub(crate) google: GoogleConfig,
     pub(crate) calendar: Vec<DiscordCalendarConfig>,
     #[cfg(feature = "census")]
@@ -78,6 +79,8 @@ pub fn init() -> FrameworkBuilder<Data, Error> {
             #[cfg(feature = "census")]
             commands::census::population(),
             #[cfg(feature = "census")]
+            commands::census::continents(),
+            #[cfg(feature = "census")]
             commands::membership_reminder::dailyloginreminder(),
         ],
         event_handler: |ctx, event, framework, data| {
@@ -0,0 +1,26 @@
+use metrics::counter;
+
+pub fn handle() {
+    counter!("niumside_achievement_earned_events").increment(1);
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::event::Event;
+    use crate::event_handlers::counter_increments;
+
+    const PAYLOAD: &str = r#"{"achievement_id":"90185","character_id":"5429573939285739921","event_name":"AchievementEarned","timestamp":"1728117291","world_id":"10","zone_id":"2"}"#;
+
+    #[test]
+    fn test_achievement_earned() {
+        let event: Event = serde_json::from_str(PAYLOAD).unwrap();
+        assert_eq!(event, Event::AchievementEarned);
+
+        assert_eq!(
+            counter_increments("niumside_achievement_earned_events", handle),
+            1
+        );
+    }
+}
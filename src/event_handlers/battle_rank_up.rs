@@ -0,0 +1,26 @@
+use metrics::counter;
+
+pub fn handle() {
+    counter!("niumside_battle_rank_up_events").increment(1);
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::event::Event;
+    use crate::event_handlers::counter_increments;
+
+    const PAYLOAD: &str = r#"{"battle_rank":"42","character_id":"5429573939285739921","event_name":"BattleRankUp","timestamp":"1728117291","world_id":"10","zone_id":"2"}"#;
+
+    #[test]
+    fn test_battle_rank_up() {
+        let event: Event = serde_json::from_str(PAYLOAD).unwrap();
+        assert_eq!(event, Event::BattleRankUp);
+
+        assert_eq!(
+            counter_increments("niumside_battle_rank_up_events", handle),
+            1
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use metrics::counter;
+use tracing::debug;
+
+use crate::census::event::ContinentLock;
+use crate::metagame::{self, ContinentChange, MetagameSender, MetagameUpdate};
+
+pub fn handle(event: &ContinentLock, metagame: &MetagameSender) {
+    debug!(
+        "Zone {} on {} locked by {}",
+        event.zone_id, event.world_id, event.triggering_faction
+    );
+    counter!(
+        "niumside_continent_lock_events",
+        "world" => event.world_id.to_string(),
+        "zone" => event.zone_id.to_string()
+    )
+    .increment(1);
+
+    metagame::send(
+        metagame,
+        MetagameUpdate::Continent(ContinentChange::from(event)),
+    );
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::constants::{Faction, WorldID, ZoneID};
+    use crate::census::event::Event;
+    use tokio::sync::mpsc;
+
+    const PAYLOAD: &str = r#"{"event_name":"ContinentLock","metagame_event_id":"147","nc_population":"34","previous_faction":"2","timestamp":"1728117291","tr_population":"29","triggering_faction":"1","vs_population":"37","world_id":"10","zone_id":"8"}"#;
+
+    #[test]
+    fn test_continent_lock() {
+        let Event::ContinentLock(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        assert_eq!(event.world_id, WorldID::Miller);
+        assert_eq!(event.zone_id, ZoneID(8));
+        assert_eq!(event.triggering_faction, Faction::VS);
+        assert_eq!(event.previous_faction, Faction::NC);
+        assert_eq!(event.vs_population, 37);
+        assert_eq!(event.metagame_event_id, 147);
+
+        let (metagame, mut receiver) = mpsc::unbounded_channel();
+        handle(&event, &metagame);
+
+        let MetagameUpdate::Continent(change) = receiver.try_recv().unwrap() else {
+            panic!("Unexpected metagame update");
+        };
+        assert_eq!(change.zone_id, event.zone_id.definition());
+        assert!(change.locked);
+    }
+}
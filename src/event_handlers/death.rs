@@ -0,0 +1,114 @@
+use metrics::counter;
+
+use crate::active_players::{ActivePlayer, ActivePlayerDb};
+use crate::census::constants::Loadout;
+use crate::census::event::Death;
+use crate::kill_stats::{self, KillStatsDb};
+
+pub fn handle(event: &Death, active_players: &ActivePlayerDb, kill_stats: &KillStatsDb) {
+    // Deaths caused by the environment or suicides by redeploying have no attacker
+    if event.attacker_character_id != 0 && event.attacker_loadout_id != Loadout::Unknown {
+        active_players.upsert(
+            event.attacker_character_id,
+            ActivePlayer::from_loadout(
+                event.world_id,
+                event.zone_id,
+                event.attacker_loadout_id,
+                event.timestamp,
+            ),
+        );
+        active_players.set_vehicle(
+            event.attacker_character_id,
+            (event.attacker_vehicle_id != 0).then_some(event.attacker_vehicle_id),
+            event.timestamp,
+        );
+    }
+
+    // The victim respawns on foot
+    active_players.upsert(
+        event.character_id,
+        ActivePlayer::from_loadout(
+            event.world_id,
+            event.zone_id,
+            event.character_loadout_id,
+            event.timestamp,
+        ),
+    );
+    active_players.set_vehicle(event.character_id, None, event.timestamp);
+    kill_stats::update(kill_stats, |kill_stats| kill_stats.record(event));
+    counter!("niumside_death_events").increment(1);
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::store::ShardedActivePlayers;
+    use crate::census::constants::{Faction, WorldID, ZoneID};
+    use crate::census::event::Event;
+    use std::sync::Arc;
+
+    const PAYLOAD: &str = r#"{"attacker_character_id":"5428010618015189713","attacker_fire_mode_id":"7401","attacker_loadout_id":"6","attacker_team_id":"2","attacker_vehicle_id":"0","attacker_weapon_id":"7169","character_id":"5429573939285739921","character_loadout_id":"20","event_name":"Death","is_critical":"0","is_headshot":"1","team_id":"1","timestamp":"1728117291","vehicle_id":"0","world_id":"10","zone_id":"2"}"#;
+
+    #[test]
+    fn test_death_tracks_attacker_and_victim() {
+        let Event::Death(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let kill_stats = KillStatsDb::default();
+
+        handle(&event, &active_players, &kill_stats);
+
+        assert_eq!(active_players.len(), 2);
+        assert_eq!(kill_stats.lock().unwrap().counts.len(), 2);
+
+        let attacker = active_players.get(5_428_010_618_015_189_713).unwrap();
+        assert_eq!(attacker.loadout, Loadout::NCHeavyAssault);
+        assert_eq!(attacker.team_id, Faction::NC);
+        assert_eq!(attacker.world, WorldID::Miller);
+        assert_eq!(attacker.zone, ZoneID(2));
+
+        let victim = active_players.get(5_429_573_939_285_739_921).unwrap();
+        assert_eq!(victim.loadout, Loadout::VSHeavyAssault);
+        assert_eq!(victim.team_id, Faction::VS);
+        assert_eq!(victim.vehicle, None);
+    }
+
+    #[test]
+    fn test_death_tracks_attacker_vehicle() {
+        let payload = PAYLOAD.replace(
+            r#""attacker_vehicle_id":"0""#,
+            r#""attacker_vehicle_id":"5""#,
+        );
+        let Event::Death(event) = serde_json::from_str(&payload).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let kill_stats = KillStatsDb::default();
+
+        handle(&event, &active_players, &kill_stats);
+
+        let attacker = active_players.get(5_428_010_618_015_189_713).unwrap();
+        assert_eq!(attacker.vehicle.unwrap().vehicle_id, 5);
+        assert_eq!(active_players.vehicle_counts().len(), 1);
+    }
+
+    #[test]
+    fn test_death_without_attacker() {
+        let payload = PAYLOAD.replace("5428010618015189713", "0").replace(
+            r#""attacker_loadout_id":"6""#,
+            r#""attacker_loadout_id":"0""#,
+        );
+        let Event::Death(event) = serde_json::from_str(&payload).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let kill_stats = KillStatsDb::default();
+
+        handle(&event, &active_players, &kill_stats);
+
+        assert_eq!(active_players.len(), 1);
+        assert!(active_players.get(5_429_573_939_285_739_921).is_some());
+    }
+}
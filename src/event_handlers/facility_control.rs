@@ -0,0 +1,74 @@
+use metrics::counter;
+use tracing::debug;
+
+use crate::census::event::FacilityControl;
+use crate::census::territory::{self, TerritoryDb};
+use crate::metagame::{self, FacilityChange, MetagameSender, MetagameUpdate};
+
+pub fn handle(event: &FacilityControl, territory: &TerritoryDb, metagame: &MetagameSender) {
+    debug!(
+        "Facility {} on {} changed from {} to {}",
+        event.facility_id, event.world_id, event.old_faction_id, event.new_faction_id
+    );
+    counter!(
+        "niumside_facility_control_events",
+        "world" => event.world_id.to_string(),
+        "zone" => event.zone_id.to_string()
+    )
+    .increment(1);
+
+    let change = FacilityChange::from(event);
+    territory::update(territory, |territory| {
+        territory.capture(
+            change.world_id,
+            change.zone_id,
+            change.facility_id,
+            change.new_faction,
+        )
+    });
+    metagame::send(metagame, MetagameUpdate::Facility(change));
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::constants::{DefinitionID, Faction, WorldID, ZoneID};
+    use crate::census::event::Event;
+    use crate::census::territory::Territory;
+    use chrono::Duration;
+    use std::sync::{Arc, Mutex};
+    use tokio::sync::mpsc;
+
+    const PAYLOAD: &str = r#"{"duration_held":"3412","event_name":"FacilityControl","facility_id":"222280","new_faction_id":"3","old_faction_id":"2","outfit_id":"37570391403474491","timestamp":"1728117291","world_id":"10","zone_id":"2"}"#;
+
+    #[test]
+    fn test_facility_control() {
+        let Event::FacilityControl(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        assert_eq!(event.duration_held, Duration::seconds(3412));
+        assert_eq!(event.facility_id, 222_280);
+        assert_eq!(event.new_faction_id, Faction::TR);
+        assert_eq!(event.old_faction_id, Faction::NC);
+        assert_eq!(event.outfit_id, 37_570_391_403_474_491);
+        assert_eq!(event.world_id, WorldID::Miller);
+        assert_eq!(event.zone_id, ZoneID(2));
+
+        let territory = Arc::new(Mutex::new(Territory::default()));
+        let (metagame, mut receiver) = mpsc::unbounded_channel();
+        handle(&event, &territory, &metagame);
+
+        let zone_territory = territory
+            .lock()
+            .unwrap()
+            .zone_territory(WorldID::Miller, DefinitionID(2))
+            .unwrap();
+        assert!((zone_territory.tr - 100.0).abs() < f32::EPSILON);
+
+        let MetagameUpdate::Facility(change) = receiver.try_recv().unwrap() else {
+            panic!("Unexpected metagame update");
+        };
+        assert_eq!(change.outfit_id, Some(37_570_391_403_474_491));
+    }
+}
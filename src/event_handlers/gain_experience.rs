@@ -1,27 +1,107 @@
 use metrics::counter;
-use tracing::error;
 
-use crate::active_players::{ActivePlayer, ActivePlayerDb};
+use crate::active_players::{ActivePlayer, ActivePlayerDb, Vehicle, VehicleExperience};
 use crate::census::event::GainExperience;
+use crate::character_sessions::{self, SessionEvent, SessionSender};
 
-pub fn handle(event: &GainExperience, active_players: &ActivePlayerDb) {
-    active_players.lock().map_or_else(
-        |_| {
-            counter!("niumside_active_players_lock_failed").increment(1);
-            error!("Unable to lock active players");
+pub fn handle(
+    event: &GainExperience,
+    active_players: &ActivePlayerDb,
+    character_sessions: &SessionSender,
+    vehicle_experience: &VehicleExperience,
+) {
+    character_sessions::send(
+        character_sessions,
+        SessionEvent::Activity {
+            character_id: event.character_id,
+            world_id: event.world_id,
+            timestamp: event.timestamp,
         },
-        |mut active_players_lock| {
-            active_players_lock.insert(
-                event.character_id,
-                ActivePlayer {
-                    zone: event.zone_id,
-                    loadout: event.loadout_id,
-                    world: event.world_id,
-                    last_change: event.timestamp,
-                    team_id: event.team_id,
-                },
-            );
-            counter!("niumside_gain_experience_events").increment(1);
+    );
+    active_players.upsert(
+        event.character_id,
+        ActivePlayer {
+            zone: event.zone_id,
+            loadout: event.loadout_id,
+            world: event.world_id,
+            last_change: event.timestamp,
+            team_id: event.team_id,
+            logged_in: false,
+            // Other experience doesn't tell whether the player is in a vehicle, so the vehicle
+            // they were last seen in is kept
+            vehicle: vehicle_experience
+                .get(&event.experience_id)
+                .map(|vehicle_id| Vehicle {
+                    vehicle_id: *vehicle_id,
+                    last_seen: event.timestamp,
+                }),
+            outfit: None,
         },
     );
+    counter!("niumside_gain_experience_events").increment(1);
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::store::ShardedActivePlayers;
+    use crate::census::constants::{Faction, Loadout, WorldID, ZoneID};
+    use crate::census::event::Event;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    const PAYLOAD: &str = r#"{"amount":"28","character_id":"5429573939285739921","event_name":"GainExperience","experience_id":"140","loadout_id":"20","other_id":"34360508066","team_id":"1","timestamp":"1728117291","world_id":"13","zone_id":"8"}"#;
+
+    #[test]
+    fn test_gain_experience_tracks_player() {
+        let Event::GainExperience(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let (character_sessions, mut receiver) = mpsc::unbounded_channel();
+
+        handle(
+            &event,
+            &active_players,
+            &character_sessions,
+            &VehicleExperience::new(),
+        );
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            SessionEvent::Activity {
+                character_id: event.character_id,
+                world_id: event.world_id,
+                timestamp: event.timestamp,
+            }
+        );
+
+        let player = active_players.get(5_429_573_939_285_739_921).unwrap();
+        assert_eq!(player.world, WorldID::Cobalt);
+        assert_eq!(player.zone, ZoneID(8));
+        assert_eq!(player.loadout, Loadout::VSHeavyAssault);
+        assert_eq!(player.team_id, Faction::VS);
+        assert_eq!(player.last_change, event.timestamp);
+        assert_eq!(player.vehicle, None);
+    }
+
+    #[test]
+    fn test_gain_experience_tracks_vehicle() {
+        let Event::GainExperience(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let (character_sessions, _receiver) = mpsc::unbounded_channel();
+        let vehicle_experience = VehicleExperience::from([(140, 4)]);
+
+        handle(
+            &event,
+            &active_players,
+            &character_sessions,
+            &vehicle_experience,
+        );
+
+        let player = active_players.get(5_429_573_939_285_739_921).unwrap();
+        assert_eq!(player.vehicle.unwrap().vehicle_id, 4);
+    }
 }
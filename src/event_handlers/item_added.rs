@@ -0,0 +1,23 @@
+use metrics::counter;
+
+pub fn handle() {
+    counter!("niumside_item_added_events").increment(1);
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::event::Event;
+    use crate::event_handlers::counter_increments;
+
+    const PAYLOAD: &str = r#"{"character_id":"5429573939285739921","context":"GuildBankWithdrawal","event_name":"ItemAdded","item_count":"1","item_id":"6004214","timestamp":"1728117291","world_id":"10","zone_id":"2"}"#;
+
+    #[test]
+    fn test_item_added() {
+        let event: Event = serde_json::from_str(PAYLOAD).unwrap();
+        assert_eq!(event, Event::ItemAdded);
+
+        assert_eq!(counter_increments("niumside_item_added_events", handle), 1);
+    }
+}
@@ -0,0 +1,62 @@
+use metrics::counter;
+use tracing::{debug, warn};
+
+use crate::census::event::MetagameEvent;
+use crate::metagame::{self, AlertChange, MetagameSender, MetagameUpdate};
+
+pub fn handle(event: &MetagameEvent, metagame: &MetagameSender) {
+    debug!(
+        "Metagame event {} on {} zone {} changed state to {}",
+        event.metagame_event_id, event.world_id, event.zone_id, event.metagame_event_state_name
+    );
+    counter!(
+        "niumside_metagame_event_events",
+        "world" => event.world_id.to_string(),
+        "state" => event.metagame_event_state_name.clone()
+    )
+    .increment(1);
+
+    let Some(change) = AlertChange::from_event(event) else {
+        warn!(
+            "Unknown metagame event state {} ({})",
+            event.metagame_event_state, event.metagame_event_state_name
+        );
+        return;
+    };
+
+    metagame::send(metagame, MetagameUpdate::Alert(change));
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::constants::MetagameEventState;
+    use crate::census::constants::{WorldID, ZoneID};
+    use crate::census::event::Event;
+    use tokio::sync::mpsc;
+
+    const PAYLOAD: &str = r#"{"event_name":"MetagameEvent","experience_bonus":"25.000000","faction_nc":"33.725490","faction_tr":"24.705883","faction_vs":"41.176472","instance_id":"23469","metagame_event_id":"147","metagame_event_state":"135","metagame_event_state_name":"started","timestamp":"1728117291","world_id":"10","zone_id":"2"}"#;
+
+    #[test]
+    fn test_metagame_event() {
+        let Event::MetagameEvent(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        assert_eq!(event.world_id, WorldID::Miller);
+        assert_eq!(event.zone_id, ZoneID(2));
+        assert_eq!(event.instance_id, 23_469);
+        assert_eq!(event.metagame_event_id, 147);
+        assert_eq!(event.metagame_event_state, 135);
+        assert_eq!(event.metagame_event_state_name, "started");
+
+        let (metagame, mut receiver) = mpsc::unbounded_channel();
+        handle(&event, &metagame);
+
+        let MetagameUpdate::Alert(change) = receiver.try_recv().unwrap() else {
+            panic!("Unexpected metagame update");
+        };
+        assert_eq!(change.state, MetagameEventState::Started);
+        assert_eq!(change.instance_id, 23_469);
+    }
+}
@@ -1,8 +1,26 @@
+pub mod achievement_earned;
+pub mod battle_rank_up;
+pub mod continent_lock;
+pub mod continent_unlock;
+pub mod death;
+pub mod facility_control;
 pub mod gain_experience;
+pub mod item_added;
+pub mod metagame_event;
+pub mod pipeline;
+pub mod player_facility_capture;
+pub mod player_facility_defend;
+pub mod player_login;
+pub mod player_logout;
+pub mod skill_added;
+pub mod vehicle_destroy;
 
-use crate::active_players::ActivePlayerDb;
-use crate::census::event::Event;
-use tracing::error;
+use crate::active_players::{ActivePlayerDb, VehicleExperience};
+use crate::census::event::{Event, EventNames};
+use crate::census::territory::TerritoryDb;
+use crate::character_sessions::SessionSender;
+use crate::kill_stats::KillStatsDb;
+use crate::metagame::MetagameSender;
 
 #[derive(thiserror::Error, Debug)]
 pub enum EventHandlerErrors {
@@ -10,24 +28,119 @@ pub enum EventHandlerErrors {
     SqlxError(#[from] sqlx::Error),
 }
 
-pub fn receive_events(event: Event, active_players: &ActivePlayerDb) {
-    match event {
-        Event::GainExperience(event) => {
-            gain_experience::handle(&event, active_players);
+/// Receives every event from the realtime stream, registered in `pipeline::EventHandlers`
+pub trait EventHandler: Send + Sync {
+    fn handle(&self, event: &Event);
+}
+
+/// Keeps the active players and character sessions up to date, which the population is based on,
+/// and counts the kills and deaths that complement it
+pub struct PopulationTracker {
+    pub active_players: ActivePlayerDb,
+    pub character_sessions: SessionSender,
+    pub kill_stats: KillStatsDb,
+    pub vehicle_experience: VehicleExperience,
+}
+
+impl PopulationTracker {
+    /// The events the population and kill statistics are based on. Deaths and destroyed vehicles
+    /// also tell which vehicle a player is in.
+    pub const EVENT_NAMES: &'static [EventNames] = &[
+        EventNames::GainExperience,
+        EventNames::PlayerLogin,
+        EventNames::PlayerLogout,
+        EventNames::Death,
+        EventNames::VehicleDestroy,
+    ];
+}
+
+impl EventHandler for PopulationTracker {
+    fn handle(&self, event: &Event) {
+        let active_players = &self.active_players;
+        let character_sessions = &self.character_sessions;
+
+        match event {
+            Event::GainExperience(event) => {
+                gain_experience::handle(
+                    event,
+                    active_players,
+                    character_sessions,
+                    &self.vehicle_experience,
+                );
+            }
+            Event::PlayerLogin(event) => {
+                player_login::handle(event, active_players, character_sessions);
+            }
+            Event::PlayerLogout(event) => {
+                player_logout::handle(event, active_players, character_sessions);
+            }
+            Event::Death(event) => death::handle(event, active_players, &self.kill_stats),
+            Event::VehicleDestroy(event) => vehicle_destroy::handle(event, active_players),
+            Event::PlayerFacilityCapture(event) => {
+                player_facility_capture::handle(event, active_players, character_sessions);
+            }
+            Event::PlayerFacilityDefend(event) => {
+                player_facility_defend::handle(event, active_players, character_sessions);
+            }
+            // Handled by `MetagameTracker`, unknown events are counted when they are parsed
+            Event::ContinentLock(_)
+            | Event::ContinentUnlock(_)
+            | Event::MetagameEvent(_)
+            | Event::FacilityControl(_)
+            | Event::Unknown => {}
+            Event::ItemAdded => item_added::handle(),
+            Event::AchievementEarned => achievement_earned::handle(),
+            Event::SkillAdded => skill_added::handle(),
+            Event::BattleRankUp => battle_rank_up::handle(),
+        }
+    }
+}
+
+/// Records continents locking and unlocking, alerts starting and ending and facilities changing owner
+pub struct MetagameTracker {
+    pub metagame: MetagameSender,
+    pub territory: TerritoryDb,
+}
+
+impl MetagameTracker {
+    pub const EVENT_NAMES: &'static [EventNames] = &[
+        EventNames::ContinentLock,
+        EventNames::ContinentUnlock,
+        EventNames::MetagameEvent,
+        EventNames::FacilityControl,
+    ];
+}
+
+impl EventHandler for MetagameTracker {
+    fn handle(&self, event: &Event) {
+        match event {
+            Event::ContinentLock(event) => continent_lock::handle(event, &self.metagame),
+            Event::ContinentUnlock(event) => continent_unlock::handle(event, &self.metagame),
+            Event::MetagameEvent(event) => metagame_event::handle(event, &self.metagame),
+            Event::FacilityControl(event) => {
+                facility_control::handle(event, &self.territory, &self.metagame);
+            }
+            _ => {}
         }
-        Event::PlayerLogin(_) => todo!(),
-        Event::PlayerLogout(_) => todo!(),
-        Event::Death(_) => todo!(),
-        Event::VehicleDestroy(_) => todo!(),
-        Event::PlayerFacilityCapture(_) => todo!(),
-        Event::PlayerFacilityDefend(_) => todo!(),
-        Event::ContinentLock(_) => todo!(),
-        Event::ContinentUnlock(_) => todo!(),
-        Event::FacilityControl(_) => todo!(),
-        Event::MetagameEvent(_) => todo!(),
-        Event::ItemAdded => todo!(),
-        Event::AchievementEarned => todo!(),
-        Event::SkillAdded => todo!(),
-        Event::BattleRankUp => todo!(),
-    };
+    }
+}
+
+/// How much a handler increments a counter, recorded by a recorder local to the test
+#[cfg(test)]
+pub fn counter_increments(name: &str, handle: impl FnOnce()) -> u64 {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    metrics::with_local_recorder(&recorder, handle);
+
+    snapshotter
+        .snapshot()
+        .into_vec()
+        .into_iter()
+        .find_map(|(key, _, _, value)| match value {
+            DebugValue::Counter(count) if key.key().name() == name => Some(count),
+            _ => None,
+        })
+        .unwrap_or(0)
 }
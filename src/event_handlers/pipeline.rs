@@ -0,0 +1,185 @@
+use crate::census::constants::WorldID;
+use crate::census::event::Event;
+use crate::event_handlers::EventHandler;
+use metrics::{counter, gauge};
+use std::sync::Arc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+
+/// Events that can wait for a handler before new events are dropped
+pub const EVENT_QUEUE_CAPACITY: usize = 10_000;
+
+/// The handlers that receive every event from the realtime stream, in order of registration
+#[derive(Default)]
+pub struct EventHandlers {
+    handlers: Vec<Box<dyn EventHandler>>,
+}
+
+impl EventHandlers {
+    #[must_use]
+    pub fn register(mut self, handler: impl EventHandler + 'static) -> Self {
+        self.handlers.push(Box::new(handler));
+        self
+    }
+
+    /// Events of worlds that aren't known yet can't be attributed to a world and are skipped, the
+    /// unknown world was already counted when the event was parsed
+    pub fn handle(&self, event: &Event) {
+        if event.world_id() == Some(WorldID::Unknown) {
+            return;
+        }
+
+        for handler in &self.handlers {
+            handler.handle(event);
+        }
+    }
+}
+
+/// The sending side of the event queue, events are dropped instead of waiting when it is full
+#[derive(Debug, Clone)]
+pub struct EventPipeline {
+    sender: Sender<Event>,
+}
+
+impl EventPipeline {
+    pub fn new(capacity: usize) -> (Self, Receiver<Event>) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        (Self { sender }, receiver)
+    }
+
+    /// Queue an event for the handlers, returns whether the event was queued
+    pub fn send(&self, event: Event) -> bool {
+        let queued = match self.sender.try_send(event) {
+            Ok(()) => true,
+            Err(TrySendError::Full(event)) => {
+                counter!("niumside_events_dropped", "event" => event.to_string()).increment(1);
+                warn!("Event queue is full, dropping {event} event");
+                false
+            }
+            Err(TrySendError::Closed(_)) => {
+                counter!("niumside_events_dropped", "event" => "closed").increment(1);
+                error!("Event queue is closed, no event handlers are running");
+                false
+            }
+        };
+
+        self.report_depth();
+        queued
+    }
+
+    pub fn depth(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    fn report_depth(&self) {
+        #[allow(clippy::cast_precision_loss)]
+        gauge!("niumside_event_queue_depth").set(self.depth() as f64);
+    }
+}
+
+/// Start the tasks that take events from the queue and pass them to the handlers
+///
+/// # Arguments
+///
+/// * `receiver` - The receiving side of the event queue
+/// * `handlers` - The handlers every event is passed to
+/// * `workers` - The amount of tasks handling events concurrently
+pub fn spawn_workers(receiver: Receiver<Event>, handlers: EventHandlers, workers: usize) {
+    let receiver = Arc::new(Mutex::new(receiver));
+    let handlers = Arc::new(handlers);
+
+    for _ in 0..workers.max(1) {
+        let receiver = receiver.clone();
+        let handlers = handlers.clone();
+        tokio::spawn(async move { worker(&receiver, &handlers).await });
+    }
+}
+
+async fn worker(receiver: &Mutex<Receiver<Event>>, handlers: &EventHandlers) {
+    loop {
+        let Some(event) = receiver.lock().await.recv().await else {
+            return;
+        };
+
+        handlers.handle(&event);
+        counter!("niumside_events_handled").increment(1);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    struct CountingHandler(Arc<AtomicUsize>);
+
+    impl EventHandler for CountingHandler {
+        fn handle(&self, _event: &Event) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_full_queue_drops_events() {
+        let (pipeline, _receiver) = EventPipeline::new(1);
+
+        assert!(pipeline.send(Event::BattleRankUp));
+        assert!(!pipeline.send(Event::BattleRankUp));
+        assert_eq!(pipeline.depth(), 1);
+    }
+
+    #[test]
+    fn test_closed_queue_drops_events() {
+        let (pipeline, receiver) = EventPipeline::new(1);
+        drop(receiver);
+
+        assert!(!pipeline.send(Event::BattleRankUp));
+    }
+
+    #[tokio::test]
+    async fn test_workers_pass_events_to_every_handler() {
+        let first = Arc::new(AtomicUsize::new(0));
+        let second = Arc::new(AtomicUsize::new(0));
+        let handlers = EventHandlers::default()
+            .register(CountingHandler(first.clone()))
+            .register(CountingHandler(second.clone()));
+
+        let (pipeline, receiver) = EventPipeline::new(EVENT_QUEUE_CAPACITY);
+        spawn_workers(receiver, handlers, 2);
+
+        for _ in 0..10 {
+            assert!(pipeline.send(Event::SkillAdded));
+        }
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while second.load(Ordering::SeqCst) < 10 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(first.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn test_events_of_unknown_worlds_are_skipped() {
+        let handled = Arc::new(AtomicUsize::new(0));
+        let handlers = EventHandlers::default().register(CountingHandler(handled.clone()));
+        let login: Event = serde_json::from_str(r#"{"character_id":"5429573939285739921","event_name":"PlayerLogin","timestamp":"1728117291","world_id":"10"}"#).unwrap();
+        let Event::PlayerLogin(mut unknown_world) = login.clone() else {
+            panic!("Expected a PlayerLogin event");
+        };
+        unknown_world.world_id = WorldID::Unknown;
+
+        handlers.handle(&login);
+        handlers.handle(&Event::PlayerLogin(unknown_world));
+        handlers.handle(&Event::SkillAdded);
+
+        assert_eq!(handled.load(Ordering::SeqCst), 2);
+    }
+}
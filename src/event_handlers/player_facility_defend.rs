@@ -0,0 +1,89 @@
+use metrics::counter;
+
+use crate::active_players::ActivePlayerDb;
+use crate::census::event::PlayerFacilityDefend;
+use crate::character_sessions::{self, SessionEvent, SessionSender};
+
+pub fn handle(
+    event: &PlayerFacilityDefend,
+    active_players: &ActivePlayerDb,
+    character_sessions: &SessionSender,
+) {
+    character_sessions::send(
+        character_sessions,
+        SessionEvent::Outfit {
+            character_id: event.character_id,
+            outfit_id: event.outfit_id,
+        },
+    );
+    // The event doesn't include a loadout, so only refresh players that are already tracked
+    active_players.refresh(
+        event.character_id,
+        event.world_id,
+        event.zone_id,
+        event.timestamp,
+    );
+    counter!("niumside_player_facility_defend_events").increment(1);
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::store::ShardedActivePlayers;
+    use crate::active_players::ActivePlayer;
+    use crate::census::constants::{Loadout, WorldID, ZoneID};
+    use crate::census::event::Event;
+    use chrono::DateTime;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    const PAYLOAD: &str = r#"{"character_id":"5429573939285739921","event_name":"PlayerFacilityDefend","facility_id":"222280","outfit_id":"37570391403474491","timestamp":"1728117291","world_id":"10","zone_id":"6"}"#;
+
+    #[test]
+    fn test_player_facility_defend_refreshes_tracked_player() {
+        let Event::PlayerFacilityDefend(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        assert_eq!(event.outfit_id, 37_570_391_403_474_491);
+
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let (character_sessions, mut receiver) = mpsc::unbounded_channel();
+        active_players.upsert(
+            5_429_573_939_285_739_921,
+            ActivePlayer::from_loadout(
+                WorldID::Miller,
+                ZoneID(2),
+                Loadout::VSMedic,
+                DateTime::from_timestamp(1_728_117_000, 0).unwrap(),
+            ),
+        );
+
+        handle(&event, &active_players, &character_sessions);
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            SessionEvent::Outfit {
+                character_id: event.character_id,
+                outfit_id: event.outfit_id,
+            }
+        );
+
+        let player = active_players.get(5_429_573_939_285_739_921).unwrap();
+        assert_eq!(player.loadout, Loadout::VSMedic);
+        assert_eq!(player.zone, ZoneID(6));
+        assert_eq!(player.last_change, event.timestamp);
+    }
+
+    #[test]
+    fn test_player_facility_defend_ignores_untracked_player() {
+        let Event::PlayerFacilityDefend(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let (character_sessions, _receiver) = mpsc::unbounded_channel();
+
+        handle(&event, &active_players, &character_sessions);
+
+        assert!(active_players.is_empty());
+    }
+}
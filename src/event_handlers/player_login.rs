@@ -0,0 +1,99 @@
+use metrics::counter;
+use tracing::debug;
+
+use crate::active_players::ActivePlayerDb;
+use crate::census::event::PlayerLogin;
+use crate::character_sessions::{self, SessionEvent, SessionSender};
+
+pub fn handle(
+    event: &PlayerLogin,
+    active_players: &ActivePlayerDb,
+    character_sessions: &SessionSender,
+) {
+    debug!(
+        "Character {} logged in on {}",
+        event.character_id, event.world_id
+    );
+    character_sessions::send(
+        character_sessions,
+        SessionEvent::Login {
+            character_id: event.character_id,
+            world_id: event.world_id,
+            timestamp: event.timestamp,
+        },
+    );
+    // Keeps the zone and loadout if the player was already seen earning experience
+    active_players.login(event.character_id, event.world_id, event.timestamp);
+    counter!("niumside_player_login_events", "world" => event.world_id.to_string()).increment(1);
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::store::ShardedActivePlayers;
+    use crate::active_players::ActivePlayer;
+    use crate::census::constants::{Faction, Loadout, WorldID, ZoneID};
+    use crate::census::event::Event;
+    use chrono::DateTime;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    const PAYLOAD: &str = r#"{"character_id":"5429573939285739921","event_name":"PlayerLogin","timestamp":"1728117291","world_id":"17"}"#;
+
+    #[test]
+    fn test_player_login_tracks_player() {
+        let Event::PlayerLogin(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        assert_eq!(
+            event.timestamp,
+            DateTime::from_timestamp(1_728_117_291, 0).unwrap()
+        );
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let (character_sessions, mut receiver) = mpsc::unbounded_channel();
+
+        handle(&event, &active_players, &character_sessions);
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            SessionEvent::Login {
+                character_id: event.character_id,
+                world_id: event.world_id,
+                timestamp: event.timestamp,
+            }
+        );
+
+        let player = active_players.get(5_429_573_939_285_739_921).unwrap();
+        assert!(player.logged_in);
+        assert_eq!(player.world, WorldID::Emerald);
+        assert_eq!(player.zone, ZoneID::UNKNOWN);
+        assert_eq!(player.loadout, Loadout::Unknown);
+        assert_eq!(player.team_id, Faction::Unknown);
+    }
+
+    #[test]
+    fn test_player_login_keeps_known_loadout() {
+        let Event::PlayerLogin(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let (character_sessions, _receiver) = mpsc::unbounded_channel();
+        active_players.upsert(
+            5_429_573_939_285_739_921,
+            ActivePlayer::from_loadout(
+                WorldID::Emerald,
+                ZoneID(2),
+                Loadout::NCMedic,
+                DateTime::from_timestamp(1_728_117_000, 0).unwrap(),
+            ),
+        );
+
+        handle(&event, &active_players, &character_sessions);
+
+        let player = active_players.get(5_429_573_939_285_739_921).unwrap();
+        assert!(player.logged_in);
+        assert_eq!(player.zone, ZoneID(2));
+        assert_eq!(player.loadout, Loadout::NCMedic);
+        assert_eq!(player.last_change, event.timestamp);
+    }
+}
@@ -0,0 +1,70 @@
+use metrics::counter;
+use tracing::debug;
+
+use crate::active_players::ActivePlayerDb;
+use crate::census::event::PlayerLogout;
+use crate::character_sessions::{self, SessionEvent, SessionSender};
+
+pub fn handle(
+    event: &PlayerLogout,
+    active_players: &ActivePlayerDb,
+    character_sessions: &SessionSender,
+) {
+    debug!(
+        "Character {} logged out on {}",
+        event.character_id, event.world_id
+    );
+    character_sessions::send(
+        character_sessions,
+        SessionEvent::Logout {
+            character_id: event.character_id,
+            timestamp: event.timestamp,
+        },
+    );
+    active_players.remove(event.character_id);
+    counter!("niumside_player_logout_events", "world" => event.world_id.to_string()).increment(1);
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::store::ShardedActivePlayers;
+    use crate::active_players::ActivePlayer;
+    use crate::census::constants::{Loadout, WorldID, ZoneID};
+    use crate::census::event::Event;
+    use chrono::DateTime;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    const PAYLOAD: &str = r#"{"character_id":"5429573939285739921","event_name":"PlayerLogout","timestamp":"1728117291","world_id":"17"}"#;
+
+    #[test]
+    fn test_player_logout_evicts_player() {
+        let Event::PlayerLogout(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let (character_sessions, mut receiver) = mpsc::unbounded_channel();
+        active_players.upsert(
+            5_429_573_939_285_739_921,
+            ActivePlayer::from_loadout(
+                WorldID::Emerald,
+                ZoneID(2),
+                Loadout::NCMedic,
+                DateTime::from_timestamp(1_728_117_000, 0).unwrap(),
+            ),
+        );
+
+        handle(&event, &active_players, &character_sessions);
+        assert_eq!(
+            receiver.try_recv().unwrap(),
+            SessionEvent::Logout {
+                character_id: event.character_id,
+                timestamp: event.timestamp,
+            }
+        );
+
+        assert!(active_players.is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+use metrics::counter;
+
+use crate::active_players::{ActivePlayer, ActivePlayerDb};
+use crate::census::constants::Loadout;
+use crate::census::event::VehicleDestroy;
+
+pub fn handle(event: &VehicleDestroy, active_players: &ActivePlayerDb) {
+    if event.attacker_character_id != 0 && event.attacker_loadout_id != Loadout::Unknown {
+        active_players.upsert(
+            event.attacker_character_id,
+            ActivePlayer::from_loadout(
+                event.world_id,
+                event.zone_id,
+                event.attacker_loadout_id,
+                event.timestamp,
+            ),
+        );
+        active_players.set_vehicle(
+            event.attacker_character_id,
+            (event.attacker_vehicle_id != 0).then_some(event.attacker_vehicle_id),
+            event.timestamp,
+        );
+    }
+
+    // The event doesn't include the loadout of the vehicle owner,
+    // so only refresh them if they are already being tracked
+    active_players.refresh(
+        event.character_id,
+        event.world_id,
+        event.zone_id,
+        event.timestamp,
+    );
+    active_players.set_vehicle(event.character_id, None, event.timestamp);
+    counter!("niumside_vehicle_destroy_events").increment(1);
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::store::ShardedActivePlayers;
+    use crate::active_players::Vehicle;
+    use crate::census::constants::{Faction, WorldID, ZoneID};
+    use crate::census::event::Event;
+    use chrono::DateTime;
+    use std::sync::Arc;
+
+    const PAYLOAD: &str = r#"{"attacker_character_id":"5428010618015189713","attacker_loadout_id":"12","attacker_team_id":"3","attacker_vehicle_id":"4","attacker_weapon_id":"0","character_id":"5429573939285739921","event_name":"VehicleDestroy","facility_id":"0","faction_id":"1","team_id":"1","timestamp":"1728117291","vehicle_id":"2","world_id":"10","zone_id":"4"}"#;
+
+    #[test]
+    fn test_vehicle_destroy_tracks_attacker() {
+        let Event::VehicleDestroy(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+
+        handle(&event, &active_players);
+
+        assert_eq!(active_players.len(), 1);
+
+        let attacker = active_players.get(5_428_010_618_015_189_713).unwrap();
+        assert_eq!(attacker.loadout, Loadout::TREngineer);
+        assert_eq!(attacker.team_id, Faction::TR);
+        assert_eq!(attacker.world, WorldID::Miller);
+        assert_eq!(attacker.zone, ZoneID(4));
+        assert_eq!(attacker.vehicle.unwrap().vehicle_id, 4);
+    }
+
+    #[test]
+    fn test_vehicle_destroy_refreshes_tracked_victim() {
+        let Event::VehicleDestroy(event) = serde_json::from_str(PAYLOAD).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        let active_players: ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+        let last_change = DateTime::from_timestamp(1_728_117_000, 0).unwrap();
+        active_players.upsert(
+            5_429_573_939_285_739_921,
+            ActivePlayer {
+                vehicle: Some(Vehicle {
+                    vehicle_id: 2,
+                    last_seen: last_change,
+                }),
+                ..ActivePlayer::from_loadout(
+                    WorldID::Miller,
+                    ZoneID(2),
+                    Loadout::VSMAX,
+                    last_change,
+                )
+            },
+        );
+
+        handle(&event, &active_players);
+
+        let victim = active_players.get(5_429_573_939_285_739_921).unwrap();
+        assert_eq!(victim.loadout, Loadout::VSMAX);
+        assert_eq!(victim.zone, ZoneID(4));
+        assert_eq!(victim.last_change, event.timestamp);
+        assert_eq!(victim.vehicle, None);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::active_players::TrackedZones;
+use crate::census::constants::{DefinitionID, Faction, Loadout, WorldID, ZoneID};
+use crate::census::event::Death;
+use chrono::{DateTime, Utc};
+use metrics::counter;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+/// Kills and deaths are counted per world, zone definition, faction and loadout
+pub type KillKey = (WorldID, DefinitionID, Faction, Loadout);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KillCounts {
+    pub kills: u32,
+    pub deaths: u32,
+    /// Kills that were headshots
+    pub headshots: u32,
+}
+
+/// The kills and deaths seen since the last time they were stored
+#[derive(Debug, Clone)]
+pub struct KillStats {
+    pub counts: HashMap<KillKey, KillCounts>,
+    pub since: DateTime<Utc>,
+}
+
+impl Default for KillStats {
+    fn default() -> Self {
+        Self {
+            counts: HashMap::new(),
+            since: Utc::now(),
+        }
+    }
+}
+
+pub type KillStatsDb = Arc<Mutex<KillStats>>;
+
+/// The kills and deaths of a finished window, as written to the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KillWindow {
+    pub counts: HashMap<KillKey, KillCounts>,
+    pub seconds: i64,
+}
+
+impl KillStats {
+    /// Count the death of the victim and the kill of the attacker. Suicides, teamkills and
+    /// deaths caused by the environment only count as a death.
+    pub fn record(&mut self, event: &Death) {
+        let world = event.world_id;
+        let zone = event.zone_id.definition();
+        let victim_faction = event.character_loadout_id.get_faction();
+        let attacker_faction = event.attacker_loadout_id.get_faction();
+
+        self.counts
+            .entry((world, zone, victim_faction, event.character_loadout_id))
+            .or_default()
+            .deaths += 1;
+
+        if event.attacker_character_id == 0
+            || event.attacker_character_id == event.character_id
+            || attacker_faction == Faction::Unknown
+            || attacker_faction == victim_faction
+        {
+            return;
+        }
+
+        let attacker = self
+            .counts
+            .entry((world, zone, attacker_faction, event.attacker_loadout_id))
+            .or_default();
+        attacker.kills += 1;
+        attacker.headshots += u32::from(event.is_headshot);
+    }
+
+    /// Take the counts of the current window and start a new one at `now`
+    pub fn take(&mut self, now: DateTime<Utc>) -> KillWindow {
+        let since = std::mem::replace(&mut self.since, now);
+
+        KillWindow {
+            counts: std::mem::take(&mut self.counts),
+            seconds: (now - since).num_seconds(),
+        }
+    }
+}
+
+/// Apply a change to the shared kill statistics
+pub fn update<T>(kill_stats: &KillStatsDb, change: impl FnOnce(&mut KillStats) -> T) -> Option<T> {
+    kill_stats.lock().map_or_else(
+        |_| {
+            counter!("niumside_kill_stats_lock_failed").increment(1);
+            error!("Failed to lock kill_stats");
+            None
+        },
+        |mut guard| Some(change(&mut guard)),
+    )
+}
+
+async fn insert(
+    window: &KillWindow,
+    population_id: i32,
+    tracked_zones: &TrackedZones,
+    db_pool: &PgPool,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = db_pool.begin().await?;
+
+    for ((world, zone, faction, loadout), counts) in &window.counts {
+        if !tracked_zones.is_tracked(*world, ZoneID(u32::from(zone.0))) {
+            continue;
+        }
+
+        sqlx::query!(
+            "INSERT INTO world (world_id) VALUES ($1) ON CONFLICT DO NOTHING",
+            *world as i32
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO zone (zone_id) VALUES ($1) ON CONFLICT DO NOTHING",
+            i32::from(zone.0)
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        #[allow(clippy::cast_possible_wrap)]
+        #[allow(clippy::cast_possible_truncation)]
+        sqlx::query!(
+            "INSERT INTO kill_stats
+            (population_id, world_id, zone_id, faction_id, loadout_id, kills, deaths, headshots, window_seconds)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            population_id,
+            *world as i32,
+            i32::from(zone.0),
+            *faction as i16,
+            *loadout as i16,
+            counts.kills as i32,
+            counts.deaths as i32,
+            counts.headshots as i32,
+            window.seconds as i32
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+
+    transaction.commit().await
+}
+
+/// Store the kills and deaths of a window next to the population they were counted with
+pub async fn store(
+    window: &KillWindow,
+    population_id: i32,
+    tracked_zones: &TrackedZones,
+    db_pool: &PgPool,
+) {
+    if let Err(e) = insert(window, population_id, tracked_zones, db_pool).await {
+        counter!("niumside_kill_stats_failed_writes").increment(1);
+        error!("Failed to store kill stats: {e}");
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::event::Event;
+
+    const PAYLOAD: &str = r#"{"attacker_character_id":"5428010618015189713","attacker_fire_mode_id":"7401","attacker_loadout_id":"6","attacker_team_id":"2","attacker_vehicle_id":"0","attacker_weapon_id":"7169","character_id":"5429573939285739921","character_loadout_id":"20","event_name":"Death","is_critical":"0","is_headshot":"1","team_id":"1","timestamp":"1728117291","vehicle_id":"0","world_id":"10","zone_id":"65538"}"#;
+
+    fn death(payload: &str) -> Death {
+        let Event::Death(event) = serde_json::from_str(payload).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        event
+    }
+
+    #[test]
+    fn test_record_kill() {
+        let mut kill_stats = KillStats::default();
+        kill_stats.record(&death(PAYLOAD));
+
+        let attacker = kill_stats.counts[&(
+            WorldID::Miller,
+            DefinitionID(2),
+            Faction::NC,
+            Loadout::NCHeavyAssault,
+        )];
+        assert_eq!(
+            attacker,
+            KillCounts {
+                kills: 1,
+                deaths: 0,
+                headshots: 1,
+            }
+        );
+
+        let victim = kill_stats.counts[&(
+            WorldID::Miller,
+            DefinitionID(2),
+            Faction::VS,
+            Loadout::VSHeavyAssault,
+        )];
+        assert_eq!(victim.deaths, 1);
+        assert_eq!(victim.kills, 0);
+    }
+
+    #[test]
+    fn test_record_without_attacker_or_teamkill() {
+        let mut kill_stats = KillStats::default();
+        kill_stats.record(&death(&PAYLOAD.replace("5428010618015189713", "0")));
+        // A VS heavy assault killing another VS heavy assault
+        kill_stats.record(&death(&PAYLOAD.replace(
+            r#""attacker_loadout_id":"6""#,
+            r#""attacker_loadout_id":"20""#,
+        )));
+
+        assert_eq!(kill_stats.counts.len(), 1);
+        let victim = kill_stats.counts.values().next().unwrap();
+        assert_eq!(victim.deaths, 2);
+        assert_eq!(victim.kills, 0);
+    }
+
+    #[test]
+    fn test_take_starts_new_window() {
+        let mut kill_stats = KillStats::default();
+        let start = kill_stats.since;
+        kill_stats.record(&death(PAYLOAD));
+
+        let window = kill_stats.take(start + chrono::Duration::seconds(30));
+        assert_eq!(window.seconds, 30);
+        assert_eq!(window.counts.len(), 2);
+        assert!(kill_stats.counts.is_empty());
+        assert_eq!(kill_stats.since, start + chrono::Duration::seconds(30));
+    }
+}
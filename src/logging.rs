@@ -1,4 +1,4 @@
-use metrics::{describe_counter, describe_gauge};
+use metrics::{describe_counter, describe_gauge, describe_histogram};
 use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 use tracing::info;
 
@@ -9,6 +9,10 @@ pub fn metrics() -> PrometheusHandle {
         .expect("failed to install recorder");
     info!("Prometheus metrics enabled");
     describe_metrics();
+    describe_population_metrics();
+    describe_metagame_metrics();
+    describe_outfit_metrics();
+    describe_realtime_metrics();
     prometheus_metrics
 }
 
@@ -30,6 +34,202 @@ fn describe_metrics() {
         "niumside_gain_experience_events",
         "The number of gain experience events inserted into the active players"
     );
+    describe_counter!(
+        "niumside_death_events",
+        "The number of death events inserted into the active players"
+    );
+    describe_counter!(
+        "niumside_vehicle_destroy_events",
+        "The number of vehicle destroy events inserted into the active players"
+    );
+    describe_counter!(
+        "niumside_player_facility_capture_events",
+        "The number of player facility capture events received"
+    );
+    describe_counter!(
+        "niumside_player_facility_defend_events",
+        "The number of player facility defend events received"
+    );
+    describe_counter!(
+        "niumside_player_login_events",
+        "The number of player login events inserted into the active players"
+    );
+    describe_counter!(
+        "niumside_player_logout_events",
+        "The number of player logout events removed from the active players"
+    );
+    describe_counter!(
+        "niumside_continent_lock_events",
+        "The number of continent lock events received"
+    );
+    describe_counter!(
+        "niumside_continent_unlock_events",
+        "The number of continent unlock events received"
+    );
+    describe_counter!(
+        "niumside_facility_control_events",
+        "The number of facility control events received"
+    );
+    describe_counter!(
+        "niumside_metagame_event_events",
+        "The number of metagame event (alert) events received"
+    );
+    describe_counter!(
+        "niumside_item_added_events",
+        "The number of item added events received"
+    );
+    describe_counter!(
+        "niumside_achievement_earned_events",
+        "The number of achievement earned events received"
+    );
+    describe_counter!(
+        "niumside_skill_added_events",
+        "The number of skill added events received"
+    );
+    describe_counter!(
+        "niumside_battle_rank_up_events",
+        "The number of battle rank up events received"
+    );
+    describe_gauge!(
+        "niumside_character_sessions_open",
+        "Number of character sessions that are currently open"
+    );
+    describe_counter!(
+        "niumside_character_sessions_failed_writes",
+        "Number of character session changes that failed to be stored in the database"
+    );
+    describe_counter!(
+        "niumside_character_sessions_send_failed",
+        "Number of events that could not be sent to the character session tracker"
+    );
+    describe_counter!(
+        "niumside_kill_stats_lock_failed",
+        "Number of times the kill_stats lock failed"
+    );
+    describe_counter!(
+        "niumside_kill_stats_failed_writes",
+        "Number of kill statistics windows that failed to be stored in the database"
+    );
+    describe_counter!(
+        "niumside_server_health_lock_failed",
+        "Number of times the server_health lock failed"
+    );
+}
+
+fn describe_population_metrics() {
+    describe_counter!(
+        "niumside_population_failed_writes",
+        "Number of attempts to store a population snapshot that failed and were rolled back"
+    );
+    describe_counter!(
+        "niumside_population_snapshots_skipped",
+        "Number of population snapshots skipped because every attempt to store them failed"
+    );
+    describe_counter!(
+        "niumside_population_retention_deleted",
+        "Number of population snapshot and aggregate rows deleted by the retention job per resolution"
+    );
+    describe_counter!(
+        "niumside_population_retention_failures",
+        "Number of times rolling up or deleting population snapshots failed"
+    );
+}
+
+fn describe_metagame_metrics() {
+    describe_counter!(
+        "niumside_metagame_failed_writes",
+        "Number of continent, alert and facility changes that failed to be stored in the database"
+    );
+    describe_counter!(
+        "niumside_metagame_send_failed",
+        "Number of events that could not be sent to the metagame tracker"
+    );
+    describe_counter!(
+        "niumside_territory_lock_failed",
+        "Number of times the territory lock failed"
+    );
+    describe_counter!(
+        "niumside_territory_seed_failed",
+        "Number of times the map of a world could not be requested from Census"
+    );
+}
+
+fn describe_outfit_metrics() {
+    describe_counter!(
+        "niumside_outfit_requests_failed",
+        "Number of times outfits or outfit members could not be requested from Census"
+    );
+    describe_gauge!(
+        "niumside_outfit_cache_size",
+        "Number of characters whose outfit is cached in memory"
+    );
+}
+
+fn describe_realtime_metrics() {
+    describe_gauge!(
+        "niumside_event_queue_depth",
+        "Number of realtime events waiting to be handled"
+    );
+    describe_counter!(
+        "niumside_events_dropped",
+        "Number of realtime events dropped because the event queue was full"
+    );
+    describe_counter!(
+        "niumside_events_handled",
+        "Number of realtime events passed to the event handlers"
+    );
+    describe_counter!(
+        "realtime_total_resubscriptions",
+        "Total number of resubscriptions to Census stream"
+    );
+    describe_counter!(
+        "realtime_total_disconnects",
+        "Total number of times the Census stream connection was replaced, by reason"
+    );
+    describe_counter!(
+        "niumside_realtime_upstream_messages",
+        "Number of text frames received from each realtime upstream"
+    );
+    describe_counter!(
+        "niumside_realtime_upstream_duplicates",
+        "Number of events dropped because another realtime upstream delivered them first"
+    );
+    describe_histogram!(
+        "niumside_realtime_upstream_lag_seconds",
+        "How long after the first realtime upstream each upstream delivered an event"
+    );
+    describe_counter!(
+        "niumside_subscription_mismatches",
+        "Number of subscriptions Census acknowledged without everything that was requested"
+    );
+    describe_gauge!(
+        "niumside_subscription_mismatch",
+        "Whether the last subscription Census acknowledged on an upstream misses requested fields"
+    );
+    describe_gauge!(
+        "realtime_event_server_online",
+        "Whether the Census event server of a world is online according to its heartbeat"
+    );
+    describe_counter!(
+        "niumside_parse_failures",
+        "Number of messages from Census that could not be parsed, by event and field"
+    );
+    describe_counter!(
+        "niumside_unknown_values",
+        "Number of values Census sent that are not known yet, such as new loadouts, by type"
+    );
+    describe_counter!(
+        "niumside_dead_letter_failed_writes",
+        "Number of messages that failed to parse and could not be saved to the dead letter file"
+    );
+    describe_counter!(
+        "niumside_recording_send_failed",
+        "Number of realtime frames that could not be passed to the recorder"
+    );
+    describe_counter!(
+        "niumside_recording_failed_writes",
+        "Number of times the recorder stopped because the recording could not be written"
+    );
 }
 
 pub fn tracing(log_level: tracing::Level) {
@@ -11,6 +11,8 @@ extern crate google_calendar3 as calendar3;
 mod active_players;
 #[cfg(feature = "census")]
 mod census;
+#[cfg(feature = "census")]
+mod character_sessions;
 mod constants;
 #[cfg(feature = "census")]
 mod controllers;
@@ -18,15 +20,19 @@ mod discord;
 #[cfg(feature = "census")]
 mod event_handlers;
 mod google_calendar;
+#[cfg(feature = "census")]
+mod kill_stats;
 mod logging;
 #[cfg(feature = "census")]
+mod metagame;
+#[cfg(feature = "census")]
 mod serde;
 mod startup;
 mod storage;
 mod utils;
 mod web;
 
-use crate::active_players::ActivePlayerHashmap;
+use crate::active_players::store::ShardedActivePlayers;
 use crate::discord::{Data, Error};
 use crate::storage::configuration::Settings;
 use poise::FrameworkBuilder;
@@ -38,6 +44,8 @@ use std::sync::{Arc, Mutex};
 struct Services {
     #[cfg(feature = "census")]
     active_players: active_players::ActivePlayerDb,
+    #[cfg(feature = "census")]
+    server_health: census::server_health::ServerHealthDb,
     #[cfg(feature = "database")]
     db_pool: PgPool,
     rocket: rocket::Rocket<rocket::Build>,
@@ -47,8 +55,10 @@ struct Services {
 #[allow(clippy::unused_async)]
 async fn agnostic_init(#[cfg(feature = "database")] postgres: PgPool) -> anyhow::Result<Services> {
     #[cfg(feature = "census")]
-    let active_players: active_players::ActivePlayerDb =
-        Arc::new(Mutex::new(ActivePlayerHashmap::new()));
+    let active_players: active_players::ActivePlayerDb = Arc::new(ShardedActivePlayers::default());
+    #[cfg(feature = "census")]
+    let server_health: census::server_health::ServerHealthDb =
+        Arc::new(Mutex::new(census::server_health::ServerHealth::default()));
 
     let rocket = web::init();
 
@@ -57,6 +67,8 @@ async fn agnostic_init(#[cfg(feature = "database")] postgres: PgPool) -> anyhow:
     Ok(Services {
         #[cfg(feature = "census")]
         active_players,
+        #[cfg(feature = "census")]
+        server_health,
         #[cfg(feature = "database")]
         db_pool: postgres,
         rocket,
@@ -66,12 +78,19 @@ async fn agnostic_init(#[cfg(feature = "database")] postgres: PgPool) -> anyhow:
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let app_config = Settings::new(Path::new("config"))?;
+    #[cfg_attr(not(feature = "census"), allow(unused_mut))]
+    let mut app_config = Settings::new(Path::new("config"))?;
+    #[cfg(feature = "census")]
+    app_config.apply(storage::arguments::Arguments::parse(std::env::args().skip(1))?);
 
     logging::tracing(app_config.app.log_level);
 
     #[cfg(feature = "database")]
-    let postgres = storage::db_pool::create(&app_config.database.connection_string.clone()).await?;
+    let postgres = storage::db_pool::create(
+        &app_config.database.connection_string.clone(),
+        app_config.database.population_layout,
+    )
+    .await?;
 
     let initialised_services = agnostic_init(
         #[cfg(feature = "database")]
@@ -89,6 +108,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         initialised_services.poise,
         #[cfg(feature = "census")]
         initialised_services.active_players,
+        #[cfg(feature = "census")]
+        initialised_services.server_health,
         addr,
     ))
     .await?;
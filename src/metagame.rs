@@ -0,0 +1,205 @@
+use crate::census::constants::{
+    DefinitionID, FacilityID, Faction, InstanceID, MetagameEventState, OutfitID, WorldID,
+};
+use crate::census::event::{ContinentLock, ContinentUnlock, FacilityControl, MetagameEvent};
+use crate::controllers::{facility, metagame};
+use chrono::{DateTime, Duration, Utc};
+use metrics::counter;
+use sqlx::PgPool;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tracing::error;
+
+/// A continent being locked or unlocked on a world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContinentChange {
+    pub world_id: WorldID,
+    pub zone_id: DefinitionID,
+    pub locked: bool,
+    pub triggering_faction: Faction,
+    pub previous_faction: Faction,
+    pub vs_population: u16,
+    pub nc_population: u16,
+    pub tr_population: u16,
+    pub metagame_event_id: u8,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl From<&ContinentLock> for ContinentChange {
+    fn from(event: &ContinentLock) -> Self {
+        Self {
+            world_id: event.world_id,
+            zone_id: event.zone_id.definition(),
+            locked: true,
+            triggering_faction: event.triggering_faction,
+            previous_faction: event.previous_faction,
+            vs_population: event.vs_population,
+            nc_population: event.nc_population,
+            tr_population: event.tr_population,
+            metagame_event_id: event.metagame_event_id,
+            timestamp: event.timestamp,
+        }
+    }
+}
+
+impl From<&ContinentUnlock> for ContinentChange {
+    fn from(event: &ContinentUnlock) -> Self {
+        Self {
+            world_id: event.world_id,
+            zone_id: event.zone_id.definition(),
+            locked: false,
+            triggering_faction: event.triggering_faction,
+            previous_faction: event.previous_faction,
+            vs_population: event.vs_population,
+            nc_population: event.nc_population,
+            tr_population: event.tr_population,
+            metagame_event_id: event.metagame_event_id,
+            timestamp: event.timestamp,
+        }
+    }
+}
+
+/// A metagame event (alert) starting, ending or changing its experience bonus
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlertChange {
+    pub world_id: WorldID,
+    pub instance_id: u32,
+    pub zone_id: DefinitionID,
+    pub zone_instance_id: InstanceID,
+    pub metagame_event_id: u8,
+    pub state: MetagameEventState,
+    pub experience_bonus: f32,
+    pub faction_vs: f32,
+    pub faction_nc: f32,
+    pub faction_tr: f32,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl AlertChange {
+    /// Returns `None` for states that aren't known yet
+    pub fn from_event(event: &MetagameEvent) -> Option<Self> {
+        Some(Self {
+            world_id: event.world_id,
+            instance_id: event.instance_id,
+            zone_id: event.zone_id.definition(),
+            zone_instance_id: event.zone_id.instance(),
+            metagame_event_id: event.metagame_event_id,
+            state: MetagameEventState::try_from(event.metagame_event_state).ok()?,
+            experience_bonus: event.experience_bonus,
+            faction_vs: event.faction_vs,
+            faction_nc: event.faction_nc,
+            faction_tr: event.faction_tr,
+            timestamp: event.timestamp,
+        })
+    }
+}
+
+/// A facility being captured or defended, defended when the old and new faction are the same
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FacilityChange {
+    pub world_id: WorldID,
+    pub zone_id: DefinitionID,
+    pub zone_instance_id: InstanceID,
+    pub facility_id: FacilityID,
+    pub old_faction: Faction,
+    pub new_faction: Faction,
+    /// `None` when the facility wasn't captured by an outfit
+    pub outfit_id: Option<OutfitID>,
+    pub duration_held: Duration,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl From<&FacilityControl> for FacilityChange {
+    fn from(event: &FacilityControl) -> Self {
+        Self {
+            world_id: event.world_id,
+            zone_id: event.zone_id.definition(),
+            zone_instance_id: event.zone_id.instance(),
+            facility_id: event.facility_id,
+            old_faction: event.old_faction_id,
+            new_faction: event.new_faction_id,
+            outfit_id: (event.outfit_id != 0).then_some(event.outfit_id),
+            duration_held: event.duration_held,
+            timestamp: event.timestamp,
+        }
+    }
+}
+
+/// Changes to the continents, alerts and facilities of a world, sent by the event handlers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetagameUpdate {
+    Continent(ContinentChange),
+    Alert(AlertChange),
+    Facility(FacilityChange),
+}
+
+pub type MetagameSender = UnboundedSender<MetagameUpdate>;
+
+/// Send a change to the metagame tracker, which only fails when the tracker stopped
+pub fn send(metagame: &MetagameSender, update: MetagameUpdate) {
+    if metagame.send(update).is_err() {
+        counter!("niumside_metagame_send_failed").increment(1);
+        error!("Unable to send update to the metagame tracker");
+    }
+}
+
+async fn store(update: MetagameUpdate, db_pool: &PgPool) {
+    let result = match update {
+        MetagameUpdate::Continent(change) => metagame::record_continent(db_pool, &change).await,
+        MetagameUpdate::Alert(change) => metagame::record_alert(db_pool, &change).await,
+        MetagameUpdate::Facility(change) => facility::record_capture(db_pool, &change).await,
+    };
+
+    if let Err(e) = result {
+        counter!("niumside_metagame_failed_writes").increment(1);
+        error!("Failed to store metagame update: {e}");
+    }
+}
+
+pub async fn run(mut receiver: UnboundedReceiver<MetagameUpdate>, db_pool: &PgPool) -> Option<()> {
+    loop {
+        let update = receiver.recv().await?;
+        store(update, db_pool).await;
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::census::event::Event;
+
+    #[test]
+    fn test_alert_change_splits_zone() {
+        let Event::MetagameEvent(event) = serde_json::from_str(r#"{"event_name":"MetagameEvent","experience_bonus":"25.000000","faction_nc":"33.725490","faction_tr":"24.705883","faction_vs":"41.176472","instance_id":"23469","metagame_event_id":"147","metagame_event_state":"138","metagame_event_state_name":"ended","timestamp":"1728117291","world_id":"10","zone_id":"65550"}"#).unwrap() else {
+            panic!("Unexpected event type");
+        };
+
+        let change = AlertChange::from_event(&event).unwrap();
+        assert_eq!(change.zone_id, DefinitionID(14));
+        assert_eq!(change.zone_instance_id, InstanceID(1));
+        assert_eq!(change.state, MetagameEventState::Ended);
+    }
+
+    #[test]
+    fn test_alert_change_skips_unknown_states() {
+        let Event::MetagameEvent(mut event) = serde_json::from_str(r#"{"event_name":"MetagameEvent","experience_bonus":"0.000000","faction_nc":"0","faction_tr":"0","faction_vs":"0","instance_id":"1","metagame_event_id":"147","metagame_event_state":"135","metagame_event_state_name":"started","timestamp":"1728117291","world_id":"10","zone_id":"2"}"#).unwrap() else {
+            panic!("Unexpected event type");
+        };
+        assert!(AlertChange::from_event(&event).is_some());
+
+        event.metagame_event_state = 200;
+        assert!(AlertChange::from_event(&event).is_none());
+    }
+
+    #[test]
+    fn test_facility_change_without_outfit() {
+        let Event::FacilityControl(event) = serde_json::from_str(r#"{"duration_held":"3412","event_name":"FacilityControl","facility_id":"222280","new_faction_id":"3","old_faction_id":"3","outfit_id":"0","timestamp":"1728117291","world_id":"10","zone_id":"65538"}"#).unwrap() else {
+            panic!("Unexpected event type");
+        };
+
+        let change = FacilityChange::from(&event);
+        assert_eq!(change.outfit_id, None);
+        assert_eq!(change.zone_id, DefinitionID(2));
+        assert_eq!(change.zone_instance_id, InstanceID(1));
+    }
+}
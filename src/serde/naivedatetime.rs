@@ -19,3 +19,14 @@ where
     let s = String::deserialize(deserializer)?;
     NaiveDateTime::parse_from_str(&s, ISO8601T0_FORMAT).map_err(serde::de::Error::custom)
 }
+
+#[allow(clippy::ref_option)]
+pub fn serialize_option<S>(value: &Option<NaiveDateTime>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match value {
+        Some(value) => serialize(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
@@ -1,105 +1,325 @@
 use crate::census::rest;
 use crate::census::rest::client::CensusRestClient;
 use crate::discord::{Data, Error};
+#[cfg(feature = "census")]
+use crate::event_handlers::pipeline::{self, EventHandlers, EventPipeline, EVENT_QUEUE_CAPACITY};
+#[cfg(feature = "census")]
+use crate::event_handlers::{MetagameTracker, PopulationTracker};
 use crate::logging;
 use crate::storage::configuration::Settings;
+#[cfg(feature = "census")]
+use crate::storage::configuration::{
+    CensusConfig, DatabaseConfig, PopulationLayout, VehicleExperienceConfig, WorldConfig,
+};
 use crate::web::ApiDoc;
 #[cfg(feature = "census")]
-use crate::{active_players, census};
+use crate::{active_players, census, character_sessions, kill_stats, metagame};
 use poise::serenity_prelude::ClientBuilder;
 use poise::{serenity_prelude, FrameworkBuilder};
 #[cfg(feature = "database")]
 use sqlx::PgPool;
+#[cfg(feature = "census")]
+use tokio::sync::mpsc::UnboundedReceiver;
 use utoipa::OpenApi;
 
 #[cfg(feature = "census")]
 #[allow(dead_code)]
 pub struct DbState {
     pub(crate) pool: PgPool,
+    pub(crate) population_layout: PopulationLayout,
 }
 
-pub async fn services(
-    rocket: rocket::Rocket<rocket::Build>,
-    #[cfg(feature = "database")] db_pool: PgPool,
-    app_config: Settings,
-    poise: FrameworkBuilder<Data, Error>,
-    #[cfg(feature = "census")] active_players: active_players::ActivePlayerDb,
+/// A realtime client for every environment and upstream, each subscribing to the worlds of its
+/// environment
+#[cfg(feature = "census")]
+fn realtime_client_configs(
+    census_config: &CensusConfig,
+) -> Vec<census::realtime::RealtimeClientConfig> {
+    let realtime_urls = census_config.realtime_urls();
+
+    census_config
+        .environments()
+        .into_iter()
+        .flat_map(|environment| {
+            realtime_urls
+                .iter()
+                .map(move |realtime_url| census::realtime::RealtimeClientConfig {
+                    environment,
+                    service_id: census_config.service_id.clone(),
+                    realtime_url: Some(realtime_url.clone()),
+                    worlds: census_config.environment_worlds(environment),
+                    event_names: census_config.event_names(),
+                    characters: census_config.characters.clone(),
+                })
+        })
+        .collect()
+}
+
+/// Register the event handlers and start the workers that pass realtime events to them
+#[cfg(feature = "census")]
+fn start_event_pipeline(
+    active_players: active_players::ActivePlayerDb,
+    character_sessions: character_sessions::SessionSender,
+    metagame: metagame::MetagameSender,
+    territory: census::territory::TerritoryDb,
+    kill_stats: kill_stats::KillStatsDb,
+    vehicle_experience: &[VehicleExperienceConfig],
+) -> EventPipeline {
+    let event_handlers = EventHandlers::default()
+        .register(PopulationTracker {
+            active_players,
+            character_sessions,
+            kill_stats,
+            vehicle_experience: vehicle_experience
+                .iter()
+                .map(|config| (config.experience_id, config.vehicle_id))
+                .collect(),
+        })
+        .register(MetagameTracker {
+            metagame,
+            territory,
+        });
+
+    let (events, events_receiver) = EventPipeline::new(EVENT_QUEUE_CAPACITY);
+    pipeline::spawn_workers(
+        events_receiver,
+        event_handlers,
+        std::thread::available_parallelism().map_or(4, std::num::NonZero::get),
+    );
+
+    events
+}
+
+/// Store the character sessions and metagame changes sent by the event handlers
+#[cfg(feature = "census")]
+async fn store_events(
+    db_pool: PgPool,
+    character_sessions_receiver: UnboundedReceiver<character_sessions::SessionEvent>,
+    metagame_receiver: UnboundedReceiver<metagame::MetagameUpdate>,
+) {
+    tokio::join!(
+        character_sessions::run(character_sessions_receiver, &db_pool),
+        metagame::run(metagame_receiver, &db_pool)
+    );
+}
+
+/// Keep the characters, zones, territory and outfits of active players in sync with the Census
+/// REST API
+#[cfg(feature = "census")]
+async fn update_census_data(
+    db_pool: PgPool,
+    census_rest_client: CensusRestClient,
+    territory: census::territory::TerritoryDb,
+    active_players: active_players::ActivePlayerDb,
+    character_sessions: character_sessions::SessionSender,
+    worlds: Vec<WorldConfig>,
+    environments: Vec<census::constants::Environment>,
+) {
+    tokio::join!(
+        rest::update_data::run(&db_pool, &census_rest_client),
+        census::territory::run(
+            territory,
+            db_pool.clone(),
+            census_rest_client.clone(),
+            worlds,
+            environments
+        ),
+        active_players::outfits::run(
+            active_players,
+            character_sessions,
+            db_pool.clone(),
+            census_rest_client.clone()
+        )
+    );
+}
+
+/// Listen on the given address and leave shutting down on Ctrl-C to the other services
+fn rocket_config(
+    rocket: &rocket::Rocket<rocket::Build>,
     addr: std::net::SocketAddr,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> rocket::figment::Figment {
     let shutdown = rocket::config::Shutdown {
         ctrlc: false,
         ..rocket::config::Shutdown::default()
     };
 
-    #[cfg(feature = "census")]
-    let db_state = DbState {
-        pool: db_pool.clone(),
-    };
-
-    let config = rocket
+    rocket
         .figment()
         .clone()
         .merge((rocket::Config::ADDRESS, addr.ip()))
         .merge((rocket::Config::PORT, addr.port()))
         .merge((rocket::Config::LOG_LEVEL, rocket::config::LogLevel::Off))
-        .merge((rocket::Config::SHUTDOWN, shutdown));
-
-    #[cfg(feature = "census")]
-    let census_rest_client = CensusRestClient {
-        census_url: app_config.census.census_base_url,
-        service_id: app_config.census.service_id.clone(),
-    };
-
-    let rocket = rocket
-        .configure(config)
-        .manage(logging::metrics())
-        .manage(ApiDoc::openapi());
-
-    #[cfg(feature = "census")]
-    let rocket = rocket.manage(db_state);
+        .merge((rocket::Config::SHUTDOWN, shutdown))
+}
 
-    #[cfg(feature = "database")]
-    let poise_db = db_pool.clone();
-    let discord_census_rest_client = census_rest_client.clone();
+/// Register the Discord commands once connected and create the client that runs them
+async fn discord_client(
+    poise: FrameworkBuilder<Data, Error>,
+    data: Data,
+    token: String,
+) -> Result<serenity_prelude::Client, Box<dyn std::error::Error>> {
     let poise_framework = poise
         .setup(|ctx, _ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data {
-                    #[cfg(feature = "database")]
-                    db_pool: poise_db,
-                    google: app_config.google,
-                    calendar: app_config.discord.calendar,
-                    #[cfg(feature = "census")]
-                    census_rest_client: discord_census_rest_client,
-                })
+                Ok(data)
             })
         })
         .build();
 
     let intents = serenity_prelude::GatewayIntents::non_privileged();
-    let mut poise_client = ClientBuilder::new(app_config.discord.token, intents)
+    Ok(ClientBuilder::new(token, intents)
         .framework(poise_framework)
         .await
         .ok()
-        .ok_or("Failed to create Discord client")?;
+        .ok_or("Failed to create Discord client")?)
+}
 
-    #[cfg(feature = "census")]
-    {
-        let census_realtime_state = census::realtime::State {
-            active_players: active_players.clone(),
-        };
+/// Start recording the realtime stream, the stream is still handled when the recording can't be
+/// started
+#[cfg(feature = "census")]
+fn start_recording(path: Option<&std::path::Path>) -> Option<census::recording::RecordingSender> {
+    let path = path?;
+    census::recording::start_recording(path)
+        .inspect_err(|e| {
+            tracing::error!(
+                "Unable to record the realtime stream to {}: {e}",
+                path.display()
+            );
+        })
+        .ok()
+}
 
-        let census_realtime_config = census::realtime::RealtimeClientConfig {
-            environment: "ps2".to_owned(),
-            service_id: app_config.census.service_id.clone(),
-            realtime_url: Some(app_config.census.realtime_base_url),
-        };
+/// What every realtime connection shares: the event queue, the health of the connections and
+/// the recording, dead letters and deduplication when they are configured
+#[cfg(feature = "census")]
+fn realtime_state(
+    census_config: &CensusConfig,
+    events: EventPipeline,
+    server_health: census::server_health::ServerHealthDb,
+) -> census::realtime::State {
+    census::realtime::State {
+        events,
+        server_health,
+        recording: start_recording(census_config.recording.as_deref()),
+        dead_letters: census_config
+            .dead_letters
+            .as_deref()
+            .map(|path| std::sync::Arc::new(census::dead_letter::DeadLetters::new(path))),
+        deduplicator: (census_config.realtime_urls().len() > 1)
+            .then(|| std::sync::Arc::new(census::dedup::Deduplicator::default())),
+    }
+}
+
+/// Roll up and delete old population snapshots when a retention is configured
+#[cfg(feature = "census")]
+fn spawn_retention(database_config: &DatabaseConfig, db_pool: &PgPool) {
+    if let Some(retention) = database_config.retention.clone() {
+        tokio::spawn(active_players::retention::run(
+            db_pool.clone(),
+            retention,
+            database_config.population_layout,
+        ));
+    }
+}
 
+/// Connect to every environment of Census, or replay a recording when one was passed with
+/// `--replay`
+#[cfg(feature = "census")]
+fn spawn_realtime(
+    census_config: &CensusConfig,
+    realtime_configs: Vec<census::realtime::RealtimeClientConfig>,
+    state: census::realtime::State,
+) {
+    if let Some(path) = census_config.replay.clone() {
+        let speed = census_config.replay_speed.unwrap_or(1.0);
+        tokio::spawn(census::recording::replay(path, speed, state));
+        return;
+    }
+
+    for realtime_config in realtime_configs {
+        let state = state.clone();
         tokio::spawn(async move {
-            census::realtime::client(census_realtime_config, census_realtime_state).await;
+            census::realtime::client(realtime_config, state).await;
         });
     }
+}
+
+pub async fn services(
+    rocket: rocket::Rocket<rocket::Build>,
+    #[cfg(feature = "database")] db_pool: PgPool,
+    app_config: Settings,
+    poise: FrameworkBuilder<Data, Error>,
+    #[cfg(feature = "census")] active_players: active_players::ActivePlayerDb,
+    #[cfg(feature = "census")] server_health: census::server_health::ServerHealthDb,
+    addr: std::net::SocketAddr,
+) -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "census")]
+    let db_state = DbState {
+        pool: db_pool.clone(),
+        population_layout: app_config.database.population_layout,
+    };
+
+    let config = rocket_config(&rocket, addr);
+
+    #[cfg(feature = "census")]
+    let census_realtime_configs = realtime_client_configs(&app_config.census);
+    #[cfg(feature = "census")]
+    let tracked_zones = active_players::TrackedZones::from(app_config.census.worlds.as_slice());
+
+    #[cfg(feature = "census")]
+    let census_rest_client = CensusRestClient::from(app_config.census.clone());
+
+    let rocket = rocket
+        .configure(config)
+        .manage(logging::metrics())
+        .manage(ApiDoc::openapi());
+
+    #[cfg(feature = "census")]
+    let territory = census::territory::TerritoryDb::default();
+    #[cfg(feature = "census")]
+    let kill_stats = kill_stats::KillStatsDb::default();
+
+    #[cfg(feature = "census")]
+    let rocket = rocket
+        .manage(db_state)
+        .manage(server_health.clone())
+        .manage(territory.clone());
+
+    let data = Data {
+        #[cfg(feature = "database")]
+        db_pool: db_pool.clone(),
+        population_layout: app_config.database.population_layout,
+        google: app_config.google,
+        calendar: app_config.discord.calendar,
+        #[cfg(feature = "census")]
+        census_rest_client: census_rest_client.clone(),
+    };
+    let mut poise_client = discord_client(poise, data, app_config.discord.token).await?;
+
+    #[cfg(feature = "census")]
+    let (character_sessions, character_sessions_receiver) = tokio::sync::mpsc::unbounded_channel();
+    #[cfg(feature = "census")]
+    let (metagame, metagame_receiver) = tokio::sync::mpsc::unbounded_channel();
+
+    #[cfg(feature = "census")]
+    {
+        let events = start_event_pipeline(
+            active_players.clone(),
+            character_sessions.clone(),
+            metagame,
+            territory.clone(),
+            kill_stats.clone(),
+            &app_config.census.vehicle_experience,
+        );
+        let census_realtime_state = realtime_state(&app_config.census, events, server_health);
+
+        spawn_realtime(
+            &app_config.census,
+            census_realtime_configs,
+            census_realtime_state,
+        );
+    }
 
     let poise_client_future = tokio::spawn(async move { poise_client.start().await });
 
@@ -107,14 +327,34 @@ pub async fn services(
 
     #[cfg(feature = "census")]
     {
-        let update_data_pool = db_pool.clone();
-        let census_update_data_future = tokio::spawn(async move {
-            rest::update_data::run(&update_data_pool, &census_rest_client).await;
-        });
+        let census_update_data_future = tokio::spawn(update_census_data(
+            db_pool.clone(),
+            census_rest_client,
+            territory,
+            active_players.clone(),
+            character_sessions,
+            app_config.census.worlds.clone(),
+            app_config.census.environments(),
+        ));
+
+        let event_stores_future = tokio::spawn(store_events(
+            db_pool.clone(),
+            character_sessions_receiver,
+            metagame_receiver,
+        ));
+
+        spawn_retention(&app_config.database, &db_pool);
 
         let active_players_clean = active_players.clone();
         let active_players_process_loop_future = tokio::spawn(async move {
-            active_players::process_loop(active_players.clone(), db_pool).await
+            active_players::process_loop(
+                active_players.clone(),
+                kill_stats,
+                db_pool,
+                tracked_zones,
+                app_config.database.population_layout,
+            )
+            .await
         });
 
         let active_players_clean_future =
@@ -123,7 +363,8 @@ pub async fn services(
         tokio::try_join!(
             census_update_data_future,
             active_players_process_loop_future,
-            active_players_clean_future
+            active_players_clean_future,
+            event_stores_future
         )?;
     }
 
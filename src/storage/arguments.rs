@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+/// Command line arguments, which take precedence over the configuration files
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Arguments {
+    /// `--record <file>`, record the realtime stream to a file
+    pub record: Option<PathBuf>,
+    /// `--replay <file>`, replay a recording instead of connecting to Census
+    pub replay: Option<PathBuf>,
+    /// `--replay-speed <factor>`, how many times faster than recorded to replay
+    pub replay_speed: Option<f64>,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum ArgumentsError {
+    #[error("Missing value for argument {0}")]
+    MissingValue(String),
+    #[error("The replay speed has to be a number above 0, got {0}")]
+    InvalidReplaySpeed(String),
+    #[error("Unknown argument {0}")]
+    Unknown(String),
+}
+
+impl Arguments {
+    /// Parse the arguments, without the name of the executable
+    pub fn parse(arguments: impl IntoIterator<Item = String>) -> Result<Self, ArgumentsError> {
+        let mut parsed = Self::default();
+        let mut arguments = arguments.into_iter();
+
+        while let Some(argument) = arguments.next() {
+            let mut value = || {
+                arguments
+                    .next()
+                    .ok_or_else(|| ArgumentsError::MissingValue(argument.clone()))
+            };
+
+            match argument.as_str() {
+                "--record" => parsed.record = Some(value()?.into()),
+                "--replay" => parsed.replay = Some(value()?.into()),
+                "--replay-speed" => {
+                    let speed = value()?;
+                    parsed.replay_speed = Some(
+                        speed
+                            .parse()
+                            .ok()
+                            .filter(|speed: &f64| speed.is_finite() && *speed > 0.0)
+                            .ok_or(ArgumentsError::InvalidReplaySpeed(speed))?,
+                    );
+                }
+                _ => return Err(ArgumentsError::Unknown(argument)),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn parse(arguments: &[&str]) -> Result<Arguments, ArgumentsError> {
+        Arguments::parse(arguments.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn test_parse_replay() {
+        assert_eq!(parse(&[]).unwrap(), Arguments::default());
+        assert_eq!(
+            parse(&["--replay", "stream.ndjson.gz", "--replay-speed", "10"]).unwrap(),
+            Arguments {
+                record: None,
+                replay: Some("stream.ndjson.gz".into()),
+                replay_speed: Some(10.0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse(&["--record"]),
+            Err(ArgumentsError::MissingValue("--record".to_owned()))
+        );
+        assert_eq!(
+            parse(&["--replay-speed", "0"]),
+            Err(ArgumentsError::InvalidReplaySpeed("0".to_owned()))
+        );
+        assert_eq!(
+            parse(&["--verbose"]),
+            Err(ArgumentsError::Unknown("--verbose".to_owned()))
+        );
+    }
+}
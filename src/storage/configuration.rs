@@ -1,10 +1,18 @@
+#[cfg(feature = "census")]
+use crate::census::constants::{
+    CharacterID, DefinitionID, Environment as CensusEnvironment, ExperienceID, VehicleID, WorldID,
+};
+#[cfg(feature = "census")]
+use crate::census::event::EventNames;
 use crate::constants;
+#[cfg(feature = "census")]
+use crate::storage::arguments::Arguments;
 use calendar3::oauth2::ServiceAccountKey;
 use config::{Config, ConfigError, Environment, File};
 use poise::serenity_prelude::{ChannelId, GuildId, MessageId};
 use serde::{Deserialize, Deserializer};
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tracing::Level;
 use url::Url;
 
@@ -34,20 +42,175 @@ impl DeserializeWith for Level {
     }
 }
 
+#[derive(Debug, Deserialize, Clone)]
+#[allow(unused)]
+#[cfg(feature = "census")]
+pub struct WorldConfig {
+    pub id: WorldID,
+    /// The zone definitions to track on this world, all zones when not set
+    pub zones: Option<Vec<DefinitionID>>,
+}
+
+/// An experience ID that is only gained while in a vehicle, such as a gunner kill share
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[cfg(feature = "census")]
+pub struct VehicleExperienceConfig {
+    pub experience_id: ExperienceID,
+    pub vehicle_id: VehicleID,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 #[allow(unused)]
 #[cfg(feature = "census")]
 pub struct CensusConfig {
     pub realtime_base_url: Url,
+    /// More realtime servers to connect to next to `realtime_base_url`, events are deduplicated
+    /// so the population keeps updating while any of them delivers events
+    #[serde(default)]
+    pub realtime_upstreams: Vec<Url>,
     pub census_base_url: Url,
     pub lithafalcon_base_url: Url,
     pub service_id: String,
+    /// The environments to connect to, the environments of `worlds` when not set
+    pub environments: Option<Vec<CensusEnvironment>>,
+    /// The worlds to subscribe to, all worlds of the environments when empty
+    #[serde(default)]
+    pub worlds: Vec<WorldConfig>,
+    /// The events to subscribe to, the events used for population tracking when not set
+    pub event_names: Option<Vec<EventNames>>,
+    /// The characters to subscribe to, all characters when not set
+    pub characters: Option<Vec<CharacterID>>,
+    /// The experience IDs that attribute a player to a vehicle
+    #[serde(default)]
+    pub vehicle_experience: Vec<VehicleExperienceConfig>,
+    /// Record every text frame from Census to this file as gzip compressed NDJSON
+    pub recording: Option<PathBuf>,
+    /// Replay this recording instead of connecting to Census, only set with `--replay`
+    #[serde(skip)]
+    pub replay: Option<PathBuf>,
+    /// How many times faster than recorded to replay, real speed when not set
+    pub replay_speed: Option<f64>,
+    /// Save a sample of the messages that failed to parse to this file as NDJSON
+    pub dead_letters: Option<PathBuf>,
+}
+
+#[cfg(feature = "census")]
+impl CensusConfig {
+    /// The environments to connect to, PC when neither environments nor worlds are configured
+    pub fn environments(&self) -> Vec<CensusEnvironment> {
+        if let Some(environments) = &self.environments {
+            return environments.clone();
+        }
+
+        let mut environments: Vec<CensusEnvironment> = self
+            .worlds
+            .iter()
+            .map(|world| world.id.environment())
+            .collect();
+        environments.sort_unstable();
+        environments.dedup();
+
+        if environments.is_empty() {
+            vec![CensusEnvironment::Ps2]
+        } else {
+            environments
+        }
+    }
+
+    /// Every realtime server to connect to, starting with `realtime_base_url`
+    pub fn realtime_urls(&self) -> Vec<Url> {
+        let mut urls = vec![self.realtime_base_url.clone()];
+        for url in &self.realtime_upstreams {
+            if !urls.contains(url) {
+                urls.push(url.clone());
+            }
+        }
+
+        urls
+    }
+
+    /// The configured worlds of an environment, empty when all of its worlds are tracked
+    pub fn environment_worlds(&self, environment: CensusEnvironment) -> Vec<WorldID> {
+        self.worlds
+            .iter()
+            .map(|world| world.id)
+            .filter(|world| world.environment() == environment)
+            .collect()
+    }
+
+    /// The configured events, with the vehicle experience IDs added when only some experience is
+    /// subscribed to
+    pub fn event_names(&self) -> Option<Vec<EventNames>> {
+        let mut event_names = self.event_names.clone()?;
+
+        if !event_names.contains(&EventNames::GainExperience) {
+            for vehicle_experience in &self.vehicle_experience {
+                let event_name = EventNames::GainExperienceId(vehicle_experience.experience_id);
+                if !event_names.contains(&event_name) {
+                    event_names.push(event_name);
+                }
+            }
+        }
+
+        Some(event_names)
+    }
+}
+
+/// How long population snapshots are kept before only their aggregates remain
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// How often to roll up and delete snapshots
+    pub interval_minutes: u64,
+    /// How long the loadouts of the raw snapshots are kept. The vehicles, outfits and kill
+    /// statistics of a snapshot aren't rolled up, so they and the snapshot itself are kept.
+    pub raw_hours: i64,
+    /// How long the five minute aggregates are kept
+    pub five_minute_days: i64,
+    /// How long the hourly aggregates are kept
+    pub hourly_days: i64,
+    /// How long the daily aggregates are kept, forever when not set
+    pub daily_days: Option<i64>,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            interval_minutes: 15,
+            raw_hours: 48,
+            five_minute_days: 30,
+            hourly_days: 365,
+            daily_days: None,
+        }
+    }
+}
+
+/// How the loadouts of population snapshots are stored and read
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PopulationLayout {
+    /// A table per zone, faction and loadout level of each world
+    #[default]
+    Normalized,
+    /// The normalized levels, and one `population_snapshot` row per loadout that the population
+    /// is read from
+    Flat,
+}
+
+impl PopulationLayout {
+    pub const fn is_flat(self) -> bool {
+        matches!(self, Self::Flat)
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 #[allow(unused)]
 pub struct DatabaseConfig {
     pub connection_string: String,
+    /// Roll up and delete old population snapshots, they are kept forever when not set
+    pub retention: Option<RetentionConfig>,
+    #[serde(default)]
+    pub population_layout: PopulationLayout,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -127,4 +290,12 @@ impl Settings {
         // You can deserialize (and thus freeze) the entire configuration as
         s.try_deserialize()
     }
+
+    /// Override the configuration with the command line arguments
+    #[cfg(feature = "census")]
+    pub fn apply(&mut self, arguments: Arguments) {
+        self.census.recording = arguments.record.or_else(|| self.census.recording.take());
+        self.census.replay = arguments.replay;
+        self.census.replay_speed = arguments.replay_speed.or(self.census.replay_speed);
+    }
 }
@@ -1,6 +1,11 @@
+use crate::storage::configuration::PopulationLayout;
 use sqlx::{error::Error, postgres::PgPoolOptions, Pool, Postgres};
+use tracing::info;
 
-pub async fn create(connection_string: &str) -> Result<Pool<Postgres>, Error> {
+pub async fn create(
+    connection_string: &str,
+    population_layout: PopulationLayout,
+) -> Result<Pool<Postgres>, Error> {
     let pool = PgPoolOptions::new()
         .max_connections(5)
         .max_lifetime(std::time::Duration::from_secs(10))
@@ -8,5 +13,83 @@ pub async fn create(connection_string: &str) -> Result<Pool<Postgres>, Error> {
         .await?;
 
     sqlx::migrate!().run(&pool.clone()).await?;
+    if population_layout.is_flat() {
+        backfill_population_snapshot(&pool).await?;
+    }
     Ok(pool)
 }
+
+/// Copy the snapshots that were stored while the flat layout wasn't selected from the normalized
+/// levels to `population_snapshot`
+///
+/// # Returns
+///
+/// * `Ok(u64)` - The number of loadout rows copied
+/// * `Err(sqlx::Error)` - The error returned by sqlx
+pub async fn backfill_population_snapshot(pool: &Pool<Postgres>) -> Result<u64, Error> {
+    let copied = sqlx::query!(
+        "INSERT INTO population_snapshot
+            (population_id, timestamp, world_id, zone_id, instance_id, team_id, loadout_id, amount)
+        SELECT *
+        FROM population_loadout pl
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM population_snapshot ps
+            WHERE ps.world_id = pl.world_id AND ps.population_id = pl.population_id
+        )"
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    if copied > 0 {
+        info!("Copied {copied} loadout rows of earlier snapshots to population_snapshot");
+    }
+    Ok(copied)
+}
+
+#[cfg(all(test, feature = "census"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::active_players::snapshot::store_pop;
+    use crate::census::constants::{DefinitionID, Faction, InstanceID, Loadout, WorldID};
+    use crate::controllers::population::WorldBreakdown;
+    use sqlx::PgPool;
+    use std::collections::HashMap;
+
+    async fn store_snapshot(db_pool: &PgPool, population_layout: PopulationLayout) {
+        let teams = HashMap::from([(Faction::VS, HashMap::from([(Loadout::VSMAX, 3)]))]);
+        let instances = HashMap::from([(InstanceID(0), teams)]);
+        let loadout_breakdown: WorldBreakdown = HashMap::from([(
+            WorldID::Miller,
+            HashMap::from([(DefinitionID(2), instances)]),
+        )]);
+        store_pop(
+            &loadout_breakdown,
+            &HashMap::new(),
+            &HashMap::new(),
+            population_layout,
+            db_pool,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    async fn test_backfill_copies_missing_snapshots_once(db_pool: PgPool) {
+        store_snapshot(&db_pool, PopulationLayout::Normalized).await;
+        store_snapshot(&db_pool, PopulationLayout::Flat).await;
+        store_snapshot(&db_pool, PopulationLayout::Normalized).await;
+
+        assert_eq!(backfill_population_snapshot(&db_pool).await.unwrap(), 2);
+        assert_eq!(backfill_population_snapshot(&db_pool).await.unwrap(), 0);
+
+        let amounts: Vec<i16> =
+            sqlx::query_scalar("SELECT amount FROM population_snapshot ORDER BY population_id")
+                .fetch_all(&db_pool)
+                .await
+                .unwrap();
+        assert_eq!(amounts, vec![3, 3, 3]);
+    }
+}
@@ -1,3 +1,5 @@
+#[cfg(feature = "census")]
+pub mod arguments;
 pub mod configuration;
 
 #[cfg(feature = "database")]
@@ -1,4 +1,10 @@
 #[cfg(feature = "census_api")]
+use crate::census::constants::WorldID;
+#[cfg(feature = "census_api")]
+use crate::census::server_health::{self, ActiveSubscription, ServerHealthDb};
+#[cfg(feature = "census_api")]
+use crate::census::territory::{territories, TerritoryDb, ZoneTerritory};
+#[cfg(feature = "census_api")]
 use crate::startup::DbState;
 #[cfg(feature = "census_api")]
 use crate::web::State;
@@ -18,13 +24,39 @@ use thiserror::Error;
 use utoipa::ToSchema;
 
 #[cfg(feature = "census_api")]
-use crate::controllers::population::{get_current_tree, PopulationApiResponse, ZoneBreakdown};
+use crate::controllers::facility::{self, FacilityCapture};
+#[cfg(feature = "census_api")]
+use crate::controllers::kill_stats::{get_current_kill_stats, FactionKills};
+#[cfg(feature = "census_api")]
+use crate::controllers::metagame::{self, Alert, ContinentStatus};
+#[cfg(feature = "census_api")]
+use crate::controllers::outfit::{get_current_outfits, PopOutfit};
+#[cfg(feature = "census_api")]
+use crate::controllers::population::{
+    get_current_tree, get_current_vehicles, PopVehicleZone, PopulationApiResponse, ZoneBreakdown,
+};
+#[cfg(feature = "census_api")]
+use crate::controllers::population_history::{self, HistoryGroup, PopHistory};
+#[cfg(feature = "census_api")]
+use chrono::{DateTime, Duration, Utc};
+#[cfg(feature = "census_api")]
+use std::str::FromStr;
+#[cfg(feature = "census_api")]
+use tracing::error;
+
+/// The number of captures returned by `/captures`
+#[cfg(feature = "census_api")]
+const RECENT_CAPTURES: i64 = 50;
 
 #[derive(Error, Debug, Serialize, ToSchema)]
 #[cfg(feature = "census_api")]
 pub enum Error {
     #[error("No data available")]
     NoDataAvailable,
+    #[error("Failed to get data from the database")]
+    Database,
+    #[error("Invalid parameter: {0}")]
+    InvalidParameter(String),
 }
 
 #[derive(Serialize, ToSchema)]
@@ -41,6 +73,24 @@ pub enum PossibleResults {
     PopResult(PopulationApiResponse),
     #[serde(rename = "zone")]
     ZoneResult(ZoneBreakdown),
+    #[serde(rename = "alerts")]
+    AlertsResult(Vec<Alert>),
+    #[serde(rename = "continents")]
+    ContinentsResult(Vec<ContinentStatus>),
+    #[serde(rename = "territory")]
+    TerritoryResult(Vec<ZoneTerritory>),
+    #[serde(rename = "captures")]
+    CapturesResult(Vec<FacilityCapture>),
+    #[serde(rename = "kills")]
+    KillsResult(Vec<FactionKills>),
+    #[serde(rename = "vehicles")]
+    VehiclesResult(Vec<PopVehicleZone>),
+    #[serde(rename = "outfits")]
+    OutfitsResult(Vec<PopOutfit>),
+    #[serde(rename = "subscriptions")]
+    SubscriptionsResult(Vec<ActiveSubscription>),
+    #[serde(rename = "history")]
+    HistoryResult(PopHistory),
     #[serde(rename = "error")]
     Error(Error),
 }
@@ -52,21 +102,25 @@ pub enum PossibleResults {
 (status = 400, description = "Bad request", body = Error, example = json ! (Error::NoDataAvailable)),
     )
 )]
-#[get("/population?<world>&<zone>&<team>&<loadout>")]
+#[get("/population?<world>&<zone>&<team>&<loadout>&<environment>")]
 #[cfg(feature = "census_api")]
 pub async fn population(
     world: Option<Vec<i32>>,
     zone: Option<Vec<i32>>,
     team: Option<Vec<i16>>,
     loadout: Option<Vec<i16>>,
+    environment: Option<Vec<String>>,
     db_pool_state: &State<DbState>,
+    server_health_state: &State<ServerHealthDb>,
 ) -> Result<Json<Response>, BadRequest<Json<Response>>> {
-    let Some(result) = get_current_tree(
+    let Some(mut result) = get_current_tree(
         &db_pool_state.pool,
+        db_pool_state.population_layout,
         world.as_deref(),
         zone.as_deref(),
         team.as_deref(),
         loadout.as_deref(),
+        environment.as_deref(),
     )
     .await
     else {
@@ -77,6 +131,8 @@ pub async fn population(
         return Err(BadRequest(Json(response)));
     };
 
+    result.event_streams = server_health::event_streams(server_health_state);
+
     let response = Response {
         result: PossibleResults::PopResult(result),
     };
@@ -84,8 +140,280 @@ pub async fn population(
     Ok(Json(response))
 }
 
+#[cfg(feature = "census_api")]
+fn database_error(e: &sqlx::Error) -> BadRequest<Json<Response>> {
+    error!("Failed to get data from the database: {e}");
+
+    BadRequest(Json(Response {
+        result: PossibleResults::Error(Error::Database),
+    }))
+}
+
+#[cfg(feature = "census_api")]
+fn invalid_parameter(message: &str) -> BadRequest<Json<Response>> {
+    BadRequest(Json(Response {
+        result: PossibleResults::Error(Error::InvalidParameter(message.to_owned())),
+    }))
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+(status = 200, description = "Successful response", body = Response),
+(status = 400, description = "Bad request", body = Error, example = json ! (Error::InvalidParameter("bucket".to_owned()))),
+    )
+)]
+#[get("/population/history?<world>&<zone>&<from>&<to>&<bucket>&<group>")]
+#[cfg(feature = "census_api")]
+pub async fn history(
+    world: Option<Vec<i32>>,
+    zone: Option<Vec<i32>>,
+    from: Option<i64>,
+    to: Option<i64>,
+    bucket: Option<i64>,
+    group: Option<String>,
+    db_pool_state: &State<DbState>,
+) -> Result<Json<Response>, BadRequest<Json<Response>>> {
+    let to = match to {
+        Some(to) => DateTime::from_timestamp(to, 0).ok_or_else(|| invalid_parameter("to"))?,
+        None => Utc::now(),
+    };
+    let from = match from {
+        Some(from) => DateTime::from_timestamp(from, 0).ok_or_else(|| invalid_parameter("from"))?,
+        None => to - Duration::days(1),
+    };
+    if from >= to {
+        return Err(invalid_parameter("from must be before to"));
+    }
+
+    let group = match group {
+        Some(group) => HistoryGroup::from_str(&group).map_err(|_| invalid_parameter("group"))?,
+        None => HistoryGroup::default(),
+    };
+    let bucket_seconds = population_history::bucket_seconds((to - from).num_seconds(), bucket)
+        .ok_or_else(|| {
+            invalid_parameter(&format!(
+                "bucket must be at least {} seconds and return at most {} buckets",
+                population_history::MIN_BUCKET_SECONDS,
+                population_history::MAX_BUCKETS
+            ))
+        })?;
+
+    let history = population_history::get_history(
+        &db_pool_state.pool,
+        db_pool_state.population_layout,
+        world.as_deref(),
+        zone.as_deref(),
+        from.naive_utc()..to.naive_utc(),
+        bucket_seconds,
+        group,
+    )
+    .await
+    .map_err(|e| database_error(&e))?;
+
+    Ok(Json(Response {
+        result: PossibleResults::HistoryResult(history),
+    }))
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+(status = 200, description = "Successful response", body = Response),
+(status = 400, description = "Bad request", body = Error, example = json ! (Error::Database)),
+    )
+)]
+#[get("/alerts?<world>")]
+#[cfg(feature = "census_api")]
+pub async fn alerts(
+    world: Option<Vec<i32>>,
+    db_pool_state: &State<DbState>,
+) -> Result<Json<Response>, BadRequest<Json<Response>>> {
+    let alerts = metagame::get_current_alerts(&db_pool_state.pool, world.as_deref())
+        .await
+        .map_err(|e| database_error(&e))?;
+
+    Ok(Json(Response {
+        result: PossibleResults::AlertsResult(alerts),
+    }))
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+(status = 200, description = "Successful response", body = Response),
+(status = 400, description = "Bad request", body = Error, example = json ! (Error::Database)),
+    )
+)]
+#[get("/continents?<world>")]
+#[cfg(feature = "census_api")]
+pub async fn continents(
+    world: Option<Vec<i32>>,
+    db_pool_state: &State<DbState>,
+) -> Result<Json<Response>, BadRequest<Json<Response>>> {
+    let continents = metagame::get_continent_status(&db_pool_state.pool, world.as_deref())
+        .await
+        .map_err(|e| database_error(&e))?;
+
+    Ok(Json(Response {
+        result: PossibleResults::ContinentsResult(continents),
+    }))
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+(status = 200, description = "Successful response", body = Response),
+    )
+)]
+#[get("/territory?<world>")]
+#[cfg(feature = "census_api")]
+pub fn territory(world: Option<Vec<i32>>, territory_state: &State<TerritoryDb>) -> Json<Response> {
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    let worlds: Option<Vec<WorldID>> = world.map(|worlds| {
+        worlds
+            .into_iter()
+            .filter_map(|world| WorldID::try_from(world as u16).ok())
+            .filter(|world| *world != WorldID::Unknown)
+            .collect()
+    });
+
+    Json(Response {
+        result: PossibleResults::TerritoryResult(territories(territory_state, worlds.as_deref())),
+    })
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+(status = 200, description = "Successful response", body = Response),
+(status = 400, description = "Bad request", body = Error, example = json ! (Error::Database)),
+    )
+)]
+#[get("/captures?<world>")]
+#[cfg(feature = "census_api")]
+pub async fn captures(
+    world: Option<Vec<i32>>,
+    db_pool_state: &State<DbState>,
+) -> Result<Json<Response>, BadRequest<Json<Response>>> {
+    let captures =
+        facility::get_recent_captures(&db_pool_state.pool, world.as_deref(), RECENT_CAPTURES)
+            .await
+            .map_err(|e| database_error(&e))?;
+
+    Ok(Json(Response {
+        result: PossibleResults::CapturesResult(captures),
+    }))
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+(status = 200, description = "Successful response", body = Response),
+(status = 400, description = "Bad request", body = Error, example = json ! (Error::Database)),
+    )
+)]
+#[get("/kills?<world>")]
+#[cfg(feature = "census_api")]
+pub async fn kills(
+    world: Option<Vec<i32>>,
+    db_pool_state: &State<DbState>,
+) -> Result<Json<Response>, BadRequest<Json<Response>>> {
+    let kills = get_current_kill_stats(
+        &db_pool_state.pool,
+        db_pool_state.population_layout,
+        world.as_deref(),
+    )
+    .await
+    .map_err(|e| database_error(&e))?;
+
+    Ok(Json(Response {
+        result: PossibleResults::KillsResult(kills),
+    }))
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+(status = 200, description = "Successful response", body = Response),
+(status = 400, description = "Bad request", body = Error, example = json ! (Error::Database)),
+    )
+)]
+#[get("/vehicles?<world>&<zone>")]
+#[cfg(feature = "census_api")]
+pub async fn vehicles(
+    world: Option<Vec<i32>>,
+    zone: Option<Vec<i32>>,
+    db_pool_state: &State<DbState>,
+) -> Result<Json<Response>, BadRequest<Json<Response>>> {
+    let vehicles = get_current_vehicles(&db_pool_state.pool, world.as_deref(), zone.as_deref())
+        .await
+        .map_err(|e| database_error(&e))?;
+
+    Ok(Json(Response {
+        result: PossibleResults::VehiclesResult(vehicles),
+    }))
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+(status = 200, description = "Successful response", body = Response),
+(status = 400, description = "Bad request", body = Error, example = json ! (Error::Database)),
+    )
+)]
+#[get("/outfits?<world>&<zone>&<outfit>")]
+#[cfg(feature = "census_api")]
+pub async fn outfits(
+    world: Option<Vec<i32>>,
+    zone: Option<Vec<i32>>,
+    outfit: Option<Vec<i64>>,
+    db_pool_state: &State<DbState>,
+) -> Result<Json<Response>, BadRequest<Json<Response>>> {
+    let outfits = get_current_outfits(
+        &db_pool_state.pool,
+        world.as_deref(),
+        zone.as_deref(),
+        outfit.as_deref(),
+    )
+    .await
+    .map_err(|e| database_error(&e))?;
+
+    Ok(Json(Response {
+        result: PossibleResults::OutfitsResult(outfits),
+    }))
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+(status = 200, description = "Successful response", body = Response),
+    )
+)]
+#[get("/subscriptions")]
+#[cfg(feature = "census_api")]
+pub fn subscriptions(server_health_state: &State<ServerHealthDb>) -> Json<Response> {
+    Json(Response {
+        result: PossibleResults::SubscriptionsResult(server_health::subscriptions(
+            server_health_state,
+        )),
+    })
+}
+
 #[allow(clippy::no_effect_underscore_binding)]
 #[cfg(feature = "census_api")]
 pub fn routes() -> Vec<rocket::Route> {
-    routes![population]
+    routes![
+        population,
+        history,
+        alerts,
+        continents,
+        territory,
+        captures,
+        kills,
+        vehicles,
+        outfits,
+        subscriptions
+    ]
 }